@@ -0,0 +1,89 @@
+//! Compares the in-process rate limit backends.
+//!
+//! Run with `cargo bench --bench rate_limit`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::runtime::Runtime;
+
+use noteflow_backend::middleware::{GcraRateLimiter, InMemoryRateLimiter, RateLimitBackend};
+
+const LIMIT: u32 = 100;
+const WINDOW: Duration = Duration::from_secs(60);
+const TASKS: usize = 8;
+const CHECKS_PER_TASK: usize = 1_000;
+
+fn backends() -> Vec<(&'static str, Arc<dyn RateLimitBackend>)> {
+    vec![
+        ("sliding_log", Arc::new(InMemoryRateLimiter::new())),
+        ("gcra", Arc::new(GcraRateLimiter::new())),
+    ]
+}
+
+/// One hot key, mostly rejected once the budget is spent
+fn single_key(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("single_key");
+    for (name, backend) in backends() {
+        group.bench_function(name, |b| {
+            b.to_async(&rt)
+                .iter(|| async { backend.check("ip:10.0.0.1", LIMIT, WINDOW, 1).await })
+        });
+    }
+    group.finish();
+}
+
+/// Many distinct keys, as with a large anonymous client population
+fn many_keys(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let keys: Vec<String> = (0..10_000)
+        .map(|i| format!("ip:10.0.{}.{}", i / 256, i % 256))
+        .collect();
+    let mut group = c.benchmark_group("many_keys");
+    for (name, backend) in backends() {
+        let mut i = 0;
+        group.bench_function(name, |b| {
+            b.to_async(&rt).iter(|| {
+                i = (i + 1) % keys.len();
+                let key = &keys[i];
+                let backend = &backend;
+                async move { backend.check(key, LIMIT, WINDOW, 1).await }
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Concurrent tasks on distinct keys; shows lock contention between keys
+fn contended(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("contended");
+    for (name, backend) in backends() {
+        group.bench_with_input(BenchmarkId::new(name, TASKS), &TASKS, |b, &tasks| {
+            b.to_async(&rt).iter(|| {
+                let backend = backend.clone();
+                async move {
+                    let handles: Vec<_> = (0..tasks)
+                        .map(|t| {
+                            let backend = backend.clone();
+                            tokio::spawn(async move {
+                                let key = format!("user:{}", t);
+                                for _ in 0..CHECKS_PER_TASK {
+                                    let _ = backend.check(&key, LIMIT, WINDOW, 1).await;
+                                }
+                            })
+                        })
+                        .collect();
+                    for handle in handles {
+                        handle.await.unwrap();
+                    }
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, single_key, many_keys, contended);
+criterion_main!(benches);
@@ -0,0 +1,2 @@
+-- Allow operators to disable accounts without deleting their data
+ALTER TABLE users ADD COLUMN IF NOT EXISTS disabled_at TIMESTAMPTZ;
@@ -0,0 +1,4 @@
+-- Keyset pagination over a user's live notes, newest edits first
+CREATE INDEX IF NOT EXISTS idx_notes_user_updated_id
+    ON notes(user_id, updated_at DESC, id DESC)
+    WHERE is_deleted = false;
@@ -0,0 +1,8 @@
+-- Keyset pagination when listing by creation time or title
+CREATE INDEX IF NOT EXISTS idx_notes_user_created_id
+    ON notes(user_id, created_at DESC, id DESC)
+    WHERE is_deleted = false;
+
+CREATE INDEX IF NOT EXISTS idx_notes_user_title_id
+    ON notes(user_id, lower(title), id)
+    WHERE is_deleted = false;
@@ -0,0 +1,57 @@
+-- Nested notebooks (folders) for organizing notes, with manual ordering
+CREATE TABLE IF NOT EXISTS notebooks (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- NULL for top-level notebooks; only empty notebooks may be deleted
+    parent_id UUID REFERENCES notebooks(id) ON DELETE RESTRICT,
+    name VARCHAR(100) NOT NULL,
+    position INTEGER NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK (parent_id <> id)
+);
+
+CREATE INDEX idx_notebooks_user_parent ON notebooks(user_id, parent_id, position);
+
+-- Trashed notes keep their notebook until it is deleted, then fall back to the top level
+ALTER TABLE notes ADD COLUMN IF NOT EXISTS notebook_id UUID REFERENCES notebooks(id) ON DELETE SET NULL;
+ALTER TABLE notes ADD COLUMN IF NOT EXISTS position INTEGER NOT NULL DEFAULT 0;
+
+CREATE INDEX IF NOT EXISTS idx_notes_user_notebook_position
+    ON notes(user_id, notebook_id, position, id)
+    WHERE is_deleted = false;
+
+-- Filing and reordering are not edits: moving a note shifts its siblings,
+-- and deleting a notebook refiles its trashed notes, whose updated_at marks
+-- when they were trashed. Changes only to the columns named as trigger
+-- arguments leave updated_at alone.
+CREATE OR REPLACE FUNCTION update_updated_at_ignoring()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF to_jsonb(NEW) - TG_ARGV - 'updated_at' = to_jsonb(OLD) - TG_ARGV - 'updated_at' THEN
+        RETURN NEW;
+    END IF;
+    NEW.updated_at = NOW();
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS update_notes_updated_at ON notes;
+CREATE TRIGGER update_notes_updated_at
+    BEFORE UPDATE ON notes
+    FOR EACH ROW
+    EXECUTE FUNCTION update_updated_at_ignoring('notebook_id', 'position');
+
+CREATE TRIGGER update_notebooks_updated_at
+    BEFORE UPDATE ON notebooks
+    FOR EACH ROW
+    EXECUTE FUNCTION update_updated_at_ignoring('position');
+
+-- Existing notes keep their creation order at the top level
+UPDATE notes n
+SET position = ordered.rank - 1
+FROM (
+    SELECT id, row_number() OVER (PARTITION BY user_id ORDER BY created_at, id) AS rank
+    FROM notes
+) ordered
+WHERE n.id = ordered.id;
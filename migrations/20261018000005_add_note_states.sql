@@ -0,0 +1,36 @@
+-- Pinned notes lead lists, archived ones are left out of them by default,
+-- and favorites can be filtered on
+ALTER TABLE notes ADD COLUMN IF NOT EXISTS is_pinned BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE notes ADD COLUMN IF NOT EXISTS is_archived BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE notes ADD COLUMN IF NOT EXISTS is_favorite BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Pinning, archiving and starring a note are not edits either
+DROP TRIGGER IF EXISTS update_notes_updated_at ON notes;
+CREATE TRIGGER update_notes_updated_at
+    BEFORE UPDATE ON notes
+    FOR EACH ROW
+    EXECUTE FUNCTION update_updated_at_ignoring(
+        'notebook_id', 'position', 'is_pinned', 'is_archived', 'is_favorite'
+    );
+
+-- Every list orders pinned notes first, so the keyset indexes lead with the
+-- pin; they serve each sort's default direction
+DROP INDEX IF EXISTS idx_notes_user_updated_id;
+CREATE INDEX idx_notes_user_updated_id
+    ON notes(user_id, is_pinned DESC, updated_at DESC, id DESC)
+    WHERE is_deleted = false;
+
+DROP INDEX IF EXISTS idx_notes_user_created_id;
+CREATE INDEX idx_notes_user_created_id
+    ON notes(user_id, is_pinned DESC, created_at DESC, id DESC)
+    WHERE is_deleted = false;
+
+DROP INDEX IF EXISTS idx_notes_user_title_id;
+CREATE INDEX idx_notes_user_title_id
+    ON notes(user_id, is_pinned DESC, lower(title), id)
+    WHERE is_deleted = false;
+
+DROP INDEX IF EXISTS idx_notes_user_notebook_position;
+CREATE INDEX idx_notes_user_notebook_position
+    ON notes(user_id, notebook_id, is_pinned DESC, position, id)
+    WHERE is_deleted = false;
@@ -0,0 +1,300 @@
+//! Operator tooling for NoteFlow: user management, data maintenance,
+//! migrations and configuration checks.
+
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Instant;
+use tracing_subscriber::EnvFilter;
+use validator::Validate;
+
+use noteflow_backend::{
+    config::{redact_url, AuthCacheBackendKind, Config},
+    db::{create_pool, create_redis_client, migrations, run_migrations, RedisManager},
+    models::{
+        note::NoteQueryParams,
+        user::{RegisterRequest, ResetPasswordRequest},
+    },
+    services::{AuthService, NoteService, UserCache},
+    utils::{errors::AppError, jwt::JwtManager},
+};
+
+#[derive(Parser)]
+#[command(name = "noteflow-admin", version, about = "NoteFlow administration")]
+struct Cli {
+    /// TOML config file; environment variables override its values
+    #[arg(long, global = true, env = "CONFIG_FILE")]
+    config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create a user account
+    CreateUser {
+        email: String,
+        #[arg(long)]
+        display_name: String,
+        /// Read from NOTEFLOW_ADMIN_PASSWORD if omitted, to keep it out of shell history
+        #[arg(long, env = "NOTEFLOW_ADMIN_PASSWORD", hide_env_values = true)]
+        password: String,
+    },
+    /// Block a user from logging in and revoke their active tokens
+    DisableUser { email: String },
+    /// Re-enable a disabled user
+    EnableUser { email: String },
+    /// Set a new password for a user
+    ResetPassword {
+        email: String,
+        /// Read from NOTEFLOW_ADMIN_PASSWORD if omitted, to keep it out of shell history
+        #[arg(long, env = "NOTEFLOW_ADMIN_PASSWORD", hide_env_values = true)]
+        password: String,
+    },
+    /// List a user's notes, most recently updated first
+    ListNotes {
+        email: String,
+        #[arg(long, default_value_t = 1)]
+        page: i64,
+        #[arg(long, default_value_t = 20)]
+        limit: i64,
+    },
+    /// Permanently delete soft-deleted notes
+    PurgeDeleted {
+        /// Only purge notes deleted at least this many days ago
+        #[arg(long, default_value_t = 30)]
+        older_than_days: i64,
+        /// Report how many notes would be purged without deleting them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Apply pending migrations
+    Migrate {
+        /// List pending migrations without applying them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Print the effective configuration and check connectivity
+    Config,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    match run(cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
+    dotenvy::dotenv().ok();
+    let config = Config::load(cli.config.as_deref())?;
+
+    // Keep output to command results; opt into more with RUST_LOG
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| "warn".into()))
+        .init();
+
+    if let Command::Config = cli.command {
+        return diagnostics(&config).await;
+    }
+
+    let pool = create_pool(&config.database_url, 2).await?;
+    let jwt_manager = Arc::new(JwtManager::new(
+        config.jwt_secret.clone(),
+        config.jwt_access_expiration,
+        config.jwt_refresh_expiration,
+    ));
+    let mut auth_service = AuthService::new(pool.clone(), jwt_manager);
+    // A shared auth cache must hear about account changes made here; a
+    // per-process one on the servers simply expires
+    if config.auth_cache_ttl_secs > 0 && config.auth_cache_backend == AuthCacheBackendKind::Redis {
+        let redis = create_redis_client(&config.redis_url).await?;
+        auth_service = auth_service.with_user_cache(UserCache::redis(
+            RedisManager::new(redis),
+            config.auth_cache_ttl_secs,
+        ));
+    }
+    let note_service = NoteService::new(pool.clone(), config.clone());
+
+    match cli.command {
+        Command::CreateUser {
+            email,
+            display_name,
+            password,
+        } => {
+            let req = RegisterRequest {
+                email,
+                password,
+                display_name,
+            };
+            req.validate().map_err(AppError::from)?;
+            let user = auth_service.create_user(&req).await?;
+            println!("Created user {} ({})", user.email, user.id);
+        }
+        Command::DisableUser { email } => {
+            let user = auth_service.set_disabled(&email, true).await?;
+            println!("Disabled user {} ({})", user.email, user.id);
+        }
+        Command::EnableUser { email } => {
+            let user = auth_service.set_disabled(&email, false).await?;
+            println!("Enabled user {} ({})", user.email, user.id);
+        }
+        Command::ResetPassword { email, password } => {
+            let req = ResetPasswordRequest { password };
+            req.validate().map_err(AppError::from)?;
+            auth_service.reset_password(&email, &req).await?;
+            println!("Password reset for {}", email);
+        }
+        Command::ListNotes { email, page, limit } => {
+            let user = auth_service.find_by_email(&email).await?;
+            let list = note_service
+                .list(
+                    user.id,
+                    NoteQueryParams {
+                        page: Some(page),
+                        limit: Some(limit),
+                        fields: Some("title,updated_at".to_string()),
+                        ..Default::default()
+                    },
+                )
+                .await?;
+
+            println!(
+                "{} note(s) for {}",
+                list.total.unwrap_or_default(),
+                user.email
+            );
+            for note in list.notes {
+                println!(
+                    "{}  {}  {}",
+                    note.id,
+                    note.updated_at.unwrap_or_default().format("%Y-%m-%d %H:%M"),
+                    note.title.unwrap_or_default()
+                );
+            }
+        }
+        Command::PurgeDeleted {
+            older_than_days,
+            dry_run,
+        } => {
+            let older_than = chrono::Duration::days(older_than_days.max(0));
+            let count = note_service.purge_deleted(older_than, dry_run).await?;
+            if dry_run {
+                println!("Would purge {} note(s)", count);
+            } else {
+                println!("Purged {} note(s)", count);
+            }
+        }
+        Command::Migrate { dry_run: true } => migrations::print_pending(&pool).await?,
+        Command::Migrate { dry_run: false } => {
+            run_migrations(&pool).await?;
+            migrations::print_status(&pool).await?;
+        }
+        Command::Config => unreachable!("handled before connecting"),
+    }
+
+    pool.close().await;
+    Ok(())
+}
+
+/// Print settings with secrets masked, then try each backing service
+async fn diagnostics(config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    println!("Settings");
+    for (name, value) in settings(config) {
+        println!("  {:<28} {}", name, value);
+    }
+    println!();
+    println!("Connectivity");
+
+    let started = Instant::now();
+    match create_pool(&config.database_url, 1).await {
+        Ok(pool) => {
+            let pending = migrations::pending(&pool).await.map(|p| p.len());
+            println!("  postgres   ok ({:?})", started.elapsed());
+            match pending {
+                Ok(0) => println!("  migrations up to date"),
+                Ok(n) => println!("  migrations {} pending", n),
+                Err(e) => println!("  migrations unknown: {}", e),
+            }
+            pool.close().await;
+        }
+        Err(e) => println!("  postgres   FAILED: {}", e),
+    }
+
+    let started = Instant::now();
+    match create_redis_client(&config.redis_url).await {
+        Ok(_) => println!("  redis      ok ({:?})", started.elapsed()),
+        Err(e) => println!("  redis      FAILED: {}", e),
+    }
+
+    Ok(())
+}
+
+/// Each setting by its environment variable name, listed one by one rather
+/// than through `Debug` so a new secret can't be printed by accident
+fn settings(config: &Config) -> Vec<(&'static str, String)> {
+    let list = |items: Vec<String>| {
+        if items.is_empty() {
+            "(none)".to_string()
+        } else {
+            items.join(",")
+        }
+    };
+    let optional = |value: Option<String>| value.unwrap_or_else(|| "(unset)".to_string());
+
+    vec![
+        ("HOST", config.host.clone()),
+        ("PORT", config.port.to_string()),
+        ("DATABASE_URL", redact_url(&config.database_url)),
+        ("DATABASE_MAX_CONNECTIONS", config.database_max_connections.to_string()),
+        ("MIGRATE_ON_STARTUP", config.migrate_on_startup.to_string()),
+        ("REDIS_URL", redact_url(&config.redis_url)),
+        ("CACHE_ENABLED", config.cache_enabled.to_string()),
+        ("CACHE_TTL", config.cache_ttl_secs.to_string()),
+        ("AUTH_CACHE_TTL", config.auth_cache_ttl_secs.to_string()),
+        ("AUTH_CACHE_BACKEND", format!("{:?}", config.auth_cache_backend).to_lowercase()),
+        ("JWT_SECRET", "<redacted>".to_string()),
+        ("JWT_ACCESS_EXPIRATION", config.jwt_access_expiration.to_string()),
+        ("JWT_REFRESH_EXPIRATION", config.jwt_refresh_expiration.to_string()),
+        ("RATE_LIMIT_ANONYMOUS", config.rate_limit_anonymous.to_string()),
+        ("RATE_LIMIT_AUTHENTICATED", config.rate_limit_authenticated.to_string()),
+        ("RATE_LIMIT_LOGIN", config.rate_limit_login.to_string()),
+        ("RATE_LIMIT_REGISTER", config.rate_limit_register.to_string()),
+        ("RATE_LIMIT_SEARCH", config.rate_limit_search.to_string()),
+        ("RATE_LIMIT_AUTH_FAILURES", config.rate_limit_auth_failures.to_string()),
+        ("RATE_LIMIT_WRITE_COST", config.rate_limit_write_cost.to_string()),
+        ("RATE_LIMIT_BACKEND", format!("{:?}", config.rate_limit_backend).to_lowercase()),
+        (
+            "TRUSTED_PROXIES",
+            list(config.trusted_proxies.iter().map(ToString::to_string).collect()),
+        ),
+        (
+            "CORS_ALLOWED_ORIGINS",
+            list(config.cors_allowed_origins.iter().map(ToString::to_string).collect()),
+        ),
+        ("CORS_ALLOW_CREDENTIALS", config.cors_allow_credentials.to_string()),
+        (
+            "CORS_EXPOSE_HEADERS",
+            list(config.cors_expose_headers.iter().map(ToString::to_string).collect()),
+        ),
+        ("CORS_MAX_AGE", config.cors_max_age_secs.to_string()),
+        ("METRICS_ADDR", optional(config.metrics_addr.map(|a| a.to_string()))),
+        (
+            "OTEL_EXPORTER_OTLP_ENDPOINT",
+            optional(config.otel_exporter_endpoint.as_deref().map(redact_url)),
+        ),
+        ("OTEL_SERVICE_NAME", config.otel_service_name.clone()),
+        ("SHUTDOWN_TIMEOUT", config.shutdown_timeout_secs.to_string()),
+        ("MAX_NOTE_SIZE", config.max_note_size.to_string()),
+        ("MAX_NOTES_PER_USER", config.max_notes_per_user.to_string()),
+        ("MAX_COLLABORATORS_PER_NOTE", config.max_collaborators_per_note.to_string()),
+    ]
+}
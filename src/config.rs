@@ -1,65 +1,493 @@
+use axum::http::HeaderName;
+use ipnet::IpNet;
+use std::collections::{HashMap, HashSet};
 use std::env;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::str::FromStr;
 
-#[derive(Debug, Clone)]
+use crate::middleware::cors::OriginPattern;
+
+/// Shortest JWT signing secret accepted; HS256 wants at least 256 bits
+pub const MIN_JWT_SECRET_LEN: usize = 32;
+
+/// Where rate limit counters are stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitBackendKind {
+    /// Per-process sliding log; each replica enforces its own limit
+    Memory,
+    /// Per-process GCRA with constant memory per key
+    Gcra,
+    /// Shared Redis counters; limits hold across all replicas
+    Redis,
+}
+
+impl FromStr for RateLimitBackendKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "memory" => Ok(Self::Memory),
+            "gcra" => Ok(Self::Gcra),
+            "redis" => Ok(Self::Redis),
+            other => Err(format!("unknown rate limit backend: {}", other)),
+        }
+    }
+}
+
+/// Where authenticated user lookups are cached
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthCacheBackendKind {
+    /// Per process; other replicas see account changes once entries expire
+    Memory,
+    /// Shared, so invalidation reaches every replica immediately
+    Redis,
+}
+
+impl FromStr for AuthCacheBackendKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "memory" => Ok(Self::Memory),
+            "redis" => Ok(Self::Redis),
+            other => Err(format!("unknown auth cache backend: {}", other)),
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct Config {
     pub host: String,
     pub port: u16,
     pub database_url: String,
     pub database_max_connections: u32,
+    pub migrate_on_startup: bool,
     pub redis_url: String,
+    pub cache_enabled: bool,
+    pub cache_ttl_secs: u64,
+    /// 0 disables the cache
+    pub auth_cache_ttl_secs: u64,
+    pub auth_cache_backend: AuthCacheBackendKind,
     pub jwt_secret: String,
     pub jwt_access_expiration: i64,
     pub jwt_refresh_expiration: i64,
     pub rate_limit_anonymous: u32,
     pub rate_limit_authenticated: u32,
+    pub rate_limit_login: u32,
+    pub rate_limit_register: u32,
+    /// Full-text searches allowed per user per minute
+    pub rate_limit_search: u32,
+    /// Requests failing authentication allowed per IP per minute on protected routes
+    pub rate_limit_auth_failures: u32,
+    pub rate_limit_write_cost: u32,
+    pub rate_limit_backend: RateLimitBackendKind,
+    pub trusted_proxies: Vec<IpNet>,
+    pub cors_allowed_origins: Vec<OriginPattern>,
+    pub cors_allow_credentials: bool,
+    pub cors_expose_headers: Vec<HeaderName>,
+    pub cors_max_age_secs: u64,
+    /// Serve `/metrics` on this address only, instead of on the API port
+    pub metrics_addr: Option<SocketAddr>,
+    pub otel_exporter_endpoint: Option<String>,
+    pub otel_service_name: String,
+    pub shutdown_timeout_secs: u64,
     pub max_note_size: usize,
     pub max_notes_per_user: i64,
+    /// Reserved for note sharing, which doesn't exist yet; accepted but not enforced
     pub max_collaborators_per_note: usize,
 }
 
+/// Every problem found while loading configuration, reported together
+#[derive(Debug)]
+pub struct ConfigError(pub Vec<String>);
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid configuration:")?;
+        for error in &self.0 {
+            write!(f, "\n  - {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
 impl Config {
-    pub fn from_env() -> Result<Self, env::VarError> {
-        Ok(Self {
-            host: env::var("HOST").unwrap_or_else(|_| "0.0.0.0".to_string()),
-            port: env::var("PORT")
-                .unwrap_or_else(|_| "8080".to_string())
-                .parse()
-                .unwrap_or(8080),
-            database_url: env::var("DATABASE_URL")?,
-            database_max_connections: env::var("DATABASE_MAX_CONNECTIONS")
-                .unwrap_or_else(|_| "20".to_string())
-                .parse()
-                .unwrap_or(20),
-            redis_url: env::var("REDIS_URL")?,
-            jwt_secret: env::var("JWT_SECRET")?,
-            jwt_access_expiration: env::var("JWT_ACCESS_EXPIRATION")
-                .unwrap_or_else(|_| "86400".to_string())
-                .parse()
-                .unwrap_or(86400),
-            jwt_refresh_expiration: env::var("JWT_REFRESH_EXPIRATION")
-                .unwrap_or_else(|_| "604800".to_string())
-                .parse()
-                .unwrap_or(604800),
-            rate_limit_anonymous: env::var("RATE_LIMIT_ANONYMOUS")
-                .unwrap_or_else(|_| "20".to_string())
-                .parse()
-                .unwrap_or(20),
-            rate_limit_authenticated: env::var("RATE_LIMIT_AUTHENTICATED")
-                .unwrap_or_else(|_| "100".to_string())
-                .parse()
-                .unwrap_or(100),
-            max_note_size: env::var("MAX_NOTE_SIZE")
-                .unwrap_or_else(|_| "102400".to_string())
-                .parse()
-                .unwrap_or(102400),
-            max_notes_per_user: env::var("MAX_NOTES_PER_USER")
-                .unwrap_or_else(|_| "50".to_string())
-                .parse()
-                .unwrap_or(50),
-            max_collaborators_per_note: env::var("MAX_COLLABORATORS_PER_NOTE")
-                .unwrap_or_else(|_| "10".to_string())
-                .parse()
-                .unwrap_or(10),
+    /// Load configuration from environment variables only
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Self::load(None)
+    }
+
+    /// Load configuration from an optional TOML file, overridden by environment variables
+    ///
+    /// File keys are the environment variable names in lowercase, either flat
+    /// (`rate_limit_login = 5`) or grouped in tables (`[rate_limit] login = 5`).
+    /// Malformed values, unknown file keys and out-of-range settings are all
+    /// collected into one error rather than replaced by defaults.
+    pub fn load(path: Option<&Path>) -> Result<Self, ConfigError> {
+        let file = match path {
+            Some(path) => read_file(path)?,
+            None => HashMap::new(),
+        };
+        let mut source = Source::new(file);
+
+        let config = Self {
+            host: source.string("HOST", "0.0.0.0"),
+            port: source.parse("PORT", 8080),
+            database_url: source.required("DATABASE_URL"),
+            database_max_connections: source.parse("DATABASE_MAX_CONNECTIONS", 20),
+            migrate_on_startup: source.parse("MIGRATE_ON_STARTUP", true),
+            redis_url: source.required("REDIS_URL"),
+            cache_enabled: source.parse("CACHE_ENABLED", true),
+            cache_ttl_secs: source.parse("CACHE_TTL", 300),
+            auth_cache_ttl_secs: source.parse("AUTH_CACHE_TTL", 30),
+            auth_cache_backend: source.parse("AUTH_CACHE_BACKEND", AuthCacheBackendKind::Memory),
+            jwt_secret: source.required("JWT_SECRET"),
+            jwt_access_expiration: source.parse("JWT_ACCESS_EXPIRATION", 86400),
+            jwt_refresh_expiration: source.parse("JWT_REFRESH_EXPIRATION", 604800),
+            rate_limit_anonymous: source.parse("RATE_LIMIT_ANONYMOUS", 20),
+            rate_limit_authenticated: source.parse("RATE_LIMIT_AUTHENTICATED", 100),
+            rate_limit_login: source.parse("RATE_LIMIT_LOGIN", 5),
+            rate_limit_register: source.parse("RATE_LIMIT_REGISTER", 5),
+            rate_limit_search: source.parse("RATE_LIMIT_SEARCH", 30),
+            rate_limit_auth_failures: source.parse("RATE_LIMIT_AUTH_FAILURES", 10),
+            rate_limit_write_cost: source.parse("RATE_LIMIT_WRITE_COST", 2),
+            rate_limit_backend: source.parse("RATE_LIMIT_BACKEND", RateLimitBackendKind::Memory),
+            trusted_proxies: source.parse_with("TRUSTED_PROXIES", Vec::new(), parse_networks),
+            cors_allowed_origins: source.parse_with(
+                "CORS_ALLOWED_ORIGINS",
+                vec![OriginPattern::Any],
+                parse_list,
+            ),
+            cors_allow_credentials: source.parse("CORS_ALLOW_CREDENTIALS", false),
+            cors_expose_headers: source.parse_with("CORS_EXPOSE_HEADERS", Vec::new(), parse_list),
+            cors_max_age_secs: source.parse("CORS_MAX_AGE", 600),
+            metrics_addr: source.parse_with("METRICS_ADDR", None, |raw| {
+                raw.trim()
+                    .parse::<SocketAddr>()
+                    .map(Some)
+                    .map_err(|e| e.to_string())
+            }),
+            otel_exporter_endpoint: source.optional("OTEL_EXPORTER_OTLP_ENDPOINT"),
+            otel_service_name: source.string("OTEL_SERVICE_NAME", "noteflow-backend"),
+            shutdown_timeout_secs: source.parse("SHUTDOWN_TIMEOUT", 30),
+            max_note_size: source.parse("MAX_NOTE_SIZE", 102400),
+            max_notes_per_user: source.parse("MAX_NOTES_PER_USER", 50),
+            max_collaborators_per_note: source.parse("MAX_COLLABORATORS_PER_NOTE", 10),
+        };
+
+        let mut errors = source.finish();
+        config.validate(&mut errors);
+
+        if errors.is_empty() {
+            Ok(config)
+        } else {
+            Err(ConfigError(errors))
+        }
+    }
+
+    /// Range and consistency checks on parsed values
+    fn validate(&self, errors: &mut Vec<String>) {
+        let mut check = |ok: bool, message: &str| {
+            if !ok {
+                errors.push(message.to_string());
+            }
+        };
+
+        check(!self.host.trim().is_empty(), "HOST must not be empty");
+        check(self.port != 0, "PORT must be between 1 and 65535");
+        check(
+            self.database_url.is_empty()
+                || has_scheme(&self.database_url, &["postgres", "postgresql"]),
+            "DATABASE_URL must be a postgres:// URL",
+        );
+        check(
+            self.database_max_connections > 0,
+            "DATABASE_MAX_CONNECTIONS must be at least 1",
+        );
+        check(
+            self.redis_url.is_empty() || has_scheme(&self.redis_url, &["redis", "rediss"]),
+            "REDIS_URL must be a redis:// or rediss:// URL",
+        );
+        check(
+            !self.cache_enabled || self.cache_ttl_secs > 0,
+            "CACHE_TTL must be at least 1 second (set CACHE_ENABLED=false to disable caching)",
+        );
+        check(
+            self.jwt_secret.len() >= MIN_JWT_SECRET_LEN,
+            &format!(
+                "JWT_SECRET must be at least {} characters",
+                MIN_JWT_SECRET_LEN
+            ),
+        );
+        check(
+            self.jwt_access_expiration > 0,
+            "JWT_ACCESS_EXPIRATION must be positive",
+        );
+        check(
+            self.jwt_refresh_expiration >= self.jwt_access_expiration,
+            "JWT_REFRESH_EXPIRATION must not be shorter than JWT_ACCESS_EXPIRATION",
+        );
+        check(
+            self.rate_limit_anonymous > 0
+                && self.rate_limit_authenticated > 0
+                && self.rate_limit_login > 0
+                && self.rate_limit_register > 0
+                && self.rate_limit_search > 0
+                && self.rate_limit_auth_failures > 0,
+            "RATE_LIMIT_ANONYMOUS, RATE_LIMIT_AUTHENTICATED, RATE_LIMIT_LOGIN, RATE_LIMIT_REGISTER, RATE_LIMIT_SEARCH and RATE_LIMIT_AUTH_FAILURES must be at least 1",
+        );
+        check(
+            (1..=self.rate_limit_authenticated).contains(&self.rate_limit_write_cost),
+            "RATE_LIMIT_WRITE_COST must be between 1 and RATE_LIMIT_AUTHENTICATED",
+        );
+        check(
+            !(self.cors_allow_credentials
+                && self.cors_allowed_origins.contains(&OriginPattern::Any)),
+            "CORS_ALLOW_CREDENTIALS requires an explicit CORS_ALLOWED_ORIGINS list, not '*'",
+        );
+        check(
+            self.otel_exporter_endpoint
+                .as_deref()
+                .is_none_or(|e| has_scheme(e, &["http", "https"])),
+            "OTEL_EXPORTER_OTLP_ENDPOINT must be an http:// or https:// URL",
+        );
+        check(self.max_note_size > 0, "MAX_NOTE_SIZE must be positive");
+        check(
+            self.max_notes_per_user > 0,
+            "MAX_NOTES_PER_USER must be positive",
+        );
+    }
+}
+
+/// Secrets are masked so configs can be logged
+impl fmt::Debug for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Config")
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("database_url", &redact_url(&self.database_url))
+            .field("database_max_connections", &self.database_max_connections)
+            .field("migrate_on_startup", &self.migrate_on_startup)
+            .field("redis_url", &redact_url(&self.redis_url))
+            .field("cache_enabled", &self.cache_enabled)
+            .field("cache_ttl_secs", &self.cache_ttl_secs)
+            .field("auth_cache_ttl_secs", &self.auth_cache_ttl_secs)
+            .field("auth_cache_backend", &self.auth_cache_backend)
+            .field("jwt_secret", &"<redacted>")
+            .field("jwt_access_expiration", &self.jwt_access_expiration)
+            .field("jwt_refresh_expiration", &self.jwt_refresh_expiration)
+            .field("rate_limit_anonymous", &self.rate_limit_anonymous)
+            .field("rate_limit_authenticated", &self.rate_limit_authenticated)
+            .field("rate_limit_login", &self.rate_limit_login)
+            .field("rate_limit_register", &self.rate_limit_register)
+            .field("rate_limit_search", &self.rate_limit_search)
+            .field("rate_limit_auth_failures", &self.rate_limit_auth_failures)
+            .field("rate_limit_write_cost", &self.rate_limit_write_cost)
+            .field("rate_limit_backend", &self.rate_limit_backend)
+            .field("trusted_proxies", &self.trusted_proxies)
+            .field(
+                "cors_allowed_origins",
+                &self
+                    .cors_allowed_origins
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>(),
+            )
+            .field("cors_allow_credentials", &self.cors_allow_credentials)
+            .field("cors_expose_headers", &self.cors_expose_headers)
+            .field("cors_max_age_secs", &self.cors_max_age_secs)
+            .field("metrics_addr", &self.metrics_addr)
+            .field("otel_exporter_endpoint", &self.otel_exporter_endpoint)
+            .field("otel_service_name", &self.otel_service_name)
+            .field("shutdown_timeout_secs", &self.shutdown_timeout_secs)
+            .field("max_note_size", &self.max_note_size)
+            .field("max_notes_per_user", &self.max_notes_per_user)
+            .field("max_collaborators_per_note", &self.max_collaborators_per_note)
+            .finish()
+    }
+}
+
+/// Mask the password in a connection URL
+pub fn redact_url(url: &str) -> String {
+    match url::Url::parse(url) {
+        Ok(mut parsed) if parsed.password().is_some() => {
+            let _ = parsed.set_password(Some("****"));
+            parsed.to_string()
+        }
+        Ok(_) => url.to_string(),
+        Err(_) => "<invalid url>".to_string(),
+    }
+}
+
+fn has_scheme(url: &str, schemes: &[&str]) -> bool {
+    url::Url::parse(url).is_ok_and(|u| schemes.contains(&u.scheme()))
+}
+
+/// Raw setting values keyed by environment variable name, tracking which
+/// keys were read and which failed to parse
+struct Source {
+    file: HashMap<String, String>,
+    used: HashSet<String>,
+    errors: Vec<String>,
+}
+
+impl Source {
+    fn new(file: HashMap<String, String>) -> Self {
+        Self {
+            file,
+            used: HashSet::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// The environment wins over the file; empty variables count as unset
+    fn get(&mut self, key: &str) -> Option<String> {
+        let file_key = key.to_ascii_lowercase();
+        let value = env::var(key)
+            .ok()
+            .filter(|v| !v.is_empty())
+            .or_else(|| self.file.get(&file_key).cloned());
+        self.used.insert(file_key);
+        value
+    }
+
+    fn required(&mut self, key: &str) -> String {
+        self.get(key).unwrap_or_else(|| {
+            self.errors.push(format!("{} is required", key));
+            String::new()
+        })
+    }
+
+    fn string(&mut self, key: &str, default: &str) -> String {
+        self.get(key).unwrap_or_else(|| default.to_string())
+    }
+
+    fn optional(&mut self, key: &str) -> Option<String> {
+        self.get(key)
+    }
+
+    fn parse<T>(&mut self, key: &str, default: T) -> T
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        self.parse_with(key, default, |raw| {
+            raw.trim().parse::<T>().map_err(|e| e.to_string())
+        })
+    }
+
+    fn parse_with<T>(
+        &mut self,
+        key: &str,
+        default: T,
+        parse: impl FnOnce(&str) -> Result<T, String>,
+    ) -> T {
+        let Some(raw) = self.get(key) else {
+            return default;
+        };
+        parse(&raw).unwrap_or_else(|e| {
+            self.errors
+                .push(format!("{}: invalid value '{}': {}", key, raw, e));
+            default
         })
     }
-}
\ No newline at end of file
+
+    /// Errors so far, plus any file keys that no setting consumed
+    fn finish(self) -> Vec<String> {
+        let mut errors = self.errors;
+        let mut unknown: Vec<_> = self
+            .file
+            .keys()
+            .filter(|k| !self.used.contains(*k))
+            .collect();
+        unknown.sort();
+        errors.extend(
+            unknown
+                .into_iter()
+                .map(|k| format!("unknown setting '{}' in config file", k)),
+        );
+        errors
+    }
+}
+
+/// Read a TOML file into flat lowercase keys, joining table names with `_`
+fn read_file(path: &Path) -> Result<HashMap<String, String>, ConfigError> {
+    let fail = |e: &dyn fmt::Display| ConfigError(vec![format!("{}: {}", path.display(), e)]);
+
+    let text = std::fs::read_to_string(path).map_err(|e| fail(&e))?;
+    let table: toml::Table = text.parse().map_err(|e| fail(&e))?;
+
+    let mut values = HashMap::new();
+    flatten("", &table, &mut values).map_err(|e| fail(&e))?;
+    Ok(values)
+}
+
+fn flatten(
+    prefix: &str,
+    table: &toml::Table,
+    out: &mut HashMap<String, String>,
+) -> Result<(), String> {
+    for (key, value) in table {
+        let key = if prefix.is_empty() {
+            key.to_ascii_lowercase()
+        } else {
+            format!("{}_{}", prefix, key.to_ascii_lowercase())
+        };
+        match value {
+            toml::Value::Table(nested) => flatten(&key, nested, out)?,
+            other => {
+                out.insert(key.clone(), scalar(&key, other)?);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Render a TOML value the way it would be written in an environment variable
+fn scalar(key: &str, value: &toml::Value) -> Result<String, String> {
+    match value {
+        toml::Value::String(s) => Ok(s.clone()),
+        toml::Value::Integer(i) => Ok(i.to_string()),
+        toml::Value::Float(f) => Ok(f.to_string()),
+        toml::Value::Boolean(b) => Ok(b.to_string()),
+        toml::Value::Array(items) => items
+            .iter()
+            .map(|item| scalar(key, item))
+            .collect::<Result<Vec<_>, _>>()
+            .map(|items| items.join(",")),
+        _ => Err(format!("unsupported value for '{}'", key)),
+    }
+}
+
+/// Parse a comma-separated list, skipping empty entries
+fn parse_list<T>(value: &str) -> Result<Vec<T>, String>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse::<T>().map_err(|e| format!("'{}': {}", s, e)))
+        .collect()
+}
+
+/// Parse a comma-separated list of CIDRs or bare IP addresses
+fn parse_networks(value: &str) -> Result<Vec<IpNet>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            s.parse::<IpNet>()
+                .ok()
+                .or_else(|| s.parse::<IpAddr>().ok().map(IpNet::from))
+                .ok_or_else(|| format!("'{}' is not an IP address or CIDR", s))
+        })
+        .collect()
+}
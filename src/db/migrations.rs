@@ -0,0 +1,193 @@
+use sqlx::migrate::{Migrate, Migrator};
+use sqlx::postgres::{PgConnection, PgPool};
+use std::collections::HashMap;
+use std::fmt;
+use tracing::Instrument;
+
+use super::db_span;
+use crate::utils::errors::{AppError, Result};
+
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// Advisory lock held while migrating so replicas starting together apply
+/// each migration exactly once; the others wait and then find nothing pending
+const MIGRATION_LOCK_ID: i64 = 0x6e6f_7465_666c_6f77; // "noteflow"
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MigrationState {
+    Applied,
+    Pending,
+    /// Applied, but the file has changed since
+    ChecksumMismatch,
+    /// Started but did not complete; needs manual repair
+    Failed,
+}
+
+impl fmt::Display for MigrationState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // `pad` so the status table's column widths apply
+        f.pad(match self {
+            MigrationState::Applied => "applied",
+            MigrationState::Pending => "pending",
+            MigrationState::ChecksumMismatch => "checksum mismatch",
+            MigrationState::Failed => "failed",
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub state: MigrationState,
+}
+
+/// Compare the embedded migrations with what the database has applied
+///
+/// Read-only: does not create the migrations table or take the lock.
+pub async fn status(pool: &PgPool) -> Result<Vec<MigrationStatus>> {
+    let mut conn = pool.acquire().await?;
+    let applied = applied_migrations(&mut conn).await?;
+    Ok(compare(&applied))
+}
+
+/// Migrations not yet applied, in the order they would run
+pub async fn pending(pool: &PgPool) -> Result<Vec<MigrationStatus>> {
+    Ok(status(pool)
+        .await?
+        .into_iter()
+        .filter(|m| m.state == MigrationState::Pending)
+        .collect())
+}
+
+/// Print each migration's version, state and description, for the CLIs
+pub async fn print_status(pool: &PgPool) -> Result<()> {
+    for m in status(pool).await? {
+        println!("{:<16} {:<20} {}", m.version, m.state, m.description);
+    }
+    Ok(())
+}
+
+/// Print the migrations a run would apply, for the CLIs' dry runs
+pub async fn print_pending(pool: &PgPool) -> Result<()> {
+    let pending = pending(pool).await?;
+    if pending.is_empty() {
+        println!("No pending migrations");
+    }
+    for m in pending {
+        println!("would apply {} {}", m.version, m.description);
+    }
+    Ok(())
+}
+
+/// Apply every pending migration under an advisory lock
+///
+/// The lock is transaction-scoped, so it is released on commit or rollback,
+/// and when the connection closes on a panic or a cancelled startup, never
+/// left held on a pooled connection. Pending migrations are applied together
+/// in that transaction: either all of them land or none do.
+///
+/// Refuses to run if a previous migration failed or an applied migration's
+/// file has been modified. Returns the migrations that were applied.
+pub async fn run_pending(pool: &PgPool) -> Result<Vec<MigrationStatus>> {
+    let mut tx = pool.begin().await?;
+
+    tracing::debug!("Waiting for migration lock...");
+    sqlx::query("SELECT pg_advisory_xact_lock($1)")
+        .bind(MIGRATION_LOCK_ID)
+        .execute(&mut *tx)
+        .instrument(db_span!("SELECT", "pg_advisory_xact_lock"))
+        .await?;
+
+    // Dropping the transaction on error rolls it back and releases the lock
+    let applied = apply_pending(&mut tx).await?;
+    tx.commit().await?;
+
+    Ok(applied)
+}
+
+async fn apply_pending(conn: &mut PgConnection) -> Result<Vec<MigrationStatus>> {
+    conn.ensure_migrations_table().await.map_err(migrate_error)?;
+
+    let applied = applied_migrations(conn).await?;
+    let statuses = compare(&applied);
+
+    if let Some(bad) = statuses
+        .iter()
+        .find(|m| matches!(m.state, MigrationState::Failed | MigrationState::ChecksumMismatch))
+    {
+        return Err(AppError::InternalError(format!(
+            "Migration {} ({}) is in state '{}'; repair it before migrating",
+            bad.version, bad.description, bad.state
+        )));
+    }
+
+    let mut newly_applied = Vec::new();
+    for status in statuses.into_iter().filter(|m| m.state == MigrationState::Pending) {
+        let migration = MIGRATOR
+            .iter()
+            .find(|m| m.version == status.version && !m.migration_type.is_down_migration())
+            .expect("pending migration comes from the embedded set");
+
+        tracing::info!("🔧 Applying migration {} ({})", status.version, status.description);
+        let elapsed = conn.apply(migration).await.map_err(migrate_error)?;
+        tracing::info!("✅ Applied migration {} in {:?}", status.version, elapsed);
+
+        newly_applied.push(MigrationStatus {
+            state: MigrationState::Applied,
+            ..status
+        });
+    }
+
+    Ok(newly_applied)
+}
+
+/// Applied migrations keyed by version: (checksum, success)
+async fn applied_migrations(conn: &mut PgConnection) -> Result<HashMap<i64, (Vec<u8>, bool)>> {
+    let table_exists: bool =
+        sqlx::query_scalar("SELECT to_regclass('_sqlx_migrations') IS NOT NULL")
+            .fetch_one(&mut *conn)
+            .instrument(db_span!("SELECT", "to_regclass"))
+            .await?;
+
+    if !table_exists {
+        return Ok(HashMap::new());
+    }
+
+    let rows: Vec<(i64, Vec<u8>, bool)> =
+        sqlx::query_as("SELECT version, checksum, success FROM _sqlx_migrations")
+            .fetch_all(&mut *conn)
+            .instrument(db_span!("SELECT", "_sqlx_migrations"))
+            .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(version, checksum, success)| (version, (checksum, success)))
+        .collect())
+}
+
+fn compare(applied: &HashMap<i64, (Vec<u8>, bool)>) -> Vec<MigrationStatus> {
+    MIGRATOR
+        .iter()
+        .filter(|m| !m.migration_type.is_down_migration())
+        .map(|m| {
+            let state = match applied.get(&m.version) {
+                None => MigrationState::Pending,
+                Some((_, false)) => MigrationState::Failed,
+                Some((checksum, true)) if checksum.as_slice() != &*m.checksum => {
+                    MigrationState::ChecksumMismatch
+                }
+                Some(_) => MigrationState::Applied,
+            };
+            MigrationStatus {
+                version: m.version,
+                description: m.description.to_string(),
+                state,
+            }
+        })
+        .collect()
+}
+
+fn migrate_error(e: sqlx::migrate::MigrateError) -> AppError {
+    AppError::InternalError(format!("Migration failed: {}", e))
+}
@@ -1,5 +1,7 @@
+pub mod migrations;
 pub mod postgres;
 pub mod redis;
 
-pub use postgres::{create_pool, run_migrations, run_migrations_if_needed};
+pub(crate) use postgres::db_span;
+pub use postgres::{create_pool, run_migrations};
 pub use redis::{create_redis_client, RedisManager};
\ No newline at end of file
@@ -2,6 +2,34 @@ use crate::utils::errors::{AppError, Result};
 use sqlx::postgres::{PgPool, PgPoolOptions};
 use std::time::Duration;
 
+/// Span for one query, named like `SELECT notes` when exported
+///
+/// Attach it to the future running the query, e.g.
+/// `.fetch_all(&pool).instrument(db_span!("SELECT", "notes")).await`, so the
+/// span covers waiting for a connection as well as the round trip.
+macro_rules! db_span {
+    ($operation:literal) => {
+        tracing::info_span!(
+            "db.query",
+            otel.name = $operation,
+            otel.kind = "client",
+            db.system = "postgresql",
+            db.operation = $operation,
+        )
+    };
+    ($operation:literal, $table:expr) => {
+        tracing::info_span!(
+            "db.query",
+            otel.name = %format!("{} {}", $operation, $table),
+            otel.kind = "client",
+            db.system = "postgresql",
+            db.operation = $operation,
+            db.sql.table = %$table,
+        )
+    };
+}
+pub(crate) use db_span;
+
 pub async fn create_pool(database_url: &str, max_connections: u32) -> Result<PgPool> {
     PgPoolOptions::new()
         .max_connections(max_connections)
@@ -10,38 +38,16 @@ pub async fn create_pool(database_url: &str, max_connections: u32) -> Result<PgP
         .max_lifetime(Duration::from_secs(1800))
         .connect(database_url)
         .await
-        .map_err(|e| AppError::DatabaseError(e))
+        .map_err(AppError::DatabaseError)
 }
 
+/// Apply all pending migrations; see [`crate::db::migrations`]
 pub async fn run_migrations(pool: &PgPool) -> Result<()> {
-    sqlx::migrate!("./migrations")
-        .run(pool)
-        .await
-        .map_err(|e| AppError::InternalError(format!("Migration failed: {}", e)))
-}
-
-pub async fn run_migrations_if_needed(pool: &PgPool) -> Result<()> {
-    // Check if _sqlx_migrations table exists
-    let needs_migration = sqlx::query_scalar::<_, bool>(
-        "SELECT NOT EXISTS (
-            SELECT FROM pg_tables 
-            WHERE schemaname = 'public' 
-            AND tablename = '_sqlx_migrations'
-        )",
-    )
-    .fetch_one(pool)
-    .await?;
-
-    if needs_migration {
-        tracing::info!("🔧 First run detected, running migrations...");
-        sqlx::migrate!("./migrations")
-            .run(pool)
-            .await
-            .map_err(|e| AppError::InternalError(format!("Migration failed: {}", e)))?;
-        tracing::info!("✅ Migrations completed");
+    let applied = super::migrations::run_pending(pool).await?;
+    if applied.is_empty() {
+        tracing::info!("✅ Database schema is up to date");
     } else {
-        tracing::info!("✅ Database already migrated");
+        tracing::info!("✅ Applied {} migration(s)", applied.len());
     }
-
     Ok(())
 }
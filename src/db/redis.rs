@@ -2,13 +2,14 @@ use crate::utils::errors::{AppError, Result};
 use redis::{aio::ConnectionManager, Client};
 
 pub async fn create_redis_client(redis_url: &str) -> Result<ConnectionManager> {
-    let client = Client::open(redis_url).map_err(|e| AppError::RedisError(e))?;
+    let client = Client::open(redis_url).map_err(AppError::RedisError)?;
 
     ConnectionManager::new(client)
         .await
-        .map_err(|e| AppError::RedisError(e))
+        .map_err(AppError::RedisError)
 }
 
+#[derive(Clone)]
 pub struct RedisManager {
     pub conn: ConnectionManager,
 }
@@ -18,15 +19,17 @@ impl RedisManager {
         Self { conn }
     }
 
+    #[tracing::instrument(skip(self, message), fields(db.system = "redis"))]
     pub async fn publish(&mut self, channel: &str, message: &str) -> Result<()> {
         redis::cmd("PUBLISH")
             .arg(channel)
             .arg(message)
             .query_async(&mut self.conn)
             .await
-            .map_err(|e| AppError::RedisError(e))
+            .map_err(AppError::RedisError)
     }
 
+    #[tracing::instrument(skip(self, value), fields(db.system = "redis"))]
     pub async fn set_with_expiry(&mut self, key: &str, value: &str, seconds: usize) -> Result<()> {
         redis::cmd("SETEX")
             .arg(key)
@@ -34,22 +37,41 @@ impl RedisManager {
             .arg(value)
             .query_async(&mut self.conn)
             .await
-            .map_err(|e| AppError::RedisError(e))
+            .map_err(AppError::RedisError)
     }
 
+    #[tracing::instrument(skip(self), fields(db.system = "redis"))]
     pub async fn get(&mut self, key: &str) -> Result<Option<String>> {
         redis::cmd("GET")
             .arg(key)
             .query_async(&mut self.conn)
             .await
-            .map_err(|e| AppError::RedisError(e))
+            .map_err(AppError::RedisError)
     }
 
+    /// Set several keys at once, all expiring after `seconds`
+    #[tracing::instrument(skip(self, entries), fields(db.system = "redis", keys = entries.len()))]
+    pub async fn set_many_with_expiry(
+        &mut self,
+        entries: &[(String, String)],
+        seconds: usize,
+    ) -> Result<()> {
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        for (key, value) in entries {
+            pipe.cmd("SETEX").arg(key).arg(seconds).arg(value).ignore();
+        }
+        pipe.query_async(&mut self.conn)
+            .await
+            .map_err(AppError::RedisError)
+    }
+
+    #[tracing::instrument(skip(self), fields(db.system = "redis"))]
     pub async fn delete(&mut self, key: &str) -> Result<()> {
         redis::cmd("DEL")
             .arg(key)
             .query_async(&mut self.conn)
             .await
-            .map_err(|e| AppError::RedisError(e))
+            .map_err(AppError::RedisError)
     }
 }
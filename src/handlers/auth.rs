@@ -1,32 +1,78 @@
 use axum::{extract::State, http::StatusCode, Json};
 use std::sync::Arc;
-use crate::models::user::{RegisterRequest, LoginRequest, RefreshTokenRequest, AuthResponse};
+use crate::middleware::ClientIp;
+use crate::models::user::{RegisterRequest, LoginRequest, RefreshTokenRequest, AuthResponse, TokenPairResponse};
 use crate::services::AuthService;
-use crate::utils::errors::Result;
+use crate::utils::{errors::{ProblemDetails, Result}, validation::ValidatedJson};
 
+/// Create an account and return a token pair
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/register",
+    tag = "auth",
+    request_body = RegisterRequest,
+    responses(
+        (status = 201, description = "Account created", body = AuthResponse),
+        (status = 400, description = "Invalid input", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 409, description = "Email already registered", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "Rate limit exceeded", body = ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
 pub async fn register(
     State(auth_service): State<Arc<AuthService>>,
-    Json(req): Json<RegisterRequest>,
+    ClientIp(ip): ClientIp,
+    ValidatedJson(req): ValidatedJson<RegisterRequest>,
 ) -> Result<(StatusCode, Json<AuthResponse>)> {
-    let response = auth_service.register(req).await?;
+    let response = auth_service.register(req, ip).await?;
+    tracing::info!("User registered: {} from {}", response.user.id, ip);
     Ok((StatusCode::CREATED, Json(response)))
 }
 
+/// Exchange credentials for a token pair
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/login",
+    tag = "auth",
+    request_body = LoginRequest,
+    responses(
+        (status = 200, description = "Logged in", body = AuthResponse),
+        (status = 400, description = "Invalid input", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "Invalid credentials", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Account disabled", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "Rate limit exceeded", body = ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
 pub async fn login(
     State(auth_service): State<Arc<AuthService>>,
-    Json(req): Json<LoginRequest>,
+    ClientIp(ip): ClientIp,
+    ValidatedJson(req): ValidatedJson<LoginRequest>,
 ) -> Result<Json<AuthResponse>> {
-    let response = auth_service.login(req).await?;
+    // Failures are logged by the service, which knows the user without the email
+    let response = auth_service.login(req, ip).await?;
+    tracing::info!("Login succeeded: {} from {}", response.user.id, ip);
     Ok(Json(response))
 }
 
+/// Exchange a refresh token for a new token pair
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/refresh",
+    tag = "auth",
+    request_body = RefreshTokenRequest,
+    responses(
+        (status = 200, description = "New token pair", body = TokenPairResponse),
+        (status = 401, description = "Invalid or expired refresh token", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Account disabled", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "Rate limit exceeded", body = ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
 pub async fn refresh(
     State(auth_service): State<Arc<AuthService>>,
-    Json(req): Json<RefreshTokenRequest>,
-) -> Result<Json<serde_json::Value>> {
+    ValidatedJson(req): ValidatedJson<RefreshTokenRequest>,
+) -> Result<Json<TokenPairResponse>> {
     let (access_token, refresh_token) = auth_service.refresh_token(&req.refresh_token).await?;
-    Ok(Json(serde_json::json!({
-        "access_token": access_token,
-        "refresh_token": refresh_token
-    })))
+    Ok(Json(TokenPairResponse {
+        access_token,
+        refresh_token,
+    }))
 }
\ No newline at end of file
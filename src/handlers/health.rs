@@ -0,0 +1,161 @@
+use axum::{extract::State, http::StatusCode, Json};
+use redis::aio::ConnectionManager;
+use serde::Serialize;
+use sqlx::PgPool;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
+
+use crate::db::db_span;
+
+/// How long a single dependency check may take before it counts as down
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub struct HealthState {
+    pub pool: PgPool,
+    pub redis: ConnectionManager,
+    /// Redis only fails readiness when something depends on it (e.g. shared rate limits)
+    pub redis_critical: bool,
+    /// Readiness fails as soon as shutdown begins so load balancers stop routing here
+    pub shutdown: CancellationToken,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    Up,
+    Degraded,
+    Down,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DependencyHealth {
+    pub status: HealthStatus,
+    pub critical: bool,
+    pub latency_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DependencyChecks {
+    pub postgres: DependencyHealth,
+    pub redis: DependencyHealth,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReadinessResponse {
+    pub status: HealthStatus,
+    pub version: &'static str,
+    pub migration_version: Option<i64>,
+    pub checks: DependencyChecks,
+}
+
+/// Legacy health check endpoint
+pub async fn health_check() -> &'static str {
+    "OK"
+}
+
+/// Liveness: the process is up and serving requests; never touches dependencies
+pub async fn liveness() -> Json<serde_json::Value> {
+    Json(serde_json::json!({ "status": HealthStatus::Up }))
+}
+
+/// Readiness: every critical dependency answers within the timeout
+///
+/// Returns 503 when a critical dependency is down or the instance is
+/// draining, and 200 with a `degraded` status when only a non-critical
+/// dependency is down.
+pub async fn readiness(
+    State(state): State<Arc<HealthState>>,
+) -> (StatusCode, Json<ReadinessResponse>) {
+    let mut redis = state.redis.clone();
+    let (postgres, redis) = tokio::join!(
+        check(true, async {
+            sqlx::query("SELECT 1")
+                .execute(&state.pool)
+                .instrument(db_span!("SELECT"))
+                .await
+                .map(|_| ())
+        }),
+        check(state.redis_critical, async move {
+            redis::cmd("PING")
+                .query_async::<_, String>(&mut redis)
+                .await
+                .map(|_| ())
+        }),
+    );
+
+    let migration_version = sqlx::query_scalar::<_, Option<i64>>(
+        "SELECT MAX(version) FROM _sqlx_migrations WHERE success",
+    )
+    .fetch_one(&state.pool)
+    .instrument(db_span!("SELECT", "_sqlx_migrations"))
+    .await
+    .ok()
+    .flatten();
+
+    let checks = [&postgres, &redis];
+    let critical_down = checks
+        .iter()
+        .any(|c| c.critical && c.status == HealthStatus::Down);
+    let status = if critical_down || state.shutdown.is_cancelled() {
+        HealthStatus::Down
+    } else if checks.iter().any(|c| c.status == HealthStatus::Down) {
+        HealthStatus::Degraded
+    } else {
+        HealthStatus::Up
+    };
+
+    let code = if status == HealthStatus::Down {
+        StatusCode::SERVICE_UNAVAILABLE
+    } else {
+        StatusCode::OK
+    };
+
+    (
+        code,
+        Json(ReadinessResponse {
+            status,
+            version: crate::VERSION,
+            migration_version,
+            checks: DependencyChecks { postgres, redis },
+        }),
+    )
+}
+
+async fn check<E, F>(critical: bool, probe: F) -> DependencyHealth
+where
+    E: std::fmt::Display,
+    F: Future<Output = Result<(), E>>,
+{
+    let started = Instant::now();
+    let result = tokio::time::timeout(CHECK_TIMEOUT, probe).await;
+    let latency_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+    // Details go to the log only; this endpoint is unauthenticated
+    let error = match result {
+        Ok(Ok(())) => None,
+        Ok(Err(e)) => {
+            tracing::warn!("Health check failed: {}", e);
+            Some("unavailable".to_string())
+        }
+        Err(_) => {
+            tracing::warn!("Health check timed out after {:?}", CHECK_TIMEOUT);
+            Some("timed out".to_string())
+        }
+    };
+
+    DependencyHealth {
+        status: if error.is_some() {
+            HealthStatus::Down
+        } else {
+            HealthStatus::Up
+        },
+        critical,
+        latency_ms,
+        error,
+    }
+}
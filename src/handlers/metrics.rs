@@ -0,0 +1,21 @@
+use axum::{extract::State, http::header, response::IntoResponse};
+use metrics_exporter_prometheus::PrometheusHandle;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+pub struct MetricsState {
+    pub handle: PrometheusHandle,
+    pub pool: PgPool,
+}
+
+/// Prometheus scrape endpoint
+///
+/// Only renders what is already recorded; gauges needing the database are
+/// refreshed in the background by `metrics::start_sampler`.
+pub async fn metrics(State(state): State<Arc<MetricsState>>) -> impl IntoResponse {
+    crate::metrics::record_pool_size(&state.pool);
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.handle.render(),
+    )
+}
@@ -1,2 +1,5 @@
 pub mod auth;
+pub mod health;
+pub mod metrics;
+pub mod notebooks;
 pub mod notes;
\ No newline at end of file
@@ -0,0 +1,160 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    Extension,
+    Json,
+};
+use uuid::Uuid;
+use std::sync::Arc;
+use crate::models::notebook::{CreateNotebookRequest, MoveNotebookRequest, NotebookListResponse, NotebookResponse, UpdateNotebookRequest};
+use crate::models::user::AuthUser;
+use crate::services::NoteService;
+use crate::utils::{errors::{ProblemDetails, Result}, validation::{ValidatedJson, ValidatedPath}};
+
+/// List the caller's notebooks as a flat tree, parents before children
+#[utoipa::path(
+    get,
+    path = "/api/v1/notebooks",
+    tag = "notebooks",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "All notebooks", body = NotebookListResponse),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "Rate limit exceeded", body = ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
+pub async fn list_notebooks(
+    State(note_service): State<Arc<NoteService>>,
+    Extension(user): Extension<AuthUser>,
+) -> Result<Json<NotebookListResponse>> {
+    let notebooks = note_service.list_notebooks(user.id).await?;
+    Ok(Json(notebooks))
+}
+
+/// Create a notebook, optionally inside another
+#[utoipa::path(
+    post,
+    path = "/api/v1/notebooks",
+    tag = "notebooks",
+    security(("bearer_auth" = [])),
+    request_body = CreateNotebookRequest,
+    responses(
+        (status = 201, description = "Notebook created", body = NotebookResponse),
+        (status = 400, description = "Invalid input", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "Rate limit exceeded", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Parent belongs to another user", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Parent not found", body = ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
+pub async fn create_notebook(
+    State(note_service): State<Arc<NoteService>>,
+    Extension(user): Extension<AuthUser>,
+    ValidatedJson(req): ValidatedJson<CreateNotebookRequest>,
+) -> Result<(StatusCode, Json<NotebookResponse>)> {
+    let notebook = note_service.create_notebook(user.id, req).await?;
+    Ok((StatusCode::CREATED, Json(notebook)))
+}
+
+/// Get a notebook with its note count
+#[utoipa::path(
+    get,
+    path = "/api/v1/notebooks/{id}",
+    tag = "notebooks",
+    security(("bearer_auth" = [])),
+    params(("id" = Uuid, Path, description = "Notebook ID")),
+    responses(
+        (status = 200, description = "The notebook", body = NotebookResponse),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "Rate limit exceeded", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Notebook belongs to another user", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Notebook not found", body = ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
+pub async fn get_notebook(
+    State(note_service): State<Arc<NoteService>>,
+    Extension(user): Extension<AuthUser>,
+    ValidatedPath(notebook_id): ValidatedPath<Uuid>,
+) -> Result<Json<NotebookResponse>> {
+    let notebook = note_service.get_notebook(notebook_id, user.id).await?;
+    Ok(Json(notebook))
+}
+
+/// Rename a notebook
+#[utoipa::path(
+    put,
+    path = "/api/v1/notebooks/{id}",
+    tag = "notebooks",
+    security(("bearer_auth" = [])),
+    params(("id" = Uuid, Path, description = "Notebook ID")),
+    request_body = UpdateNotebookRequest,
+    responses(
+        (status = 200, description = "Updated notebook", body = NotebookResponse),
+        (status = 400, description = "Invalid input", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "Rate limit exceeded", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Notebook belongs to another user", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Notebook not found", body = ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
+pub async fn update_notebook(
+    State(note_service): State<Arc<NoteService>>,
+    Extension(user): Extension<AuthUser>,
+    ValidatedPath(notebook_id): ValidatedPath<Uuid>,
+    ValidatedJson(req): ValidatedJson<UpdateNotebookRequest>,
+) -> Result<Json<NotebookResponse>> {
+    let notebook = note_service.rename_notebook(notebook_id, user.id, req).await?;
+    Ok(Json(notebook))
+}
+
+/// Delete an empty notebook
+#[utoipa::path(
+    delete,
+    path = "/api/v1/notebooks/{id}",
+    tag = "notebooks",
+    security(("bearer_auth" = [])),
+    params(("id" = Uuid, Path, description = "Notebook ID")),
+    responses(
+        (status = 204, description = "Notebook deleted"),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "Rate limit exceeded", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Notebook belongs to another user", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Notebook not found", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 409, description = "Notebook still contains notes or notebooks", body = ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
+pub async fn delete_notebook(
+    State(note_service): State<Arc<NoteService>>,
+    Extension(user): Extension<AuthUser>,
+    ValidatedPath(notebook_id): ValidatedPath<Uuid>,
+) -> Result<StatusCode> {
+    note_service.delete_notebook(notebook_id, user.id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Move a notebook under another parent or to another position among its siblings
+#[utoipa::path(
+    post,
+    path = "/api/v1/notebooks/{id}/move",
+    tag = "notebooks",
+    security(("bearer_auth" = [])),
+    params(("id" = Uuid, Path, description = "Notebook ID")),
+    request_body = MoveNotebookRequest,
+    responses(
+        (status = 200, description = "Moved notebook", body = NotebookResponse),
+        (status = 400, description = "Invalid input, or the move would nest the notebook inside itself", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "Rate limit exceeded", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Notebook or parent belongs to another user", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Notebook or parent not found", body = ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
+pub async fn move_notebook(
+    State(note_service): State<Arc<NoteService>>,
+    Extension(user): Extension<AuthUser>,
+    ValidatedPath(notebook_id): ValidatedPath<Uuid>,
+    ValidatedJson(req): ValidatedJson<MoveNotebookRequest>,
+) -> Result<Json<NotebookResponse>> {
+    let notebook = note_service.move_notebook(notebook_id, user.id, req).await?;
+    Ok(Json(notebook))
+}
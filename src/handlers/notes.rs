@@ -1,57 +1,357 @@
 use axum::{
-    extract::{State, Path, Query},
-    http::StatusCode,
+    body::Body,
+    extract::{rejection::QueryRejection, State, Query},
+    http::{header, StatusCode},
     Extension,
     Json,
 };
+use chrono::Utc;
 use uuid::Uuid;
 use std::sync::Arc;
-use crate::models::note::{CreateNoteRequest, UpdateNoteRequest, NoteResponse, NoteListResponse, NoteQueryParams};
-use crate::models::user::User;
+use crate::models::note::{
+    BulkNoteRequest, BulkNoteResponse, BulkNoteStateRequest, BulkNoteStateResponse, CreateNoteRequest, ExportNotesRequest, ExportParams, NoteFormat, NoteFormatParams,
+    NoteListResponse, NoteQueryParams, NoteResponse, NoteStateRequest, PreviewRequest, PreviewResponse, UpdateNoteRequest,
+};
+use crate::models::notebook::MoveNoteRequest;
+use crate::models::user::AuthUser;
 use crate::services::NoteService;
-use crate::utils::errors::Result;
+use crate::utils::{errors::{ProblemDetails, Result}, validation::{ValidatedJson, ValidatedPath}};
 
+/// Create a note
+#[utoipa::path(
+    post,
+    path = "/api/v1/notes",
+    tag = "notes",
+    security(("bearer_auth" = [])),
+    request_body = CreateNoteRequest,
+    responses(
+        (status = 201, description = "Note created", body = NoteResponse),
+        (status = 400, description = "Invalid input", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "Rate limit exceeded", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Note limit reached", body = ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
 pub async fn create_note(
     State(note_service): State<Arc<NoteService>>,
-    Extension(user): Extension<User>,
-    Json(req): Json<CreateNoteRequest>,
+    Extension(user): Extension<AuthUser>,
+    ValidatedJson(req): ValidatedJson<CreateNoteRequest>,
 ) -> Result<(StatusCode, Json<NoteResponse>)> {
     let note = note_service.create(user.id, req).await?;
     Ok((StatusCode::CREATED, Json(note)))
 }
 
+/// Get a note with its tags, optionally rendered as HTML
+#[utoipa::path(
+    get,
+    path = "/api/v1/notes/{id}",
+    tag = "notes",
+    security(("bearer_auth" = [])),
+    params(("id" = Uuid, Path, description = "Note ID"), NoteFormatParams),
+    responses(
+        (status = 200, description = "The note", body = NoteResponse),
+        (status = 400, description = "Unknown format", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "Rate limit exceeded", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Note belongs to another user", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Note not found", body = ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
 pub async fn get_note(
     State(note_service): State<Arc<NoteService>>,
-    Extension(user): Extension<User>,
-    Path(note_id): Path<Uuid>,
+    Extension(user): Extension<AuthUser>,
+    ValidatedPath(note_id): ValidatedPath<Uuid>,
+    params: std::result::Result<Query<NoteFormatParams>, QueryRejection>,
 ) -> Result<Json<NoteResponse>> {
-    let note = note_service.get(note_id, user.id).await?;
+    let Query(params) = params?;
+    let mut note = note_service.get(note_id, user.id).await?;
+    if params.format == Some(NoteFormat::Html) {
+        note.html = Some(note_service.render_html(&note.content).await);
+    }
     Ok(Json(note))
 }
 
+/// Download a note as Markdown, standalone HTML, plain text or JSON
+///
+/// Markdown starts with YAML front matter holding the title, tags and timestamps.
+#[utoipa::path(
+    get,
+    path = "/api/v1/notes/{id}/export",
+    tag = "notes",
+    security(("bearer_auth" = [])),
+    params(("id" = Uuid, Path, description = "Note ID"), ExportParams),
+    responses(
+        (status = 200, description = "The note as an attachment", content(
+            (String = "text/markdown"),
+            (String = "text/html"),
+            (String = "text/plain"),
+            (NoteResponse = "application/json"),
+        )),
+        (status = 400, description = "Unknown format", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "Rate limit exceeded", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Note belongs to another user", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Note not found", body = ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
+pub async fn export_note(
+    State(note_service): State<Arc<NoteService>>,
+    Extension(user): Extension<AuthUser>,
+    ValidatedPath(note_id): ValidatedPath<Uuid>,
+    params: std::result::Result<Query<ExportParams>, QueryRejection>,
+) -> Result<([(header::HeaderName, String); 2], String)> {
+    let Query(params) = params?;
+    let file = note_service
+        .export_note(note_id, user.id, params.format.unwrap_or_default())
+        .await?;
+    let headers = [
+        (header::CONTENT_TYPE, file.content_type.to_string()),
+        (header::CONTENT_DISPOSITION, attachment(&file.name)),
+    ];
+    Ok((headers, file.content))
+}
+
+/// Download up to 100 notes, or every note with a tag, as a ZIP archive
+///
+/// The archive holds one file per note in the chosen format and is streamed
+/// as it is written.
+#[utoipa::path(
+    post,
+    path = "/api/v1/notes/export",
+    tag = "notes",
+    security(("bearer_auth" = [])),
+    request_body = ExportNotesRequest,
+    responses(
+        (status = 200, description = "ZIP archive as an attachment", content_type = "application/zip", body = Vec<u8>),
+        (status = 400, description = "Invalid input", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "Rate limit exceeded", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "A requested note was not found, or no notes have the tag", body = ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
+pub async fn export_notes(
+    State(note_service): State<Arc<NoteService>>,
+    Extension(user): Extension<AuthUser>,
+    ValidatedJson(req): ValidatedJson<ExportNotesRequest>,
+) -> Result<([(header::HeaderName, String); 2], Body)> {
+    let archive = note_service.export_notes(user.id, req).await?;
+    let name = format!("noteflow-export-{}.zip", Utc::now().format("%Y%m%d-%H%M%S"));
+    let headers = [
+        (header::CONTENT_TYPE, "application/zip".to_string()),
+        (header::CONTENT_DISPOSITION, attachment(&name)),
+    ];
+    Ok((headers, Body::from_stream(archive)))
+}
+
+/// Export file names hold no quotes or slashes, but may hold any letter:
+/// `filename*` carries them for clients that understand it, `filename` an
+/// ASCII stand-in for the rest
+fn attachment(file_name: &str) -> String {
+    let ascii: String = file_name
+        .chars()
+        .map(|c| if c.is_ascii() { c } else { '_' })
+        .collect();
+    let encoded: String = url::form_urlencoded::byte_serialize(file_name.as_bytes()).collect();
+    format!("attachment; filename=\"{}\"; filename*=UTF-8''{}", ascii, encoded)
+}
+
+/// Render Markdown as it would appear in a saved note, without saving it
+///
+/// The HTML is sanitized: scripts, event handlers and unsafe URLs are removed.
+#[utoipa::path(
+    post,
+    path = "/api/v1/notes/preview",
+    tag = "notes",
+    security(("bearer_auth" = [])),
+    request_body = PreviewRequest,
+    responses(
+        (status = 200, description = "Rendered HTML", body = PreviewResponse),
+        (status = 400, description = "Invalid input", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "Rate limit exceeded", body = ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
+pub async fn preview_note(
+    State(note_service): State<Arc<NoteService>>,
+    ValidatedJson(req): ValidatedJson<PreviewRequest>,
+) -> Result<Json<PreviewResponse>> {
+    let preview = note_service.preview(req).await?;
+    Ok(Json(preview))
+}
+
+/// List the caller's notes, pinned first, then most recently updated unless sorted otherwise
+#[utoipa::path(
+    get,
+    path = "/api/v1/notes",
+    tag = "notes",
+    security(("bearer_auth" = [])),
+    params(NoteQueryParams),
+    responses(
+        (status = 200, description = "A page of notes", body = NoteListResponse),
+        (status = 400, description = "Invalid cursor, sort, fields, notebook or filter", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "Rate limit exceeded", body = ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
 pub async fn list_notes(
     State(note_service): State<Arc<NoteService>>,
-    Extension(user): Extension<User>,
-    Query(params): Query<NoteQueryParams>,
+    Extension(user): Extension<AuthUser>,
+    params: std::result::Result<Query<NoteQueryParams>, QueryRejection>,
 ) -> Result<Json<NoteListResponse>> {
+    let Query(params) = params?;
     let notes = note_service.list(user.id, params).await?;
     Ok(Json(notes))
 }
 
+/// Update a note's title and/or content
+#[utoipa::path(
+    put,
+    path = "/api/v1/notes/{id}",
+    tag = "notes",
+    security(("bearer_auth" = [])),
+    params(("id" = Uuid, Path, description = "Note ID")),
+    request_body = UpdateNoteRequest,
+    responses(
+        (status = 200, description = "Updated note", body = NoteResponse),
+        (status = 400, description = "Invalid input", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "Rate limit exceeded", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Note belongs to another user", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Note not found", body = ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
 pub async fn update_note(
     State(note_service): State<Arc<NoteService>>,
-    Extension(user): Extension<User>,
-    Path(note_id): Path<Uuid>,
-    Json(req): Json<UpdateNoteRequest>,
+    Extension(user): Extension<AuthUser>,
+    ValidatedPath(note_id): ValidatedPath<Uuid>,
+    ValidatedJson(req): ValidatedJson<UpdateNoteRequest>,
 ) -> Result<Json<NoteResponse>> {
     let note = note_service.update(note_id, user.id, req).await?;
     Ok(Json(note))
 }
 
+/// Delete, restore, tag or untag up to 100 notes in one transaction
+///
+/// Notes that are missing or belong to someone else are skipped and reported
+/// in `results`; the rest are changed together.
+#[utoipa::path(
+    post,
+    path = "/api/v1/notes/bulk",
+    tag = "notes",
+    security(("bearer_auth" = [])),
+    request_body = BulkNoteRequest,
+    responses(
+        (status = 200, description = "Outcome for each note", body = BulkNoteResponse),
+        (status = 400, description = "Invalid input", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "Rate limit exceeded", body = ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
+pub async fn bulk_notes(
+    State(note_service): State<Arc<NoteService>>,
+    Extension(user): Extension<AuthUser>,
+    ValidatedJson(req): ValidatedJson<BulkNoteRequest>,
+) -> Result<Json<BulkNoteResponse>> {
+    let result = note_service.bulk(user.id, req).await?;
+    Ok(Json(result))
+}
+
+/// Pin, archive or favorite a note; omitted states are left unchanged
+#[utoipa::path(
+    put,
+    path = "/api/v1/notes/{id}/state",
+    tag = "notes",
+    security(("bearer_auth" = [])),
+    params(("id" = Uuid, Path, description = "Note ID")),
+    request_body = NoteStateRequest,
+    responses(
+        (status = 200, description = "Updated note", body = NoteResponse),
+        (status = 400, description = "No state given", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "Rate limit exceeded", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Note belongs to another user", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Note not found", body = ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
+pub async fn set_note_state(
+    State(note_service): State<Arc<NoteService>>,
+    Extension(user): Extension<AuthUser>,
+    ValidatedPath(note_id): ValidatedPath<Uuid>,
+    ValidatedJson(req): ValidatedJson<NoteStateRequest>,
+) -> Result<Json<NoteResponse>> {
+    let note = note_service.set_state(note_id, user.id, req).await?;
+    Ok(Json(note))
+}
+
+/// Pin, archive or favorite up to 100 notes at once
+#[utoipa::path(
+    put,
+    path = "/api/v1/notes/state",
+    tag = "notes",
+    security(("bearer_auth" = [])),
+    request_body = BulkNoteStateRequest,
+    responses(
+        (status = 200, description = "Which notes were updated", body = BulkNoteStateResponse),
+        (status = 400, description = "Invalid input or no state given", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "Rate limit exceeded", body = ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
+pub async fn bulk_set_note_state(
+    State(note_service): State<Arc<NoteService>>,
+    Extension(user): Extension<AuthUser>,
+    ValidatedJson(req): ValidatedJson<BulkNoteStateRequest>,
+) -> Result<Json<BulkNoteStateResponse>> {
+    let result = note_service.bulk_set_state(user.id, req).await?;
+    Ok(Json(result))
+}
+
+/// File a note in a notebook, or reorder it within its current one
+#[utoipa::path(
+    post,
+    path = "/api/v1/notes/{id}/move",
+    tag = "notes",
+    security(("bearer_auth" = [])),
+    params(("id" = Uuid, Path, description = "Note ID")),
+    request_body = MoveNoteRequest,
+    responses(
+        (status = 200, description = "Moved note", body = NoteResponse),
+        (status = 400, description = "Invalid input", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "Rate limit exceeded", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Note or notebook belongs to another user", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Note or notebook not found", body = ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
+pub async fn move_note(
+    State(note_service): State<Arc<NoteService>>,
+    Extension(user): Extension<AuthUser>,
+    ValidatedPath(note_id): ValidatedPath<Uuid>,
+    ValidatedJson(req): ValidatedJson<MoveNoteRequest>,
+) -> Result<Json<NoteResponse>> {
+    let note = note_service.move_note(note_id, user.id, req).await?;
+    Ok(Json(note))
+}
+
+/// Move a note to the trash
+#[utoipa::path(
+    delete,
+    path = "/api/v1/notes/{id}",
+    tag = "notes",
+    security(("bearer_auth" = [])),
+    params(("id" = Uuid, Path, description = "Note ID")),
+    responses(
+        (status = 204, description = "Note deleted"),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "Rate limit exceeded", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Note belongs to another user", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Note not found", body = ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
 pub async fn delete_note(
     State(note_service): State<Arc<NoteService>>,
-    Extension(user): Extension<User>,
-    Path(note_id): Path<Uuid>,
+    Extension(user): Extension<AuthUser>,
+    ValidatedPath(note_id): ValidatedPath<Uuid>,
 ) -> Result<StatusCode> {
     note_service.delete(note_id, user.id).await?;
     Ok(StatusCode::NO_CONTENT)
@@ -1,9 +1,13 @@
 pub mod config;
 pub mod db;
 pub mod handlers;
+pub mod metrics;
 pub mod middleware;
 pub mod models;
+pub mod routes;
 pub mod services;
+pub mod shutdown;
+pub mod telemetry;
 pub mod utils;
 
 pub use config::Config;
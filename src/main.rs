@@ -1,44 +1,117 @@
-use axum::{
-    middleware,
-    routing::{delete, get, post, put},
-    Router,
-};
+use axum::{middleware, routing::get, Extension, Router};
+use clap::{Parser, Subcommand};
+use redis::aio::ConnectionManager;
+use std::future::IntoFuture;
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
 use tower_http::{
     compression::CompressionLayer,
-    cors::{Any, CorsLayer},
-    trace::{DefaultMakeSpan, DefaultOnResponse, TraceLayer},
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    trace::{DefaultOnResponse, TraceLayer},
 };
 use tracing::Level;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use utoipa_swagger_ui::SwaggerUi;
 
 use noteflow_backend::{
-    config::Config,
-    db::{create_pool, create_redis_client, run_migrations_if_needed},
-    handlers,
-    middleware::{auth_middleware, rate_limit_middleware, start_cleanup_task, RateLimiter},
-    services::{AuthService, NoteService},
+    config::{AuthCacheBackendKind, Config, RateLimitBackendKind},
+    db::{create_pool, create_redis_client, migrations, run_migrations, RedisManager},
+    handlers::{self, health::HealthState, metrics::MetricsState},
+    metrics::{install_recorder, start_sampler},
+    middleware::{
+        auth_failure_limit_middleware, auth_middleware, cors, drain_middleware, metrics_middleware,
+        rate_limit_middleware, request_context_middleware, start_cleanup_task, GcraRateLimiter,
+        InMemoryRateLimiter, RateLimitBackend, RateLimitPolicies, RateLimiter, RedisRateLimiter,
+        TrustedProxies,
+    },
+    routes,
+    services::{AuthService, NoteCache, NoteService, UserCache},
+    shutdown,
+    telemetry::{self, make_request_span, REQUEST_ID_HEADER},
     utils::jwt::JwtManager,
 };
 
+#[derive(Parser)]
+#[command(name = "noteflow-backend", version, about = "NoteFlow API server")]
+struct Cli {
+    /// TOML config file; environment variables override its values
+    #[arg(long, global = true, env = "CONFIG_FILE")]
+    config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the HTTP server (default)
+    Serve,
+    /// Manage database migrations
+    Migrate {
+        #[command(subcommand)]
+        action: MigrateAction,
+    },
+}
+
+#[derive(Subcommand)]
+enum MigrateAction {
+    /// Apply all pending migrations
+    Up {
+        /// List pending migrations without applying them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Show applied and pending migrations
+    Status,
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Initialize structured logging
-    tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "noteflow_backend=debug,tower_http=debug,sqlx=info".into()),
-        )
-        .with(tracing_subscriber::fmt::layer())
-        .init();
-
-    tracing::info!("🚀 Starting NoteFlow Backend...");
+    let cli = Cli::parse();
 
-    // Load configuration from environment
+    // Load configuration from the optional config file and environment
     dotenvy::dotenv().ok();
-    let config = Config::from_env().expect("Failed to load configuration from environment");
+    let config = match Config::load(cli.config.as_deref()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // Initialize structured logging and optional OTLP trace export
+    let telemetry = telemetry::init(&config)?;
+
+    let result = match cli.command.unwrap_or(Command::Serve) {
+        Command::Serve => serve(config).await,
+        Command::Migrate { action } => migrate(config, action).await,
+    };
+
+    telemetry.shutdown();
+    result
+}
+
+async fn migrate(config: Config, action: MigrateAction) -> Result<(), Box<dyn std::error::Error>> {
+    let pool = create_pool(&config.database_url, 1).await?;
+
+    match action {
+        MigrateAction::Up { dry_run: true } => migrations::print_pending(&pool).await?,
+        MigrateAction::Up { dry_run: false } => run_migrations(&pool).await?,
+        MigrateAction::Status => migrations::print_status(&pool).await?,
+    }
+
+    pool.close().await;
+    Ok(())
+}
+
+async fn serve(config: Config) -> Result<(), Box<dyn std::error::Error>> {
+    tracing::info!("🚀 Starting NoteFlow Backend...");
     tracing::info!("✅ Configuration loaded");
+    tracing::debug!("{:#?}", config);
+
+    let metrics_handle = install_recorder()?;
 
     // Create database connection pool
     tracing::info!("📊 Connecting to PostgreSQL...");
@@ -48,12 +121,22 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         config.database_max_connections
     );
 
-    // Run database migrations if needed
-    run_migrations_if_needed(&pool).await?;
+    // Bring the schema up to date; replicas serialize on an advisory lock
+    if config.migrate_on_startup {
+        run_migrations(&pool).await?;
+    } else {
+        let pending = migrations::pending(&pool).await?;
+        if !pending.is_empty() {
+            tracing::warn!(
+                "⚠️ {} pending migration(s) not applied (MIGRATE_ON_STARTUP=false)",
+                pending.len()
+            );
+        }
+    }
 
     // Create Redis connection
     tracing::info!("🔴 Connecting to Redis...");
-    let _redis_conn = create_redis_client(&config.redis_url).await?;
+    let redis_conn = create_redis_client(&config.redis_url).await?;
     tracing::info!("✅ Redis connected");
 
     // Initialize JWT manager
@@ -65,111 +148,278 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     tracing::info!("🔐 JWT manager initialized");
 
     // Initialize services
-    let auth_service = Arc::new(AuthService::new(pool.clone(), jwt_manager.clone()));
-    let note_service = Arc::new(NoteService::new(pool.clone(), config.clone()));
+    let mut auth_service = AuthService::new(pool.clone(), jwt_manager.clone());
+    if config.auth_cache_ttl_secs > 0 {
+        let ttl = config.auth_cache_ttl_secs;
+        auth_service = auth_service.with_user_cache(match config.auth_cache_backend {
+            AuthCacheBackendKind::Memory => UserCache::memory(ttl),
+            AuthCacheBackendKind::Redis => {
+                UserCache::redis(RedisManager::new(redis_conn.clone()), ttl)
+            }
+        });
+        tracing::info!(
+            "✅ Auth user cache enabled ({:?}, TTL {}s)",
+            config.auth_cache_backend,
+            ttl
+        );
+    }
+    let auth_service = Arc::new(auth_service);
+    let mut note_service = NoteService::new(pool.clone(), config.clone());
+    if config.cache_enabled {
+        note_service = note_service.with_cache(NoteCache::new(
+            RedisManager::new(redis_conn.clone()),
+            config.cache_ttl_secs,
+        ));
+        tracing::info!("✅ Note cache enabled (TTL {}s)", config.cache_ttl_secs);
+    }
+    let note_service = Arc::new(note_service);
     tracing::info!("✅ Services initialized");
 
     // Initialize rate limiters
     let anonymous_rate_limiter = Arc::new(RateLimiter::new(
-        config.rate_limit_anonymous,
-        60, // 60 seconds window
+        rate_limit_backend(&config, &redis_conn, "anonymous"),
+        RateLimitPolicies::anonymous(&config),
     ));
     let authenticated_rate_limiter = Arc::new(RateLimiter::new(
-        config.rate_limit_authenticated,
-        60,
+        rate_limit_backend(&config, &redis_conn, "authenticated"),
+        RateLimitPolicies::authenticated(&config),
     ));
+    let auth_failure_rate_limiter = Arc::new(RateLimiter::new(
+        rate_limit_backend(&config, &redis_conn, "auth_failures"),
+        RateLimitPolicies::auth_failures(&config),
+    ));
+
+    // Cancelled on SIGINT/SIGTERM; background tasks and the server watch it
+    let shutdown_token = CancellationToken::new();
 
     // Start rate limiter cleanup tasks
-    start_cleanup_task(anonymous_rate_limiter.clone());
-    start_cleanup_task(authenticated_rate_limiter.clone());
-    tracing::info!("✅ Rate limiters initialized");
+    let cleanup_tasks = [
+        start_cleanup_task(anonymous_rate_limiter.clone(), shutdown_token.clone()),
+        start_cleanup_task(authenticated_rate_limiter.clone(), shutdown_token.clone()),
+        start_cleanup_task(auth_failure_rate_limiter.clone(), shutdown_token.clone()),
+    ];
+    tracing::info!(
+        "✅ Rate limiters initialized ({:?} backend)",
+        config.rate_limit_backend
+    );
+
+    // Database-backed gauges are sampled here rather than on each scrape
+    let metrics_sampler = start_sampler(pool.clone(), shutdown_token.clone());
 
     // Build public routes with /api/v1 prefix
-    let public_routes = Router::new()
-        .route("/health", get(health_check))
-        .route("/api/v1/auth/register", post(handlers::auth::register))
-        .route("/api/v1/auth/login", post(handlers::auth::login))
-        .route("/api/v1/auth/refresh", post(handlers::auth::refresh))
-        .with_state(auth_service)
+    let public_routes = Router::from(routes::auth())
+        .with_state(auth_service.clone())
         .layer(middleware::from_fn_with_state(
             anonymous_rate_limiter.clone(),
             rate_limit_middleware,
-        ));
+        ))
+        // Outermost so preflights are answered before rate limiting
+        .layer(cors::anonymous(&config));
 
     // Build protected routes with /api/v1 prefix
-    let protected_routes = Router::new()
-        .route("/api/v1/notes", get(handlers::notes::list_notes))
-        .route("/api/v1/notes", post(handlers::notes::create_note))
-        .route("/api/v1/notes/:id", get(handlers::notes::get_note))
-        .route("/api/v1/notes/:id", put(handlers::notes::update_note))
-        .route("/api/v1/notes/:id", delete(handlers::notes::delete_note))
+    let protected_routes = Router::from(routes::notes())
         .with_state(note_service)
+        // Rate limiting runs after auth so requests are keyed by user
+        .layer(middleware::from_fn_with_state(
+            authenticated_rate_limiter.clone(),
+            rate_limit_middleware,
+        ))
         .layer(middleware::from_fn_with_state(
-            (jwt_manager.clone(), pool.clone()),
+            (jwt_manager.clone(), auth_service),
             auth_middleware,
         ))
+        // Outside auth, which rejects bad tokens before the per-user limiter
         .layer(middleware::from_fn_with_state(
-            authenticated_rate_limiter.clone(),
-            rate_limit_middleware,
-        ));
+            auth_failure_rate_limiter,
+            auth_failure_limit_middleware,
+        ))
+        // Outermost so preflights are answered before auth and rate limiting
+        .layer(cors::authenticated(&config));
+
+    let trusted_proxies = Arc::new(TrustedProxies::new(config.trusted_proxies.clone()));
+    tracing::info!(
+        "✅ Trusting forwarding headers from {} proxy network(s)",
+        config.trusted_proxies.len()
+    );
+
+    // Health probes, outside rate limiting so orchestrators never get throttled;
+    // these and /metrics are for infrastructure, so they get no CORS headers
+    let health_routes = Router::new()
+        .route("/health", get(handlers::health::health_check))
+        .route("/health/live", get(handlers::health::liveness))
+        .route("/health/ready", get(handlers::health::readiness))
+        .with_state(Arc::new(HealthState {
+            pool: pool.clone(),
+            redis: redis_conn.clone(),
+            redis_critical: config.rate_limit_backend == RateLimitBackendKind::Redis,
+            shutdown: shutdown_token.clone(),
+        }));
+
+    // Prometheus scrape endpoint, outside rate limiting so scrapes never get throttled;
+    // served on its own listener when METRICS_ADDR is set
+    let metrics_routes = Router::new()
+        .route("/metrics", get(handlers::metrics::metrics))
+        .with_state(Arc::new(MetricsState {
+            handle: metrics_handle,
+            pool: pool.clone(),
+        }));
+
+    // OpenAPI spec generated from the handlers, plus an interactive explorer
+    let openapi = routes::openapi();
+    let docs_routes = SwaggerUi::new(routes::DOCS_PATH).url(routes::OPENAPI_PATH, openapi.clone());
+
+    let (metrics_routes, metrics_server) = match config.metrics_addr {
+        Some(addr) => {
+            let listener = tokio::net::TcpListener::bind(addr).await?;
+            tracing::info!("📈 Metrics listening on {}", listener.local_addr()?);
+            let server = axum::serve(listener, metrics_routes)
+                .with_graceful_shutdown(shutdown_token.clone().cancelled_owned());
+            (Router::new(), Some(tokio::spawn(server.into_future())))
+        }
+        None => (metrics_routes, None),
+    };
 
     // Combine all routes
     let app = Router::new()
         .merge(public_routes)
         .merge(protected_routes)
-        // CORS: Specify explicit headers when using credentials
-        .layer(
-            CorsLayer::new()
-                .allow_origin(Any)
-                .allow_methods([
-                    axum::http::Method::GET,
-                    axum::http::Method::POST,
-                    axum::http::Method::PUT,
-                    axum::http::Method::PATCH,
-                    axum::http::Method::DELETE,
-                    axum::http::Method::OPTIONS,
-                ])
-                .allow_headers([
-                    axum::http::header::AUTHORIZATION,
-                    axum::http::header::CONTENT_TYPE,
-                    axum::http::header::ACCEPT,
-                ])
-        )
+        .merge(health_routes)
+        .merge(metrics_routes)
+        .merge(docs_routes)
+        // Lets the ClientIp extractor see through our load balancers
+        .layer(Extension(trusted_proxies))
         // Compression layer
         .layer(CompressionLayer::new())
+        // Ask keep-alive clients to reconnect elsewhere while draining
+        .layer(middleware::from_fn_with_state(
+            shutdown_token.clone(),
+            drain_middleware,
+        ))
+        // Request count and latency by route and status
+        .layer(middleware::from_fn(metrics_middleware))
+        // Lets error responses report the request ID and path
+        .layer(middleware::from_fn(request_context_middleware))
         // Tracing/logging layer
         .layer(
             TraceLayer::new_for_http()
-                .make_span_with(DefaultMakeSpan::new().level(Level::INFO))
+                .make_span_with(make_request_span)
                 .on_response(DefaultOnResponse::new().level(Level::INFO)),
-        );
+        )
+        // Request IDs: reuse the caller's X-Request-Id or generate one, and echo it back
+        .layer(PropagateRequestIdLayer::new(REQUEST_ID_HEADER))
+        .layer(SetRequestIdLayer::new(REQUEST_ID_HEADER, MakeRequestUuid));
 
     // Bind server
-    let addr = SocketAddr::from(([0, 0, 0, 0], config.port));
-    tracing::info!("🌐 Server listening on {}", addr);
+    let listener = tokio::net::TcpListener::bind((config.host.as_str(), config.port)).await?;
+    tracing::info!("🌐 Server listening on {}", listener.local_addr()?);
     tracing::info!("📝 API Documentation:");
     tracing::info!("  - GET  /health                   - Health check");
-    tracing::info!("  - POST /api/v1/auth/register     - Register new user");
-    tracing::info!("  - POST /api/v1/auth/login        - User login");
-    tracing::info!("  - POST /api/v1/auth/refresh      - Refresh access token");
-    tracing::info!("  - GET  /api/v1/notes             - List notes (auth required)");
-    tracing::info!("  - POST /api/v1/notes             - Create note (auth required)");
-    tracing::info!("  - GET  /api/v1/notes/:id         - Get note (auth required)");
-    tracing::info!("  - PUT  /api/v1/notes/:id         - Update note (auth required)");
-    tracing::info!("  - DELETE /api/v1/notes/:id       - Delete note (auth required)");
+    tracing::info!("  - GET  /health/live              - Liveness probe");
+    tracing::info!("  - GET  /health/ready             - Readiness probe");
+    if config.metrics_addr.is_none() {
+        tracing::info!("  - GET  /metrics                  - Prometheus metrics");
+    }
+    tracing::info!("  - GET  {:<26}- OpenAPI spec", routes::OPENAPI_PATH);
+    tracing::info!("  - GET  {:<26}- Interactive API docs", routes::DOCS_PATH);
+    log_api_routes(&openapi);
     tracing::info!("✨ Server ready to accept connections!");
 
-    let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(
+    tokio::spawn(shutdown::wait_for_signal(shutdown_token.clone()));
+
+    // Stop accepting connections on shutdown and let in-flight requests finish,
+    // but never wait longer than the configured deadline
+    let drain_deadline = Duration::from_secs(config.shutdown_timeout_secs);
+    let server = axum::serve(
         listener,
         app.into_make_service_with_connect_info::<SocketAddr>(),
     )
-    .await?;
+    .with_graceful_shutdown(shutdown_token.clone().cancelled_owned());
+
+    // axum runs each connection in its own task, so requests still in flight at
+    // the deadline keep running until the runtime shuts down when we return
+    let drained = tokio::select! {
+        result = server => {
+            result?;
+            true
+        }
+        _ = async {
+            shutdown_token.cancelled().await;
+            tokio::time::sleep(drain_deadline).await;
+        } => {
+            tracing::warn!(
+                "⏱️ Drain deadline of {:?} exceeded, abandoning in-flight requests; they are cancelled at exit",
+                drain_deadline
+            );
+            false
+        }
+    };
+    tracing::info!("✅ Server stopped accepting connections");
+
+    // Make sure background tasks see the shutdown even if the server exited on its own
+    shutdown_token.cancel();
+    futures::future::join_all(cleanup_tasks).await;
+    let _ = metrics_sampler.await;
+    if let Some(server) = metrics_server {
+        // Past the deadline a slow scrape must not hold up shutdown either
+        if !drained {
+            server.abort();
+        }
+        match server.await {
+            Ok(Err(e)) => tracing::warn!("Metrics listener failed: {}", e),
+            Err(e) if !e.is_cancelled() => tracing::warn!("Metrics listener task failed: {}", e),
+            _ => {}
+        }
+    }
+
+    // close() waits for every checked-out connection, which abandoned requests
+    // may hold indefinitely; their connections are dropped at exit instead
+    if drained {
+        pool.close().await;
+        tracing::info!("✅ Database pool closed");
+    } else {
+        tracing::warn!("⏱️ Not waiting for database connections held by abandoned requests");
+    }
+
+    tracing::info!("👋 Shutdown complete");
 
     Ok(())
 }
 
-/// Health check endpoint
-async fn health_check() -> &'static str {
-    "OK"
+/// Build the rate limit storage selected in config; `scope` keeps limiters apart in Redis
+fn rate_limit_backend(
+    config: &Config,
+    redis_conn: &ConnectionManager,
+    scope: &str,
+) -> Arc<dyn RateLimitBackend> {
+    match config.rate_limit_backend {
+        RateLimitBackendKind::Memory => Arc::new(InMemoryRateLimiter::new()),
+        RateLimitBackendKind::Gcra => Arc::new(GcraRateLimiter::new()),
+        RateLimitBackendKind::Redis => Arc::new(RedisRateLimiter::new(
+            redis_conn.clone(),
+            format!("ratelimit:{}", scope),
+        )),
+    }
+}
+
+/// Log every documented API operation, so the startup banner never drifts from the routes
+fn log_api_routes(openapi: &utoipa::openapi::OpenApi) {
+    for (path, item) in &openapi.paths.paths {
+        let operations = [
+            ("GET", &item.get),
+            ("POST", &item.post),
+            ("PUT", &item.put),
+            ("PATCH", &item.patch),
+            ("DELETE", &item.delete),
+        ];
+        for (method, operation) in operations {
+            let Some(operation) = operation else { continue };
+            let summary = operation.summary.as_deref().unwrap_or_default();
+            let auth = if operation.security.is_some() {
+                " (auth required)"
+            } else {
+                ""
+            };
+            tracing::info!("  - {:<4} {:<26}- {}{}", method, path, summary, auth);
+        }
+    }
 }
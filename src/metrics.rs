@@ -0,0 +1,117 @@
+//! Prometheus metrics: recorder setup, metric names and sampled gauges.
+
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use sqlx::PgPool;
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
+
+use crate::db::db_span;
+use crate::utils::errors::{AppError, Result};
+
+pub const HTTP_REQUESTS_TOTAL: &str = "http_requests_total";
+pub const HTTP_REQUEST_DURATION_SECONDS: &str = "http_request_duration_seconds";
+pub const DB_POOL_CONNECTIONS: &str = "db_pool_connections";
+pub const DB_POOL_MAX_CONNECTIONS: &str = "db_pool_max_connections";
+pub const DB_POOL_ACQUIRE_SECONDS: &str = "db_pool_acquire_seconds";
+pub const RATE_LIMIT_REJECTIONS_TOTAL: &str = "rate_limit_rejections_total";
+pub const AUTH_LOGINS_TOTAL: &str = "auth_logins_total";
+pub const NOTES: &str = "notes";
+pub const NOTES_CREATED_TOTAL: &str = "notes_created_total";
+pub const NOTES_DELETED_TOTAL: &str = "notes_deleted_total";
+pub const CACHE_REQUESTS_TOTAL: &str = "cache_requests_total";
+
+/// Latency buckets in seconds, from fast cache hits to slow queries
+const LATENCY_BUCKETS: &[f64] = &[
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// How long the sampler waits for a pooled connection before reporting the pool as exhausted
+const ACQUIRE_PROBE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// How often gauges that need a database round trip are refreshed
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Install the global Prometheus recorder
+///
+/// Must be called once, before any metric is recorded.
+pub fn install_recorder() -> Result<PrometheusHandle> {
+    PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Full(HTTP_REQUEST_DURATION_SECONDS.to_string()),
+            LATENCY_BUCKETS,
+        )
+        .and_then(|builder| {
+            builder.set_buckets_for_metric(
+                Matcher::Full(DB_POOL_ACQUIRE_SECONDS.to_string()),
+                LATENCY_BUCKETS,
+            )
+        })
+        .map_err(|e| AppError::InternalError(format!("Invalid metrics buckets: {}", e)))?
+        .install_recorder()
+        .map_err(|e| AppError::InternalError(format!("Metrics recorder failed: {}", e)))
+}
+
+/// Refresh the pool size gauges; cheap enough for every scrape, as sqlx
+/// tracks these in memory
+pub fn record_pool_size(pool: &PgPool) {
+    let size = pool.size();
+    let idle = pool.num_idle() as u32;
+    metrics::gauge!(DB_POOL_CONNECTIONS, "state" => "idle").set(idle as f64);
+    metrics::gauge!(DB_POOL_CONNECTIONS, "state" => "in_use").set(size.saturating_sub(idle) as f64);
+    metrics::gauge!(DB_POOL_MAX_CONNECTIONS).set(pool.options().get_max_connections() as f64);
+}
+
+/// Refresh the gauges that need the database every [`SAMPLE_INTERVAL`] until
+/// `shutdown` fires
+///
+/// Sampling apart from scrapes keeps the cost of `/metrics` flat however
+/// often, or by whom, it is scraped.
+pub fn start_sampler(pool: PgPool, shutdown: CancellationToken) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SAMPLE_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => sample(&pool).await,
+                _ = shutdown.cancelled() => break,
+            }
+        }
+        tracing::debug!("Metrics sampler stopped");
+    })
+}
+
+/// Probe pool queueing time and count notes
+async fn sample(pool: &PgPool) {
+    // sqlx doesn't expose queueing time, so probe it with a real acquire
+    let started = Instant::now();
+    match tokio::time::timeout(ACQUIRE_PROBE_TIMEOUT, pool.acquire()).await {
+        Ok(Ok(_conn)) => {
+            metrics::histogram!(DB_POOL_ACQUIRE_SECONDS).record(started.elapsed().as_secs_f64());
+        }
+        Ok(Err(e)) => tracing::warn!("Metrics pool probe failed: {}", e),
+        Err(_) => {
+            metrics::histogram!(DB_POOL_ACQUIRE_SECONDS)
+                .record(ACQUIRE_PROBE_TIMEOUT.as_secs_f64());
+        }
+    }
+
+    let counts = sqlx::query!(
+        r#"SELECT
+               COUNT(*) FILTER (WHERE is_deleted = false) AS "active!",
+               COUNT(*) FILTER (WHERE is_deleted = true) AS "deleted!"
+           FROM notes"#
+    )
+    .fetch_one(pool)
+    .instrument(db_span!("SELECT", "notes"))
+    .await;
+
+    match counts {
+        Ok(counts) => {
+            metrics::gauge!(NOTES, "state" => "active").set(counts.active as f64);
+            metrics::gauge!(NOTES, "state" => "deleted").set(counts.deleted as f64);
+        }
+        Err(e) => tracing::warn!("Failed to sample note counts: {}", e),
+    }
+}
@@ -1,21 +1,22 @@
 use axum::{
-    body::Body,
     extract::{Request, State},
-    http::{header, StatusCode},
+    http::header,
     middleware::Next,
-    response::{IntoResponse, Response},
+    response::Response,
 };
-use sqlx::PgPool;
 use std::sync::Arc;
 use uuid::Uuid;
 
-use crate::models::user::User;
-use crate::utils::{errors::AppError, jwt::JwtManager};
+use crate::services::AuthService;
+use crate::utils::{
+    errors::{AppError, ErrorCode},
+    jwt::JwtManager,
+};
 
 /// Middleware to authenticate requests using JWT tokens
-/// Extracts the Bearer token from Authorization header, verifies it,fetches the user from database and injects user into request extensions
+/// Extracts the Bearer token from Authorization header, verifies it, resolves the user (cached) and injects an `AuthUser` into request extensions
 pub async fn auth_middleware(
-    State((jwt_manager, pool)): State<(Arc<JwtManager>, PgPool)>,
+    State((jwt_manager, auth_service)): State<(Arc<JwtManager>, Arc<AuthService>)>,
     mut req: Request,
     next: Next,
 ) -> Result<Response, AppError> {
@@ -23,7 +24,7 @@ pub async fn auth_middleware(
     let method = req.method();
     
     // 🔥 FIX: Skip auth for public routes
-    let public_routes = vec![
+    let public_routes = [
         "/health",
         "/auth/register",
         "/auth/login",
@@ -55,7 +56,7 @@ pub async fn auth_middleware(
         .and_then(|h| h.strip_prefix("Bearer "))
         .ok_or_else(|| {
             tracing::warn!("Missing or invalid Authorization header for: {}", path);
-            AppError::AuthenticationError("Missing authorization token".to_string())
+            AppError::problem(ErrorCode::MissingToken, "Missing authorization token")
         })?;
     
     // Verify JWT token
@@ -67,23 +68,13 @@ pub async fn auth_middleware(
     // Parse user ID from claims
     let user_id = Uuid::parse_str(&claims.sub).map_err(|_| {
         tracing::error!("Invalid user ID format in token: {}", claims.sub);
-        AppError::AuthenticationError("Invalid user ID in token".to_string())
+        AppError::problem(ErrorCode::InvalidToken, "Invalid user ID in token")
     })?;
     
-    // Fetch user from database
-    let user = sqlx::query_as!(
-        User,
-        "SELECT * FROM users WHERE id = $1",
-        user_id
-    )
-    .fetch_optional(&pool)
-    .await?
-    .ok_or_else(|| {
-        tracing::warn!("User not found for ID: {}", user_id);
-        AppError::AuthenticationError("User not found".to_string())
-    })?;
+    // Resolve the user, rejecting deleted and disabled accounts
+    let user = auth_service.authenticate(user_id).await?;
     
-    tracing::debug!("Authenticated user: {} ({})", user.email, user.id);
+    tracing::debug!("Authenticated user: {}", user.id);
     
     // Insert user into request extensions for handlers to access
     req.extensions_mut().insert(user);
@@ -93,7 +84,7 @@ pub async fn auth_middleware(
 }
 
 pub async fn optional_auth_middleware(
-    State((jwt_manager, pool)): State<(Arc<JwtManager>, PgPool)>,
+    State((jwt_manager, auth_service)): State<(Arc<JwtManager>, Arc<AuthService>)>,
     mut req: Request,
     next: Next,
 ) -> Response {
@@ -102,14 +93,7 @@ pub async fn optional_auth_middleware(
             if let Some(token) = auth_str.strip_prefix("Bearer ") {
                 if let Ok(claims) = jwt_manager.verify_access_token(token) {
                     if let Ok(user_id) = Uuid::parse_str(&claims.sub) {
-                        if let Ok(Some(user)) = sqlx::query_as!(
-                            User,
-                            "SELECT * FROM users WHERE id = $1",
-                            user_id
-                        )
-                        .fetch_optional(&pool)
-                        .await
-                        {
+                        if let Ok(user) = auth_service.authenticate(user_id).await {
                             req.extensions_mut().insert(user);
                         }
                     }
@@ -0,0 +1,141 @@
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts},
+    http::{request::Parts, HeaderMap},
+};
+use ipnet::IpNet;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+use crate::utils::errors::AppError;
+
+/// Proxies whose forwarding headers we believe
+///
+/// Installed once on the router as an `Extension(Arc<TrustedProxies>)`.
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies {
+    nets: Vec<IpNet>,
+}
+
+impl TrustedProxies {
+    pub fn new(nets: Vec<IpNet>) -> Self {
+        Self { nets }
+    }
+
+    /// IPv4-mapped IPv6 addresses (`::ffff:10.0.0.1`) match as the IPv4 address
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        self.nets.iter().any(|net| net.contains(&ip))
+    }
+
+    /// Resolve the originating client from the socket peer and forwarding headers
+    ///
+    /// Hops are walked from the nearest proxy outwards and the first address
+    /// that is not a trusted proxy is the client. Headers are ignored entirely
+    /// unless the socket peer itself is trusted, since anyone can send them.
+    /// `Forwarded` (RFC 7239) takes precedence over `X-Forwarded-For`.
+    /// IPv4-mapped IPv6 addresses, as a dual-stack listener reports IPv4
+    /// peers, are returned as plain IPv4.
+    pub fn resolve(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        let peer = peer.to_canonical();
+        if !self.contains(&peer) {
+            return peer;
+        }
+
+        let hops = forwarded_for(headers).or_else(|| x_forwarded_for(headers));
+        let Some(hops) = hops else {
+            return peer;
+        };
+
+        let mut client = peer;
+        for hop in hops.into_iter().rev() {
+            // Obfuscated or malformed entries end the chain of trust
+            let Some(ip) = hop else {
+                break;
+            };
+            let ip = ip.to_canonical();
+            client = ip;
+            if !self.contains(&ip) {
+                break;
+            }
+        }
+        client
+    }
+}
+
+/// Client IP address, resolved through trusted proxies
+///
+/// Falls back to the socket peer address when no `TrustedProxies` are
+/// installed. Requires the app to be served with
+/// `into_make_service_with_connect_info::<SocketAddr>()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientIp(pub IpAddr);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ClientIp
+where
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip())
+            .ok_or_else(|| AppError::InternalError("Missing connection info".to_string()))?;
+
+        let ip = match parts.extensions.get::<Arc<TrustedProxies>>() {
+            Some(proxies) => proxies.resolve(peer, &parts.headers),
+            None => peer.to_canonical(),
+        };
+
+        Ok(ClientIp(ip))
+    }
+}
+
+/// `for=` parameters of every `Forwarded` element, in order
+///
+/// Every element is one hop, so one without `for=` (e.g. `by=...;proto=https`)
+/// counts as an unidentified node rather than being skipped, which would pair
+/// the remaining addresses with the wrong proxies.
+fn forwarded_for(headers: &HeaderMap) -> Option<Vec<Option<IpAddr>>> {
+    let mut hops = Vec::new();
+    for value in headers.get_all("forwarded") {
+        let value = value.to_str().ok()?;
+        for element in value.split(',') {
+            let node = element.split(';').find_map(|pair| {
+                let (key, value) = pair.trim().split_once('=')?;
+                key.eq_ignore_ascii_case("for").then_some(value)
+            });
+            hops.push(node.and_then(parse_node));
+        }
+    }
+    (!hops.is_empty()).then_some(hops)
+}
+
+/// Entries of every `X-Forwarded-For` header, in order
+fn x_forwarded_for(headers: &HeaderMap) -> Option<Vec<Option<IpAddr>>> {
+    let mut hops = Vec::new();
+    for value in headers.get_all("x-forwarded-for") {
+        let value = value.to_str().ok()?;
+        hops.extend(value.split(',').map(parse_node));
+    }
+    (!hops.is_empty()).then_some(hops)
+}
+
+/// Parse a node identifier: `1.2.3.4`, `1.2.3.4:80`, `"[::1]:80"` or `::1`
+///
+/// Returns `None` for `unknown` and obfuscated (`_hidden`) identifiers.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+
+    if let Some(rest) = node.strip_prefix('[') {
+        let (ip, _port) = rest.split_once(']')?;
+        return ip.parse().ok();
+    }
+
+    node.parse::<IpAddr>()
+        .ok()
+        .or_else(|| node.parse::<SocketAddr>().ok().map(|addr| addr.ip()))
+}
@@ -0,0 +1,149 @@
+use axum::http::{
+    header::{self, HeaderName},
+    Method,
+};
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+use crate::config::Config;
+use crate::middleware::rate_limit::{RATELIMIT_LIMIT, RATELIMIT_REMAINING, RATELIMIT_RESET};
+use crate::telemetry::REQUEST_ID_HEADER;
+
+/// Response headers browsers may always read, so clients can back off,
+/// revalidate and quote request IDs to support
+const DEFAULT_EXPOSE_HEADERS: [HeaderName; 6] = [
+    RATELIMIT_LIMIT,
+    RATELIMIT_REMAINING,
+    RATELIMIT_RESET,
+    header::RETRY_AFTER,
+    header::ETAG,
+    REQUEST_ID_HEADER,
+];
+
+/// An entry in the origin allowlist
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OriginPattern {
+    /// `*`: any origin; cannot be combined with credentials
+    Any,
+    /// `https://app.example.com`
+    Exact(String),
+    /// `https://*.example.com`: any subdomain, but not the apex itself
+    Subdomains { scheme: String, suffix: String },
+}
+
+impl OriginPattern {
+    pub fn matches(&self, origin: &str) -> bool {
+        let origin = origin.to_ascii_lowercase();
+        match self {
+            OriginPattern::Any => true,
+            OriginPattern::Exact(allowed) => origin == *allowed,
+            OriginPattern::Subdomains { scheme, suffix } => origin
+                .strip_prefix(scheme.as_str())
+                .and_then(|rest| rest.strip_prefix("://"))
+                .and_then(|rest| rest.strip_suffix(suffix.as_str()))
+                .is_some_and(|sub| {
+                    !sub.is_empty()
+                        && !sub.starts_with('.')
+                        && sub
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+                }),
+        }
+    }
+}
+
+impl FromStr for OriginPattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().trim_end_matches('/').to_ascii_lowercase();
+        if s == "*" {
+            return Ok(OriginPattern::Any);
+        }
+
+        let (scheme, host) = s
+            .split_once("://")
+            .filter(|(scheme, _)| matches!(*scheme, "http" | "https"))
+            .ok_or_else(|| "origin must start with http:// or https://".to_string())?;
+
+        if host.is_empty() || host.contains('/') {
+            return Err("origin must not include a path".to_string());
+        }
+
+        match host.strip_prefix('*') {
+            Some(suffix) if suffix.starts_with('.') && !suffix[1..].contains('*') => {
+                Ok(OriginPattern::Subdomains {
+                    scheme: scheme.to_string(),
+                    suffix: suffix.to_string(),
+                })
+            }
+            Some(_) => Err("wildcards must be a leading '*.'".to_string()),
+            None if host.contains('*') => Err("wildcards must be a leading '*.'".to_string()),
+            None => Ok(OriginPattern::Exact(s)),
+        }
+    }
+}
+
+impl fmt::Display for OriginPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OriginPattern::Any => write!(f, "*"),
+            OriginPattern::Exact(origin) => write!(f, "{}", origin),
+            OriginPattern::Subdomains { scheme, suffix } => write!(f, "{}://*{}", scheme, suffix),
+        }
+    }
+}
+
+/// CORS for the public auth endpoints: JSON POSTs only, no bearer token
+pub fn anonymous(config: &Config) -> CorsLayer {
+    base(config)
+        .allow_methods([Method::POST])
+        .allow_headers([header::CONTENT_TYPE, header::ACCEPT])
+}
+
+/// CORS for the authenticated API
+pub fn authenticated(config: &Config) -> CorsLayer {
+    base(config)
+        .allow_methods([
+            Method::GET,
+            Method::POST,
+            Method::PUT,
+            Method::PATCH,
+            Method::DELETE,
+        ])
+        .allow_headers([
+            header::AUTHORIZATION,
+            header::CONTENT_TYPE,
+            header::ACCEPT,
+            header::IF_MATCH,
+            header::IF_NONE_MATCH,
+        ])
+}
+
+fn base(config: &Config) -> CorsLayer {
+    let expose_headers: Vec<HeaderName> = DEFAULT_EXPOSE_HEADERS
+        .into_iter()
+        .chain(config.cors_expose_headers.iter().cloned())
+        .collect();
+
+    CorsLayer::new()
+        .allow_origin(allow_origin(&config.cors_allowed_origins))
+        .allow_credentials(config.cors_allow_credentials)
+        .expose_headers(expose_headers)
+        .max_age(Duration::from_secs(config.cors_max_age_secs))
+}
+
+fn allow_origin(patterns: &[OriginPattern]) -> AllowOrigin {
+    if patterns.contains(&OriginPattern::Any) {
+        return AllowOrigin::any();
+    }
+
+    let patterns = patterns.to_vec();
+    AllowOrigin::predicate(move |origin, _| {
+        origin
+            .to_str()
+            .is_ok_and(|origin| patterns.iter().any(|p| p.matches(origin)))
+    })
+}
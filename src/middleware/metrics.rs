@@ -0,0 +1,42 @@
+use axum::{
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::Response,
+};
+use std::time::Instant;
+
+use crate::metrics::{HTTP_REQUESTS_TOTAL, HTTP_REQUEST_DURATION_SECONDS};
+
+/// Records request count and latency by method, route pattern and status
+///
+/// Routes are labelled by their pattern (`/api/v1/notes/:id`), never the raw
+/// path, to keep label cardinality bounded.
+pub async fn metrics_middleware(req: Request, next: Next) -> Response {
+    let started = Instant::now();
+    let method = req.method().to_string();
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+
+    let response = next.run(req).await;
+
+    let status = response.status().as_u16().to_string();
+    metrics::histogram!(
+        HTTP_REQUEST_DURATION_SECONDS,
+        "method" => method.clone(),
+        "route" => route.clone(),
+        "status" => status.clone()
+    )
+    .record(started.elapsed().as_secs_f64());
+    metrics::counter!(
+        HTTP_REQUESTS_TOTAL,
+        "method" => method,
+        "route" => route,
+        "status" => status
+    )
+    .increment(1);
+
+    response
+}
@@ -1,5 +1,18 @@
 pub mod auth;
+pub mod client_ip;
+pub mod cors;
+pub mod metrics;
 pub mod rate_limit;
+pub mod request_context;
+pub mod shutdown;
 
 pub use auth::{auth_middleware, optional_auth_middleware};
-pub use rate_limit::{rate_limit_middleware, start_cleanup_task, RateLimiter};
+pub use client_ip::{ClientIp, TrustedProxies};
+pub use metrics::metrics_middleware;
+pub use rate_limit::{
+    auth_failure_limit_middleware, rate_limit_middleware, start_cleanup_task, GcraRateLimiter, InMemoryRateLimiter,
+    RateLimitBackend, RateLimitDecision, RateLimitPolicies, RateLimitPolicy, RateLimiter,
+    RedisRateLimiter, RATELIMIT_LIMIT, RATELIMIT_REMAINING, RATELIMIT_RESET,
+};
+pub use request_context::{request_context_middleware, RequestContext};
+pub use shutdown::drain_middleware;
@@ -0,0 +1,92 @@
+use async_trait::async_trait;
+use dashmap::DashMap;
+use std::time::{Duration, Instant};
+
+use super::{RateLimitBackend, RateLimitDecision};
+use crate::utils::errors::Result;
+
+/// In-process rate limit storage using the Generic Cell Rate Algorithm
+///
+/// Each key stores a single theoretical arrival time (TAT) instead of a log
+/// of timestamps, so memory is O(1) per key regardless of traffic. State lives
+/// in a sharded map so requests for different keys don't contend on one lock.
+/// Like [`InMemoryRateLimiter`](super::InMemoryRateLimiter), counters are per
+/// process.
+pub struct GcraRateLimiter {
+    /// Theoretical arrival time per key, in nanoseconds since `epoch`
+    tats: DashMap<String, u64>,
+    epoch: Instant,
+}
+
+impl GcraRateLimiter {
+    pub fn new() -> Self {
+        Self {
+            tats: DashMap::new(),
+            epoch: Instant::now(),
+        }
+    }
+
+    fn now_nanos(&self) -> u64 {
+        self.epoch.elapsed().as_nanos() as u64
+    }
+}
+
+impl Default for GcraRateLimiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl RateLimitBackend for GcraRateLimiter {
+    async fn check(
+        &self,
+        key: &str,
+        limit: u32,
+        window: Duration,
+        cost: u32,
+    ) -> Result<RateLimitDecision> {
+        let now = self.now_nanos();
+        let window_nanos = window.as_nanos() as u64;
+        // Time one unit of budget takes to replenish
+        let interval = window_nanos / u64::from(limit.max(1));
+
+        let mut tat = self.tats.entry(key.to_string()).or_insert(now);
+        let current = (*tat).max(now);
+        let new_tat = current + interval * u64::from(cost);
+        // The request fits if, after adding it, no more than a full window is outstanding
+        let allow_at = new_tat.saturating_sub(window_nanos);
+
+        if now < allow_at {
+            let remaining = window_nanos.saturating_sub(current - now) / interval.max(1);
+            return Ok(RateLimitDecision {
+                allowed: false,
+                limit,
+                remaining: remaining as u32,
+                reset_after: Duration::from_nanos(current - now),
+                retry_after: Some(Duration::from_nanos(allow_at - now)),
+            });
+        }
+
+        *tat = new_tat;
+        let remaining = window_nanos.saturating_sub(new_tat - now) / interval.max(1);
+        Ok(RateLimitDecision {
+            allowed: true,
+            limit,
+            remaining: remaining as u32,
+            reset_after: Duration::from_nanos(new_tat - now),
+            retry_after: None,
+        })
+    }
+
+    async fn cleanup(&self) {
+        // A TAT in the past means the key has fully replenished
+        let now = self.now_nanos();
+        self.tats.retain(|_, tat| *tat > now);
+
+        tracing::debug!(
+            "GCRA rate limiter cleanup completed. Active keys: {}",
+            self.tats.len()
+        );
+    }
+}
@@ -0,0 +1,101 @@
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::RwLock;
+
+use super::{RateLimitBackend, RateLimitDecision};
+use crate::utils::errors::Result;
+
+/// In-process rate limit storage using a sliding window algorithm
+///
+/// Counters live in this process only, so every replica enforces its own
+/// limit and a restart resets them.
+#[derive(Default)]
+pub struct InMemoryRateLimiter {
+    /// Stores request timestamps (milliseconds) for each IP/key
+    requests: RwLock<HashMap<String, Vec<u64>>>,
+    /// Longest window seen so far, used when cleaning up
+    max_window_ms: AtomicU64,
+}
+
+impl InMemoryRateLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn now_millis() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64
+    }
+}
+
+#[async_trait]
+impl RateLimitBackend for InMemoryRateLimiter {
+    async fn check(
+        &self,
+        key: &str,
+        limit: u32,
+        window: Duration,
+        cost: u32,
+    ) -> Result<RateLimitDecision> {
+        let now = Self::now_millis();
+        let window_ms = window.as_millis() as u64;
+
+        self.max_window_ms.fetch_max(window_ms, Ordering::Relaxed);
+
+        let mut requests = self.requests.write().await;
+        let timestamps = requests.entry(key.to_string()).or_default();
+
+        // Remove timestamps outside the current window
+        timestamps.retain(|&t| now.saturating_sub(t) < window_ms);
+
+        let expires_in = |t: u64| Duration::from_millis(t + window_ms - now);
+
+        // Check if limit exceeded
+        if timestamps.len() + cost as usize > limit as usize {
+            // Oldest entries leave the window first; wait until enough have to fit `cost`
+            let excess = timestamps.len() + cost as usize - limit as usize;
+            return Ok(RateLimitDecision {
+                allowed: false,
+                limit,
+                remaining: limit.saturating_sub(timestamps.len() as u32),
+                reset_after: timestamps.first().map_or(window, |&t| expires_in(t)),
+                retry_after: Some(
+                    timestamps
+                        .get(excess - 1)
+                        .map_or(window, |&t| expires_in(t)),
+                ),
+            });
+        }
+
+        // Add one timestamp per unit of cost
+        timestamps.extend(std::iter::repeat_n(now, cost as usize));
+        Ok(RateLimitDecision {
+            allowed: true,
+            limit,
+            remaining: limit.saturating_sub(timestamps.len() as u32),
+            // Empty only when a zero-cost check found nothing recorded
+            reset_after: timestamps.first().map_or(window, |&t| expires_in(t)),
+            retry_after: None,
+        })
+    }
+
+    async fn cleanup(&self) {
+        let now = Self::now_millis();
+        let window_ms = self.max_window_ms.load(Ordering::Relaxed);
+
+        let mut requests = self.requests.write().await;
+        requests.retain(|_, timestamps| {
+            timestamps.retain(|&t| now.saturating_sub(t) < window_ms);
+            !timestamps.is_empty()
+        });
+
+        tracing::debug!(
+            "Rate limiter cleanup completed. Active keys: {}",
+            requests.len()
+        );
+    }
+}
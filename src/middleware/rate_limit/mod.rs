@@ -0,0 +1,243 @@
+use async_trait::async_trait;
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+use crate::metrics::RATE_LIMIT_REJECTIONS_TOTAL;
+use crate::middleware::client_ip::ClientIp;
+use crate::models::user::AuthUser;
+use crate::utils::errors::{AppError, Result};
+
+pub mod gcra;
+pub mod memory;
+pub mod policy;
+pub mod redis;
+
+pub use gcra::GcraRateLimiter;
+pub use memory::InMemoryRateLimiter;
+pub use policy::{RateLimitPolicies, RateLimitPolicy};
+pub use redis::RedisRateLimiter;
+
+/// Outcome of a single rate limit check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitDecision {
+    /// Whether the request is allowed through
+    pub allowed: bool,
+    /// Budget per window for the policy that was applied
+    pub limit: u32,
+    /// Budget left in the current window after this request
+    pub remaining: u32,
+    /// Time until the oldest counted request leaves the window
+    pub reset_after: Duration,
+    /// For rejected requests, time until enough budget frees up to retry
+    pub retry_after: Option<Duration>,
+}
+
+pub const RATELIMIT_LIMIT: HeaderName = HeaderName::from_static("ratelimit-limit");
+pub const RATELIMIT_REMAINING: HeaderName = HeaderName::from_static("ratelimit-remaining");
+pub const RATELIMIT_RESET: HeaderName = HeaderName::from_static("ratelimit-reset");
+
+impl RateLimitDecision {
+    /// Write the IETF draft `RateLimit-*` headers, plus `Retry-After` when rejected
+    pub fn apply_headers(&self, headers: &mut HeaderMap) {
+        headers.insert(RATELIMIT_LIMIT, HeaderValue::from(self.limit));
+        headers.insert(RATELIMIT_REMAINING, HeaderValue::from(self.remaining));
+        headers.insert(
+            RATELIMIT_RESET,
+            HeaderValue::from(ceil_secs(self.reset_after)),
+        );
+        if let Some(retry_after) = self.retry_after {
+            headers.insert(
+                header::RETRY_AFTER,
+                HeaderValue::from(ceil_secs(retry_after)),
+            );
+        }
+    }
+}
+
+/// Header values are whole seconds; round up so clients never retry early
+fn ceil_secs(duration: Duration) -> u64 {
+    duration.as_millis().div_ceil(1000) as u64
+}
+
+/// Storage backend for rate limit counters
+///
+/// Implementations must make the check-and-record step atomic so that
+/// concurrent requests for the same key cannot both slip under the limit.
+#[async_trait]
+pub trait RateLimitBackend: Send + Sync {
+    /// Record a request costing `cost` units for `key` and decide whether it
+    /// fits in the window
+    ///
+    /// A `cost` of 0 records nothing and only reports the budget left.
+    async fn check(
+        &self,
+        key: &str,
+        limit: u32,
+        window: Duration,
+        cost: u32,
+    ) -> Result<RateLimitDecision>;
+
+    /// Drop expired state; backends that expire keys on their own can ignore this
+    async fn cleanup(&self) {}
+}
+
+/// Rate limiter applying per-route policies on top of a backend
+#[derive(Clone)]
+pub struct RateLimiter {
+    backend: Arc<dyn RateLimitBackend>,
+    policies: RateLimitPolicies,
+}
+
+impl RateLimiter {
+    /// Create a new rate limiter
+    pub fn new(backend: Arc<dyn RateLimitBackend>, policies: RateLimitPolicies) -> Self {
+        Self { backend, policies }
+    }
+
+    /// Create a rate limiter backed by process memory
+    pub fn in_memory(policies: RateLimitPolicies) -> Self {
+        Self::new(Arc::new(InMemoryRateLimiter::new()), policies)
+    }
+
+    pub fn policies(&self) -> &RateLimitPolicies {
+        &self.policies
+    }
+
+    /// Check if a request from `subject` should be rate limited under `policy`
+    pub async fn check_rate_limit(
+        &self,
+        subject: &str,
+        policy: &RateLimitPolicy,
+    ) -> Result<RateLimitDecision> {
+        let key = format!("{}:{}", policy.name, subject);
+        self.backend
+            .check(&key, policy.limit, policy.window, policy.cost)
+            .await
+    }
+
+    /// Clean up old entries to prevent memory leaks
+    pub async fn cleanup(&self) {
+        self.backend.cleanup().await;
+    }
+}
+
+/// Limits requests per authenticated user, or per client IP address (resolved
+/// through trusted proxies) for anonymous requests, using the policy matching the route
+///
+/// Every response carries `RateLimit-*` headers and rejections also carry
+/// `Retry-After`. Must run after `auth_middleware` for user keying to take effect. If the
+/// backend is unreachable the request is let through rather than taking the
+/// whole API down with the limiter.
+pub async fn rate_limit_middleware(
+    State(rate_limiter): State<Arc<RateLimiter>>,
+    ClientIp(ip): ClientIp,
+    req: Request,
+    next: Next,
+) -> Response {
+    let subject = match req.extensions().get::<AuthUser>() {
+        Some(user) => format!("user:{}", user.id),
+        None => format!("ip:{}", ip),
+    };
+    let policy = rate_limiter.policies().resolve(
+        req.method(),
+        req.extensions().get::<MatchedPath>().map(|p| p.as_str()),
+        req.uri().query(),
+    );
+
+    let decision = match rate_limiter.check_rate_limit(&subject, policy).await {
+        Ok(decision) => decision,
+        Err(e) => {
+            tracing::error!("Rate limiter backend failed, allowing request: {}", e);
+            return next.run(req).await;
+        }
+    };
+
+    let mut response = if decision.allowed {
+        tracing::debug!(
+            "Request from {} ({}) - Remaining: {}",
+            subject,
+            policy.name,
+            decision.remaining
+        );
+        next.run(req).await
+    } else {
+        tracing::warn!("Rate limit exceeded for {} ({})", subject, policy.name);
+        metrics::counter!(RATE_LIMIT_REJECTIONS_TOTAL, "policy" => policy.name).increment(1);
+        AppError::RateLimitExceeded.into_response()
+    };
+
+    decision.apply_headers(response.headers_mut());
+    response
+}
+
+/// Charges requests that fail authentication to the client IP, and turns an
+/// IP away once it has used up its failures
+///
+/// Must run outside `auth_middleware`: that rejects bad tokens before the
+/// per-user limiter runs, so without this the protected routes would take
+/// unlimited token guessing. Authenticated requests cost nothing here.
+pub async fn auth_failure_limit_middleware(
+    State(rate_limiter): State<Arc<RateLimiter>>,
+    ClientIp(ip): ClientIp,
+    req: Request,
+    next: Next,
+) -> Response {
+    let subject = format!("ip:{}", ip);
+    let policy = rate_limiter.policies().resolve(req.method(), None, None).clone();
+
+    // Check before authenticating, so a correct guess after the budget ran
+    // out is rejected like any other
+    match rate_limiter
+        .check_rate_limit(&subject, &policy.clone().with_cost(0))
+        .await
+    {
+        Ok(budget) if budget.remaining == 0 => {
+            tracing::warn!("Too many failed authentications from {}", subject);
+            metrics::counter!(RATE_LIMIT_REJECTIONS_TOTAL, "policy" => policy.name).increment(1);
+            let decision = RateLimitDecision {
+                allowed: false,
+                retry_after: Some(budget.reset_after),
+                ..budget
+            };
+            let mut response = AppError::RateLimitExceeded.into_response();
+            decision.apply_headers(response.headers_mut());
+            return response;
+        }
+        Ok(_) => {}
+        Err(e) => tracing::error!("Rate limiter backend failed, allowing request: {}", e),
+    }
+
+    let mut response = next.run(req).await;
+    if response.status() == StatusCode::UNAUTHORIZED {
+        match rate_limiter.check_rate_limit(&subject, &policy).await {
+            Ok(decision) => decision.apply_headers(response.headers_mut()),
+            Err(e) => tracing::error!("Rate limiter backend failed to record a failure: {}", e),
+        }
+    }
+    response
+}
+
+/// Start background task to periodically clean up rate limiter storage until `shutdown` fires
+pub fn start_cleanup_task(
+    rate_limiter: Arc<RateLimiter>,
+    shutdown: CancellationToken,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(300)); // 5 minutes
+        loop {
+            tokio::select! {
+                _ = interval.tick() => rate_limiter.cleanup().await,
+                _ = shutdown.cancelled() => break,
+            }
+        }
+        tracing::debug!("Rate limiter cleanup task stopped");
+    })
+}
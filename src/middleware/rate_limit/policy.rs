@@ -0,0 +1,187 @@
+use axum::http::Method;
+use std::time::Duration;
+
+use crate::config::Config;
+
+/// Limit and cost applied to a group of routes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RateLimitPolicy {
+    /// Bucket name; routes sharing a policy share a budget
+    pub name: &'static str,
+    /// Budget per window
+    pub limit: u32,
+    /// Window length
+    pub window: Duration,
+    /// Budget consumed by one request
+    pub cost: u32,
+}
+
+impl RateLimitPolicy {
+    pub fn per_minute(name: &'static str, limit: u32) -> Self {
+        Self {
+            name,
+            limit,
+            window: Duration::from_secs(60),
+            cost: 1,
+        }
+    }
+
+    /// Same bucket, but each request consumes `cost` units of it
+    pub fn with_cost(mut self, cost: u32) -> Self {
+        self.cost = cost;
+        self
+    }
+}
+
+/// A policy bound to a route; `None` method matches any method, and a
+/// `query` parameter, when set, must be present and non-blank
+#[derive(Debug, Clone)]
+struct RouteRule {
+    method: Option<Method>,
+    path: &'static str,
+    query: Option<&'static str>,
+    policy: RateLimitPolicy,
+}
+
+impl RouteRule {
+    fn matches(&self, method: &Method, path: &str, query: Option<&str>) -> bool {
+        self.path == path
+            && self.method.as_ref().is_none_or(|m| m == method)
+            && self.query.is_none_or(|param| has_param(query, param))
+    }
+}
+
+fn has_param(query: Option<&str>, param: &str) -> bool {
+    query.is_some_and(|query| {
+        url::form_urlencoded::parse(query.as_bytes())
+            .any(|(key, value)| key == param && !value.trim().is_empty())
+    })
+}
+
+/// Declarative table mapping routes to rate limit policies
+///
+/// Paths are axum route patterns (e.g. `/api/v1/notes/:id`) and are matched
+/// against the request's `MatchedPath`; a rule may also require a query
+/// parameter, so searches on a listing route get their own budget. The first
+/// matching rule wins; requests matching no rule fall back to the default policy.
+#[derive(Debug, Clone)]
+pub struct RateLimitPolicies {
+    default: RateLimitPolicy,
+    rules: Vec<RouteRule>,
+}
+
+impl RateLimitPolicies {
+    pub fn new(default: RateLimitPolicy) -> Self {
+        Self {
+            default,
+            rules: Vec::new(),
+        }
+    }
+
+    /// Apply `policy` to `path` for any method
+    pub fn route(self, path: &'static str, policy: RateLimitPolicy) -> Self {
+        self.rule(None, path, None, policy)
+    }
+
+    /// Apply `policy` to `path` for a single method
+    pub fn route_method(self, method: Method, path: &'static str, policy: RateLimitPolicy) -> Self {
+        self.rule(Some(method), path, None, policy)
+    }
+
+    /// Apply `policy` to `path` for a single method when the query string
+    /// carries a non-blank `param`; add it before any rule for the same route
+    pub fn route_query(
+        self,
+        method: Method,
+        path: &'static str,
+        param: &'static str,
+        policy: RateLimitPolicy,
+    ) -> Self {
+        self.rule(Some(method), path, Some(param), policy)
+    }
+
+    fn rule(
+        mut self,
+        method: Option<Method>,
+        path: &'static str,
+        query: Option<&'static str>,
+        policy: RateLimitPolicy,
+    ) -> Self {
+        self.rules.push(RouteRule {
+            method,
+            path,
+            query,
+            policy,
+        });
+        self
+    }
+
+    /// Find the policy for a request
+    pub fn resolve(
+        &self,
+        method: &Method,
+        matched_path: Option<&str>,
+        query: Option<&str>,
+    ) -> &RateLimitPolicy {
+        matched_path
+            .and_then(|path| self.rules.iter().find(|rule| rule.matches(method, path, query)))
+            .map(|rule| &rule.policy)
+            .unwrap_or(&self.default)
+    }
+
+    /// Policies for the public (unauthenticated) routes
+    pub fn anonymous(config: &Config) -> Self {
+        Self::new(RateLimitPolicy::per_minute(
+            "anonymous",
+            config.rate_limit_anonymous,
+        ))
+        .route(
+            "/api/v1/auth/login",
+            RateLimitPolicy::per_minute("login", config.rate_limit_login),
+        )
+        .route(
+            "/api/v1/auth/register",
+            RateLimitPolicy::per_minute("register", config.rate_limit_register),
+        )
+    }
+
+    /// Policy for requests that fail authentication on the protected routes,
+    /// counted per client IP
+    pub fn auth_failures(config: &Config) -> Self {
+        Self::new(RateLimitPolicy::per_minute(
+            "auth_failures",
+            config.rate_limit_auth_failures,
+        ))
+    }
+
+    /// Policies for the authenticated routes
+    ///
+    /// Note writes draw from the same budget as reads but cost more, as do
+    /// previews and bulk exports, which render notes on every call. Full-text
+    /// searches (`GET /api/v1/notes?q=`) have a smaller budget of their own.
+    pub fn authenticated(config: &Config) -> Self {
+        let default = RateLimitPolicy::per_minute("authenticated", config.rate_limit_authenticated);
+        let write = default.clone().with_cost(config.rate_limit_write_cost);
+
+        Self::new(default)
+            .route_query(
+                Method::GET,
+                "/api/v1/notes",
+                "q",
+                RateLimitPolicy::per_minute("search", config.rate_limit_search),
+            )
+            .route_method(Method::POST, "/api/v1/notes", write.clone())
+            .route_method(Method::PUT, "/api/v1/notes/:id", write.clone())
+            .route_method(Method::DELETE, "/api/v1/notes/:id", write.clone())
+            .route_method(Method::POST, "/api/v1/notes/bulk", write.clone())
+            .route_method(Method::POST, "/api/v1/notes/preview", write.clone())
+            .route_method(Method::POST, "/api/v1/notes/export", write.clone())
+            .route_method(Method::PUT, "/api/v1/notes/:id/state", write.clone())
+            .route_method(Method::PUT, "/api/v1/notes/state", write.clone())
+            .route_method(Method::POST, "/api/v1/notes/:id/move", write.clone())
+            .route_method(Method::POST, "/api/v1/notebooks", write.clone())
+            .route_method(Method::PUT, "/api/v1/notebooks/:id", write.clone())
+            .route_method(Method::DELETE, "/api/v1/notebooks/:id", write.clone())
+            .route_method(Method::POST, "/api/v1/notebooks/:id/move", write)
+    }
+}
@@ -0,0 +1,39 @@
+use axum::{extract::Request, middleware::Next, response::Response};
+
+use crate::telemetry::REQUEST_ID_HEADER;
+
+tokio::task_local! {
+    static CURRENT: RequestContext;
+}
+
+/// Per-request details available anywhere while the request is handled,
+/// e.g. to stamp error responses with the request ID
+#[derive(Debug, Clone)]
+pub struct RequestContext {
+    pub request_id: Option<String>,
+    /// Request path without the query string, which may carry secrets
+    pub path: String,
+}
+
+impl RequestContext {
+    /// Context of the request being handled, if called from inside one
+    pub fn current() -> Option<Self> {
+        CURRENT.try_with(Clone::clone).ok()
+    }
+}
+
+/// Make the current request's context available via [`RequestContext::current`]
+///
+/// Must run inside the layer that assigns request IDs.
+pub async fn request_context_middleware(req: Request, next: Next) -> Response {
+    let context = RequestContext {
+        request_id: req
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string),
+        path: req.uri().path().to_string(),
+    };
+
+    CURRENT.scope(context, next.run(req)).await
+}
@@ -0,0 +1,26 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderValue, Version},
+    middleware::Next,
+    response::Response,
+};
+use tokio_util::sync::CancellationToken;
+
+/// Once shutdown has begun, tell HTTP/1.1 clients not to reuse their
+/// connection so they reconnect to another instance for the next request
+pub async fn drain_middleware(
+    State(shutdown): State<CancellationToken>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let keep_alive_capable = matches!(req.version(), Version::HTTP_10 | Version::HTTP_11);
+    let mut response = next.run(req).await;
+
+    if shutdown.is_cancelled() && keep_alive_capable {
+        response
+            .headers_mut()
+            .insert(header::CONNECTION, HeaderValue::from_static("close"));
+    }
+
+    response
+}
@@ -1,11 +1,13 @@
 pub mod user;
 pub mod note;
+pub mod notebook;
 pub mod revision;
 pub mod tag;
 pub mod session;
 
 pub use user::*;
 pub use note::*;
+pub use notebook::*;
 pub use revision::*;
 pub use tag::*;
 pub use session::*;
\ No newline at end of file
@@ -1,9 +1,15 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use sqlx::FromRow;
+use std::fmt;
+use std::str::FromStr;
 use uuid::Uuid;
+use utoipa::{IntoParams, ToSchema};
 use validator::Validate;
 
+use crate::utils::validation::not_blank;
+
 #[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
 pub struct Note {
     pub id: Uuid,
@@ -14,42 +20,518 @@ pub struct Note {
     pub is_deleted: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub notebook_id: Option<Uuid>,
+    /// Manual order within the notebook
+    pub position: i32,
+    pub is_pinned: bool,
+    pub is_archived: bool,
+    pub is_favorite: bool,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct NoteResponse {
     pub id: Uuid,
     pub title: String,
     pub content: String,
     pub last_edited_by: Option<Uuid>,
+    /// Containing notebook; `null` at the top level
+    pub notebook_id: Option<Uuid>,
+    /// Listed before unpinned notes
+    pub pinned: bool,
+    /// Left out of lists unless asked for
+    pub archived: bool,
+    pub favorite: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub tags: Vec<String>,
+    /// The content rendered as sanitized HTML; only with `format=html`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub html: Option<String>,
+}
+
+/// How a note's content is returned
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum NoteFormat {
+    /// As stored
+    #[default]
+    Markdown,
+    /// As stored, plus `html` rendered from it
+    Html,
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct NoteFormatParams {
+    /// Defaults to `markdown`
+    #[param(inline)]
+    pub format: Option<NoteFormat>,
+}
+
+/// File format of an export
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    /// Markdown with YAML front matter holding the title, tags and timestamps
+    #[default]
+    Markdown,
+    /// A standalone page with the note rendered as sanitized HTML
+    Html,
+    /// Readable text without Markdown syntax
+    Text,
+    /// The note as the API returns it
+    Json,
+}
+
+impl ExportFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "md",
+            ExportFormat::Html => "html",
+            ExportFormat::Text => "txt",
+            ExportFormat::Json => "json",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "text/markdown; charset=utf-8",
+            ExportFormat::Html => "text/html; charset=utf-8",
+            ExportFormat::Text => "text/plain; charset=utf-8",
+            ExportFormat::Json => "application/json",
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportParams {
+    /// Defaults to `markdown`
+    #[param(inline)]
+    pub format: Option<ExportFormat>,
+}
+
+/// Notes to export as a ZIP archive: either `note_ids` or `tag`
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct ExportNotesRequest {
+    #[validate(length(min = 1, max = 100, message = "Give between 1 and 100 note IDs"))]
+    #[schema(min_items = 1, max_items = 100)]
+    pub note_ids: Option<Vec<Uuid>>,
+    /// Export every note with this tag
+    pub tag: Option<String>,
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct PreviewRequest {
+    /// Markdown to render; limited to the maximum note size
+    pub content: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PreviewResponse {
+    /// Sanitized HTML, safe to insert into a page
+    pub html: String,
 }
 
-#[derive(Debug, Deserialize, Validate)]
+#[derive(Debug, Deserialize, Validate, ToSchema)]
 pub struct CreateNoteRequest {
-    #[validate(length(min = 1, max = 255))]
+    #[validate(length(min = 1, max = 255), custom(function = "not_blank"))]
+    #[schema(min_length = 1, max_length = 255)]
     pub title: String,
     pub content: Option<String>,
+    /// Create inside this notebook, after its existing notes; omit for the top level
+    pub notebook_id: Option<Uuid>,
 }
 
-#[derive(Debug, Deserialize, Validate)]
+#[derive(Debug, Deserialize, Validate, ToSchema)]
 pub struct UpdateNoteRequest {
-    #[validate(length(min = 1, max = 255))]
+    #[validate(length(min = 1, max = 255), custom(function = "not_blank"))]
+    #[schema(min_length = 1, max_length = 255)]
     pub title: Option<String>,
     pub content: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
+/// States to change; omitted ones are left as they are
+#[derive(Debug, Default, Deserialize, Validate, ToSchema)]
+pub struct NoteStateRequest {
+    pub pinned: Option<bool>,
+    pub archived: Option<bool>,
+    pub favorite: Option<bool>,
+}
+
+impl NoteStateRequest {
+    pub fn is_empty(&self) -> bool {
+        self.pinned.is_none() && self.archived.is_none() && self.favorite.is_none()
+    }
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct BulkNoteStateRequest {
+    #[validate(length(min = 1, max = 100, message = "Give between 1 and 100 note IDs"))]
+    #[schema(min_items = 1, max_items = 100)]
+    pub note_ids: Vec<Uuid>,
+    #[serde(flatten)]
+    pub state: NoteStateRequest,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BulkNoteStateResponse {
+    pub updated: Vec<Uuid>,
+    /// Requested notes that don't exist, are trashed or belong to someone else
+    pub not_found: Vec<Uuid>,
+}
+
+/// One action applied to many notes in a single transaction
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct BulkNoteRequest {
+    #[validate(length(min = 1, max = 100, message = "Give between 1 and 100 note IDs"))]
+    #[schema(min_items = 1, max_items = 100)]
+    pub note_ids: Vec<Uuid>,
+    #[serde(flatten)]
+    pub action: BulkNoteAction,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum BulkNoteAction {
+    /// Move notes to the trash
+    Delete,
+    /// Bring notes back from the trash
+    Restore,
+    /// Add tags, creating any the caller doesn't have yet
+    Tag { tags: Vec<String> },
+    /// Remove tags
+    Untag { tags: Vec<String> },
+}
+
+/// Outcome for one requested note
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BulkNoteResult {
+    pub id: Uuid,
+    pub ok: bool,
+    /// Why the note was skipped, e.g. `note_not_found`; absent on success
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BulkNoteResponse {
+    pub succeeded: usize,
+    pub failed: usize,
+    /// One entry per distinct requested note, in request order
+    pub results: Vec<BulkNoteResult>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct NoteListResponse {
-    pub notes: Vec<NoteResponse>,
-    pub total: i64,
+    pub notes: Vec<NoteListItem>,
+    /// Notes matching the filters; only with `include_total=true`, or on
+    /// `page` requests unless `include_total=false`, as it costs an extra query
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total: Option<i64>,
+    /// Pass as `cursor` to fetch the next page; absent on the last page
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+/// A note in a list; which fields are present depends on `view` and `fields`
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct NoteListItem {
+    pub id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    /// Start of the content as plain text
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub excerpt: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub word_count: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "present"
+    )]
+    #[schema(value_type = Option<Uuid>)]
+    pub last_edited_by: Option<Option<Uuid>>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "present"
+    )]
+    #[schema(value_type = Option<Uuid>)]
+    pub notebook_id: Option<Option<Uuid>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pinned: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archived: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub favorite: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+/// Tells a field that is `null` apart from one that is missing
+fn present<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// Selectable fields of a [`NoteListItem`]; `id` is always included
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum NoteField {
+    Title,
+    Content,
+    Excerpt,
+    WordCount,
+    Tags,
+    LastEditedBy,
+    NotebookId,
+    Pinned,
+    Archived,
+    Favorite,
+    CreatedAt,
+    UpdatedAt,
+}
+
+impl NoteField {
+    pub const ALL: [NoteField; 12] = [
+        NoteField::Title,
+        NoteField::Content,
+        NoteField::Excerpt,
+        NoteField::WordCount,
+        NoteField::Tags,
+        NoteField::LastEditedBy,
+        NoteField::NotebookId,
+        NoteField::Pinned,
+        NoteField::Archived,
+        NoteField::Favorite,
+        NoteField::CreatedAt,
+        NoteField::UpdatedAt,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NoteField::Title => "title",
+            NoteField::Content => "content",
+            NoteField::Excerpt => "excerpt",
+            NoteField::WordCount => "word_count",
+            NoteField::Tags => "tags",
+            NoteField::LastEditedBy => "last_edited_by",
+            NoteField::NotebookId => "notebook_id",
+            NoteField::Pinned => "pinned",
+            NoteField::Archived => "archived",
+            NoteField::Favorite => "favorite",
+            NoteField::CreatedAt => "created_at",
+            NoteField::UpdatedAt => "updated_at",
+        }
+    }
+}
+
+impl FromStr for NoteField {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        NoteField::ALL
+            .into_iter()
+            .find(|f| f.as_str() == s)
+            .ok_or_else(|| {
+                let names: Vec<_> = NoteField::ALL.iter().map(NoteField::as_str).collect();
+                format!("Unknown field '{}'; expected any of {}", s, names.join(", "))
+            })
+    }
+}
+
+/// Preset field selections for note lists
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum NoteView {
+    /// Every field, including the full content
+    #[default]
+    Full,
+    /// Title, excerpt, word count, tags, pin, favorite and update time; for sidebars
+    Summary,
+}
+
+impl NoteView {
+    pub fn fields(&self) -> &'static [NoteField] {
+        match self {
+            NoteView::Full => &[
+                NoteField::Title,
+                NoteField::Content,
+                NoteField::Tags,
+                NoteField::LastEditedBy,
+                NoteField::NotebookId,
+                NoteField::Pinned,
+                NoteField::Archived,
+                NoteField::Favorite,
+                NoteField::CreatedAt,
+                NoteField::UpdatedAt,
+            ],
+            NoteView::Summary => &[
+                NoteField::Title,
+                NoteField::Excerpt,
+                NoteField::WordCount,
+                NoteField::Tags,
+                NoteField::Pinned,
+                NoteField::Favorite,
+                NoteField::UpdatedAt,
+            ],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum NoteSort {
+    #[default]
+    UpdatedAt,
+    CreatedAt,
+    /// Case-insensitive
+    Title,
+    /// Manual order within a notebook; combine with `notebook_id`
+    Position,
+}
+
+impl NoteSort {
+    /// Dates list newest first, titles alphabetically
+    pub fn default_order(&self) -> SortOrder {
+        match self {
+            NoteSort::UpdatedAt | NoteSort::CreatedAt => SortOrder::Desc,
+            NoteSort::Title | NoteSort::Position => SortOrder::Asc,
+        }
+    }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+/// Keyset position after the last note of a page
+///
+/// Handed to clients as an opaque string. Paging by position rather than
+/// offset means edits made while paging never skip or repeat notes. The
+/// sort is recorded so a cursor can't be replayed against another ordering.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NoteCursor {
+    pub sort: NoteSort,
+    pub order: SortOrder,
+    /// Pinned notes come first whatever the sort
+    #[serde(default)]
+    pub pinned: bool,
+    pub key: CursorKey,
+    pub id: Uuid,
+}
+
+/// Sort key of the last note on a page
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CursorKey {
+    Time(DateTime<Utc>),
+    Text(String),
+    Number(i64),
+}
+
+impl NoteCursor {
+    pub fn encode(&self) -> String {
+        let json = serde_json::to_vec(self).expect("cursor serializes");
+        URL_SAFE_NO_PAD.encode(json)
+    }
+
+    pub fn decode(cursor: &str) -> Option<Self> {
+        let json = URL_SAFE_NO_PAD.decode(cursor).ok()?;
+        serde_json::from_slice(&json).ok()
+    }
+}
+
+/// Which notebook's notes to list
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotebookFilter {
+    /// Notes outside any notebook
+    Root,
+    Notebook(Uuid),
+}
+
+impl FromStr for NotebookFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "root" {
+            return Ok(NotebookFilter::Root);
+        }
+        s.parse()
+            .map(NotebookFilter::Notebook)
+            .map_err(|_| format!("Expected a notebook ID or 'root', got '{}'", s))
+    }
+}
+
+impl fmt::Display for NotebookFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NotebookFilter::Root => f.write_str("root"),
+            NotebookFilter::Notebook(id) => id.fmt(f),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for NotebookFilter {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct NoteQueryParams {
+    /// `next_cursor` from the previous page; omit for the first page
+    pub cursor: Option<String>,
+    /// 1-based page number; offset paging kept for older clients, prefer `cursor`
+    #[param(minimum = 1)]
     pub page: Option<i64>,
+    /// Page size, at most 100
+    #[param(minimum = 1, maximum = 100)]
     pub limit: Option<i64>,
+    /// Include `total` in the response; defaults to `true` with `page`, otherwise `false`
+    pub include_total: Option<bool>,
+    /// Defaults to `updated_at`
+    #[param(inline)]
+    pub sort: Option<NoteSort>,
+    /// Defaults to `desc` for dates and `asc` for titles
+    #[param(inline)]
+    pub order: Option<SortOrder>,
+    /// Defaults to `full`
+    #[param(inline)]
+    pub view: Option<NoteView>,
+    /// Comma-separated fields to return instead of a `view`, e.g. `title,updated_at`
+    pub fields: Option<String>,
+    /// Only notes directly in this notebook, or `root` for notes outside any notebook
+    #[param(value_type = Option<String>)]
+    pub notebook_id: Option<NotebookFilter>,
+    /// Only pinned (`true`) or unpinned (`false`) notes
+    pub pinned: Option<bool>,
+    /// Only favorites (`true`) or non-favorites (`false`)
+    pub favorite: Option<bool>,
+    /// Only archived (`true`) or unarchived (`false`) notes; by default
+    /// archived notes are left out, except from searches
+    pub archived: Option<bool>,
+    /// Full-text search over titles and content, e.g. `"exact phrase" -excluded`
+    pub q: Option<String>,
+    /// Only notes with this tag
     pub tag: Option<String>,
-}
\ No newline at end of file
+}
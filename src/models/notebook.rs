@@ -0,0 +1,75 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+use utoipa::ToSchema;
+use validator::Validate;
+
+use crate::utils::validation::not_blank;
+
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct Notebook {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub parent_id: Option<Uuid>,
+    pub name: String,
+    pub position: i32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct NotebookResponse {
+    pub id: Uuid,
+    /// Containing notebook; `null` at the top level
+    pub parent_id: Option<Uuid>,
+    pub name: String,
+    /// Order among the notebook's siblings, starting at 0
+    pub position: i32,
+    /// Notes directly in this notebook, excluding trashed ones
+    pub note_count: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Every notebook of the caller, parents before children and siblings in order
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct NotebookListResponse {
+    pub notebooks: Vec<NotebookResponse>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct CreateNotebookRequest {
+    #[validate(length(min = 1, max = 100), custom(function = "not_blank"))]
+    #[schema(min_length = 1, max_length = 100)]
+    pub name: String,
+    /// Create inside this notebook; omit for the top level
+    pub parent_id: Option<Uuid>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct UpdateNotebookRequest {
+    #[validate(length(min = 1, max = 100), custom(function = "not_blank"))]
+    #[schema(min_length = 1, max_length = 100)]
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct MoveNotebookRequest {
+    /// New parent; `null` or omitted moves the notebook to the top level
+    pub parent_id: Option<Uuid>,
+    /// Index among the new siblings; omit to append at the end
+    #[validate(range(min = 0))]
+    #[schema(minimum = 0)]
+    pub position: Option<i32>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct MoveNoteRequest {
+    /// Destination notebook; `null` or omitted moves the note to the top level
+    pub notebook_id: Option<Uuid>,
+    /// Index among the notes already there; omit to append at the end
+    #[validate(range(min = 0))]
+    #[schema(minimum = 0)]
+    pub position: Option<i32>,
+}
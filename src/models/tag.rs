@@ -4,6 +4,8 @@ use sqlx::FromRow;
 use uuid::Uuid;
 use validator::Validate;
 
+use crate::utils::validation::not_blank;
+
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct Tag {
     pub id: Uuid,
@@ -21,7 +23,7 @@ pub struct TagResponse {
 
 #[derive(Debug, Deserialize, Validate)]
 pub struct CreateTagRequest {
-    #[validate(length(min = 1, max = 50))]
+    #[validate(length(min = 1, max = 50), custom(function = "not_blank"))]
     pub name: String,
 }
 
@@ -2,8 +2,11 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use uuid::Uuid;
+use utoipa::ToSchema;
 use validator::Validate;
 
+use crate::utils::validation::not_blank;
+
 #[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
 pub struct User {
     pub id: Uuid,
@@ -12,9 +15,36 @@ pub struct User {
     pub display_name: String,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub disabled_at: Option<DateTime<Utc>>,
+}
+
+impl User {
+    pub fn is_disabled(&self) -> bool {
+        self.disabled_at.is_some()
+    }
 }
 
-#[derive(Debug, Serialize)]
+/// The caller behind a verified access token, as handlers see it
+///
+/// Carries no password hash, so it is safe to cache and pass around.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthUser {
+    pub id: Uuid,
+    pub email: String,
+    pub display_name: String,
+}
+
+impl From<User> for AuthUser {
+    fn from(user: User) -> Self {
+        Self {
+            id: user.id,
+            email: user.email,
+            display_name: user.display_name,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
 pub struct UserResponse {
     pub id: Uuid,
     pub email: String,
@@ -33,30 +63,49 @@ impl From<User> for UserResponse {
     }
 }
 
-#[derive(Debug, Deserialize, Validate)]
+#[derive(Debug, Deserialize, Validate, ToSchema)]
 pub struct RegisterRequest {
-    #[validate(email)]
+    #[validate(email, length(max = 255))]
+    #[schema(format = "email", max_length = 255)]
     pub email: String,
     #[validate(length(min = 8, max = 128))]
+    #[schema(format = Password, min_length = 8, max_length = 128)]
     pub password: String,
-    #[validate(length(min = 1, max = 100))]
+    #[schema(min_length = 1, max_length = 100)]
+    #[validate(length(min = 1, max = 100), custom(function = "not_blank"))]
     pub display_name: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Validate, ToSchema)]
 pub struct LoginRequest {
+    #[validate(length(min = 1))]
     pub email: String,
+    #[validate(length(min = 1))]
+    #[schema(format = Password)]
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ResetPasswordRequest {
+    #[validate(length(min = 8, max = 128))]
     pub password: String,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct AuthResponse {
     pub user: UserResponse,
     pub access_token: String,
     pub refresh_token: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Validate, ToSchema)]
 pub struct RefreshTokenRequest {
+    #[validate(length(min = 1))]
     pub refresh_token: String,
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TokenPairResponse {
+    pub access_token: String,
+    pub refresh_token: String,
+}
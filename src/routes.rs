@@ -0,0 +1,93 @@
+//! API routes and the OpenAPI document generated from them
+//!
+//! Routes are registered through `utoipa-axum` so every handler added here
+//! is documented from its `#[utoipa::path]` attribute; a route cannot be
+//! served without appearing in the spec.
+
+use std::sync::Arc;
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+use crate::handlers::{auth, notebooks, notes};
+use crate::services::{AuthService, NoteService};
+
+/// Where the spec and the interactive docs are served
+pub const OPENAPI_PATH: &str = "/api/v1/openapi.json";
+pub const DOCS_PATH: &str = "/api/v1/docs";
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "NoteFlow API",
+        description = "Notes with tags, authenticated with JWT bearer tokens. \
+                       Errors are returned as RFC 7807 `application/problem+json`.",
+        license(name = "MIT")
+    ),
+    modifiers(&SecurityAddon),
+    tags(
+        (name = "auth", description = "Registration, login and token refresh"),
+        (name = "notes", description = "Notes owned by the authenticated user"),
+        (name = "notebooks", description = "Nested notebooks for filing and ordering notes"),
+    )
+)]
+struct ApiDoc;
+
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer_auth",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .build(),
+            ),
+        );
+    }
+}
+
+/// Public authentication endpoints
+pub fn auth() -> OpenApiRouter<Arc<AuthService>> {
+    OpenApiRouter::new()
+        .routes(routes!(auth::register))
+        .routes(routes!(auth::login))
+        .routes(routes!(auth::refresh))
+}
+
+/// Note and notebook endpoints; callers must add the auth middleware
+pub fn notes() -> OpenApiRouter<Arc<NoteService>> {
+    OpenApiRouter::new()
+        .routes(routes!(notes::list_notes, notes::create_note))
+        .routes(routes!(
+            notes::get_note,
+            notes::update_note,
+            notes::delete_note
+        ))
+        .routes(routes!(notes::bulk_notes))
+        .routes(routes!(notes::preview_note))
+        .routes(routes!(notes::export_note))
+        .routes(routes!(notes::export_notes))
+        .routes(routes!(notes::set_note_state))
+        .routes(routes!(notes::bulk_set_note_state))
+        .routes(routes!(notes::move_note))
+        .routes(routes!(notebooks::list_notebooks, notebooks::create_notebook))
+        .routes(routes!(
+            notebooks::get_notebook,
+            notebooks::update_notebook,
+            notebooks::delete_notebook
+        ))
+        .routes(routes!(notebooks::move_notebook))
+}
+
+/// The complete OpenAPI document for the API
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    let mut doc = ApiDoc::openapi();
+    doc.info.version = crate::VERSION.to_string();
+    doc.merge(auth().into_openapi());
+    doc.merge(notes().into_openapi());
+    doc
+}
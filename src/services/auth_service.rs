@@ -1,62 +1,180 @@
+use sha2::{Digest, Sha256};
 use sqlx::PgPool;
+use tracing::Instrument;
 use uuid::Uuid;
-use crate::models::user::{User, RegisterRequest, LoginRequest, AuthResponse};
-use crate::utils::{jwt::JwtManager, errors::{AppError, Result}, validation};
+use crate::db::db_span;
+use crate::metrics::AUTH_LOGINS_TOTAL;
+use crate::models::user::{User, AuthUser, RegisterRequest, LoginRequest, AuthResponse, ResetPasswordRequest};
+use crate::services::UserCache;
+use crate::utils::{jwt::JwtManager, errors::{AppError, ErrorCode, Result}, validation};
+use std::net::IpAddr;
 use std::sync::Arc;
 
 pub struct AuthService {
     pool: PgPool,
     jwt_manager: Arc<JwtManager>,
+    user_cache: Option<UserCache>,
 }
 
 impl AuthService {
     pub fn new(pool: PgPool, jwt_manager: Arc<JwtManager>) -> Self {
-        Self { pool, jwt_manager }
+        Self { pool, jwt_manager, user_cache: None }
     }
 
-    pub async fn register(&self, req: RegisterRequest) -> Result<AuthResponse> {
-        validation::validate_email(&req.email)?;
-        validation::validate_password(&req.password)?;
-        
+    /// Cache `authenticate` lookups, invalidating entries on account changes
+    pub fn with_user_cache(mut self, cache: UserCache) -> Self {
+        self.user_cache = Some(cache);
+        self
+    }
+
+    /// Resolve the active user behind a verified access token
+    #[tracing::instrument(skip(self))]
+    pub async fn authenticate(&self, user_id: Uuid) -> Result<AuthUser> {
+        if let Some(cache) = &self.user_cache {
+            if let Some(user) = cache.get(user_id).await {
+                return Ok(user);
+            }
+        }
+
+        let user = sqlx::query_as!(
+            User,
+            "SELECT * FROM users WHERE id = $1",
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .instrument(db_span!("SELECT", "users"))
+        .await?
+        .ok_or_else(|| {
+            tracing::warn!("User not found for ID: {}", user_id);
+            AppError::problem(ErrorCode::InvalidToken, "User not found")
+        })?;
+
+        // Disabled users are never cached, so re-enabling takes effect at once
+        if user.is_disabled() {
+            tracing::warn!("Rejected token for disabled user: {}", user_id);
+            return Err(AppError::problem(ErrorCode::AccountDisabled, "Account disabled"));
+        }
+
+        let user = AuthUser::from(user);
+        if let Some(cache) = &self.user_cache {
+            cache.put(&user).await;
+        }
+
+        Ok(user)
+    }
+
+    /// Create an account for a client at `ip` and issue its first token pair
+    #[tracing::instrument(skip_all, fields(client_ip = %ip))]
+    pub async fn register(&self, req: RegisterRequest, ip: IpAddr) -> Result<AuthResponse> {
+        let user = self.create_user(&req).await?;
+
+        // Generate tokens
+        let access_token = self.jwt_manager.generate_access_token(user.id, user.email.clone())?;
+        let refresh_token = self.jwt_manager.generate_refresh_token(user.id, user.email.clone())?;
+
+        Ok(AuthResponse {
+            user: user.into(),
+            access_token,
+            refresh_token,
+        })
+    }
+
+    /// Create an account without issuing tokens; `req` must already be validated
+    #[tracing::instrument(skip_all)]
+    pub async fn create_user(&self, req: &RegisterRequest) -> Result<User> {
         let email = validation::sanitize_string(&req.email).to_lowercase();
         let display_name = validation::sanitize_string(&req.display_name);
 
         // Check if email already exists
         let existing = sqlx::query!("SELECT id FROM users WHERE email = $1", email)
             .fetch_optional(&self.pool)
+            .instrument(db_span!("SELECT", "users"))
             .await?;
 
         if existing.is_some() {
-            return Err(AppError::Conflict("Email already registered".to_string()));
+            return Err(AppError::problem(ErrorCode::EmailTaken, "Email already registered"));
         }
 
-        // Hash password
-        let password_hash = bcrypt::hash(&req.password, bcrypt::DEFAULT_COST)
-            .map_err(|e| AppError::InternalError(format!("Password hashing failed: {}", e)))?;
+        let password_hash = hash_password(&req.password)?;
 
         // Create user
         let user = sqlx::query_as!(
             User,
             r#"INSERT INTO users (email, password_hash, display_name)
                VALUES ($1, $2, $3)
-               RETURNING id, email, password_hash, display_name, created_at, updated_at"#,
+               RETURNING id, email, password_hash, display_name, created_at, updated_at, disabled_at"#,
             email, password_hash, display_name
         )
         .fetch_one(&self.pool)
+        .instrument(db_span!("INSERT", "users"))
         .await?;
 
-        // Generate tokens
-        let access_token = self.jwt_manager.generate_access_token(user.id, user.email.clone())?;
-        let refresh_token = self.jwt_manager.generate_refresh_token(user.id, user.email.clone())?;
+        Ok(user)
+    }
 
-        Ok(AuthResponse {
-            user: user.into(),
-            access_token,
-            refresh_token,
-        })
+    #[tracing::instrument(skip(self, email))]
+    pub async fn find_by_email(&self, email: &str) -> Result<User> {
+        let email = validation::sanitize_string(email).to_lowercase();
+
+        sqlx::query_as!(User, "SELECT * FROM users WHERE email = $1", email)
+            .fetch_optional(&self.pool)
+            .instrument(db_span!("SELECT", "users"))
+            .await?
+            .ok_or_else(|| AppError::problem(ErrorCode::UserNotFound, "User not found"))
+    }
+
+    /// Disable or re-enable an account; disabled users can't log in and
+    /// their outstanding tokens stop working
+    #[tracing::instrument(skip(self, email))]
+    pub async fn set_disabled(&self, email: &str, disabled: bool) -> Result<User> {
+        let email = validation::sanitize_string(email).to_lowercase();
+
+        let user = sqlx::query_as!(
+            User,
+            r#"UPDATE users
+               SET disabled_at = CASE WHEN $2 THEN COALESCE(disabled_at, NOW()) END
+               WHERE email = $1
+               RETURNING id, email, password_hash, display_name, created_at, updated_at, disabled_at"#,
+            email, disabled
+        )
+        .fetch_optional(&self.pool)
+        .instrument(db_span!("UPDATE", "users"))
+        .await?
+        .ok_or_else(|| AppError::problem(ErrorCode::UserNotFound, "User not found"))?;
+
+        self.invalidate_cached_user(user.id).await;
+        Ok(user)
+    }
+
+    /// Replace a user's password; `req` must already be validated
+    #[tracing::instrument(skip(self, email, req))]
+    pub async fn reset_password(&self, email: &str, req: &ResetPasswordRequest) -> Result<()> {
+        let email = validation::sanitize_string(email).to_lowercase();
+        let password_hash = hash_password(&req.password)?;
+
+        let user = sqlx::query!(
+            "UPDATE users SET password_hash = $1 WHERE email = $2 RETURNING id",
+            password_hash, email
+        )
+        .fetch_optional(&self.pool)
+        .instrument(db_span!("UPDATE", "users"))
+        .await?
+        .ok_or_else(|| AppError::problem(ErrorCode::UserNotFound, "User not found"))?;
+
+        self.invalidate_cached_user(user.id).await;
+        Ok(())
     }
 
-    pub async fn login(&self, req: LoginRequest) -> Result<AuthResponse> {
+    /// Drop a user from the auth cache; call after any change to their account
+    pub async fn invalidate_cached_user(&self, user_id: Uuid) {
+        if let Some(cache) = &self.user_cache {
+            cache.invalidate(user_id).await;
+        }
+    }
+
+    /// Check credentials sent from `ip` and issue a token pair
+    #[tracing::instrument(skip_all, fields(client_ip = %ip))]
+    pub async fn login(&self, req: LoginRequest, ip: IpAddr) -> Result<AuthResponse> {
         let email = validation::sanitize_string(&req.email).to_lowercase();
 
         // Fetch user
@@ -66,21 +184,41 @@ impl AuthService {
             email
         )
         .fetch_optional(&self.pool)
+        .instrument(db_span!("SELECT", "users"))
         .await?
-        .ok_or_else(|| AppError::AuthenticationError("Invalid credentials".to_string()))?;
+        .ok_or_else(|| {
+            // Never the address itself, which would end up in exported logs
+            tracing::warn!(
+                "Login failed for unknown email {} from {}",
+                email_fingerprint(&email),
+                ip
+            );
+            metrics::counter!(AUTH_LOGINS_TOTAL, "outcome" => "failure").increment(1);
+            AppError::problem(ErrorCode::InvalidCredentials, "Invalid credentials")
+        })?;
 
         // Verify password
         let password_valid = bcrypt::verify(&req.password, &user.password_hash)
             .map_err(|e| AppError::InternalError(format!("Password verification failed: {}", e)))?;
 
         if !password_valid {
-            return Err(AppError::AuthenticationError("Invalid credentials".to_string()));
+            tracing::warn!("Login failed for user {} from {}: wrong password", user.id, ip);
+            metrics::counter!(AUTH_LOGINS_TOTAL, "outcome" => "failure").increment(1);
+            return Err(AppError::problem(ErrorCode::InvalidCredentials, "Invalid credentials"));
+        }
+
+        if user.is_disabled() {
+            tracing::warn!("Login refused for disabled user {} from {}", user.id, ip);
+            metrics::counter!(AUTH_LOGINS_TOTAL, "outcome" => "disabled").increment(1);
+            return Err(AppError::problem(ErrorCode::AccountDisabled, "Account disabled"));
         }
 
         // Generate tokens
         let access_token = self.jwt_manager.generate_access_token(user.id, user.email.clone())?;
         let refresh_token = self.jwt_manager.generate_refresh_token(user.id, user.email.clone())?;
 
+        metrics::counter!(AUTH_LOGINS_TOTAL, "outcome" => "success").increment(1);
+
         Ok(AuthResponse {
             user: user.into(),
             access_token,
@@ -88,17 +226,23 @@ impl AuthService {
         })
     }
 
+    #[tracing::instrument(skip_all)]
     pub async fn refresh_token(&self, refresh_token: &str) -> Result<(String, String)> {
         // Verify refresh token
         let claims = self.jwt_manager.verify_refresh_token(refresh_token)?;
         let user_id = Uuid::parse_str(&claims.sub)
-            .map_err(|_| AppError::AuthenticationError("Invalid user ID".to_string()))?;
+            .map_err(|_| AppError::problem(ErrorCode::InvalidToken, "Invalid user ID"))?;
 
         // Fetch user
-        let user = sqlx::query!("SELECT email FROM users WHERE id = $1", user_id)
+        let user = sqlx::query!("SELECT email, disabled_at FROM users WHERE id = $1", user_id)
             .fetch_optional(&self.pool)
+            .instrument(db_span!("SELECT", "users"))
             .await?
-            .ok_or_else(|| AppError::AuthenticationError("User not found".to_string()))?;
+            .ok_or_else(|| AppError::problem(ErrorCode::InvalidToken, "User not found"))?;
+
+        if user.disabled_at.is_some() {
+            return Err(AppError::problem(ErrorCode::AccountDisabled, "Account disabled"));
+        }
 
         // Generate new tokens
         let new_access = self.jwt_manager.generate_access_token(user_id, user.email.clone())?;
@@ -106,4 +250,16 @@ impl AuthService {
 
         Ok((new_access, new_refresh))
     }
-}
\ No newline at end of file
+}
+
+/// Stable short stand-in for an email address, so repeated failures for the
+/// same address can be correlated in logs without recording it
+fn email_fingerprint(email: &str) -> String {
+    let digest = Sha256::digest(email.as_bytes());
+    format!("sha256:{:x}", digest)[..19].to_string()
+}
+
+fn hash_password(password: &str) -> Result<String> {
+    bcrypt::hash(password, bcrypt::DEFAULT_COST)
+        .map_err(|e| AppError::InternalError(format!("Password hashing failed: {}", e)))
+}
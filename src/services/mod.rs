@@ -1,5 +1,11 @@
 pub mod auth_service;
+pub mod note_cache;
+pub mod note_export;
 pub mod note_service;
+pub mod user_cache;
 
 pub use auth_service::AuthService;
-pub use note_service::NoteService;
\ No newline at end of file
+pub use note_cache::NoteCache;
+pub use note_export::ExportFile;
+pub use note_service::NoteService;
+pub use user_cache::UserCache;
\ No newline at end of file
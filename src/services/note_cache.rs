@@ -0,0 +1,212 @@
+//! Read-through Redis cache for note reads
+//!
+//! Entries are scoped to the owning user, so a hit never needs an ownership
+//! check. Redis failures are logged and treated as misses: the cache can
+//! make reads faster but never makes them fail.
+
+use serde::{de::DeserializeOwned, Serialize};
+use uuid::Uuid;
+
+use crate::db::RedisManager;
+use crate::metrics::CACHE_REQUESTS_TOTAL;
+use crate::models::note::{NoteListResponse, NoteResponse};
+use crate::utils::{errors::Result, text};
+
+const NOTE: &str = "note";
+const NOTE_LIST: &str = "note_list";
+const NOTE_HTML: &str = "note_html";
+
+/// Generation of a key that has never been invalidated, or not for a while
+const INITIAL_GENERATION: &str = "0";
+
+/// Outcome of a cache read
+pub enum Lookup<T> {
+    Hit(T),
+    /// Pass the slot to the matching `put_*` once the value is loaded
+    Miss(CacheSlot),
+}
+
+/// Where a missed entry is stored: its key under the generation current when
+/// the read began
+///
+/// Invalidation moves to a new generation instead of deleting entries, so a
+/// read that loaded its value before an invalidation but stores it after
+/// writes under the old generation, where no later read looks.
+pub struct CacheSlot(String);
+
+pub struct NoteCache {
+    redis: RedisManager,
+    ttl_secs: usize,
+}
+
+impl NoteCache {
+    pub fn new(redis: RedisManager, ttl_secs: u64) -> Self {
+        Self {
+            redis,
+            ttl_secs: ttl_secs as usize,
+        }
+    }
+
+    pub async fn get_note(&self, user_id: Uuid, note_id: Uuid) -> Lookup<NoteResponse> {
+        let generation = self.generation(&note_generation_key(user_id, note_id)).await;
+        self.lookup(NOTE, format!("cache:note:{}:{}:{}", user_id, note_id, generation))
+            .await
+    }
+
+    pub async fn put_note(&self, slot: CacheSlot, note: &NoteResponse) {
+        self.put(NOTE, slot, note).await;
+    }
+
+    /// A cached list page; `page_key` identifies the query, e.g. its page and limit
+    pub async fn get_list(&self, user_id: Uuid, page_key: &str) -> Lookup<NoteListResponse> {
+        let generation = self.generation(&list_generation_key(user_id)).await;
+        self.lookup(
+            NOTE_LIST,
+            format!("cache:notes:{}:{}:{}", user_id, generation, page_key),
+        )
+        .await
+    }
+
+    pub async fn put_list(&self, slot: CacheSlot, list: &NoteListResponse) {
+        self.put(NOTE_LIST, slot, list).await;
+    }
+
+    /// Rendered HTML of content with the given SHA-256 hash
+    ///
+    /// Keyed by content rather than note, so edits never need to invalidate
+    /// it and identical content is rendered once.
+    pub async fn get_html(&self, content_hash: &str) -> Option<String> {
+        let cached = self.redis.clone().get(&html_key(content_hash)).await;
+        decode(NOTE_HTML, cached)
+    }
+
+    pub async fn put_html(&self, content_hash: &str, html: &str) {
+        let Some(json) = encode(NOTE_HTML, &html) else {
+            return;
+        };
+        let result = self
+            .redis
+            .clone()
+            .set_with_expiry(&html_key(content_hash), &json, self.ttl_secs)
+            .await;
+        log_failure(NOTE_HTML, "write", result);
+    }
+
+    /// Drop a note and every list page of its owner
+    ///
+    /// Call after any change to the note, including its tags.
+    pub async fn invalidate_note(&self, user_id: Uuid, note_id: Uuid) {
+        self.invalidate_notes(user_id, &[note_id]).await;
+    }
+
+    /// Drop several notes of one user and all of their list pages
+    pub async fn invalidate_notes(&self, user_id: Uuid, note_ids: &[Uuid]) {
+        let keys = note_ids
+            .iter()
+            .map(|id| note_generation_key(user_id, *id))
+            .chain([list_generation_key(user_id)]);
+        self.advance(NOTE, keys).await;
+    }
+
+    /// Drop every list page of a user, e.g. after a note was created
+    pub async fn invalidate_lists(&self, user_id: Uuid) {
+        self.advance(NOTE_LIST, [list_generation_key(user_id)]).await;
+    }
+
+    /// Current generation behind `key`; on errors the lookup that follows
+    /// will most likely fail too and count as a miss
+    async fn generation(&self, key: &str) -> String {
+        match self.redis.clone().get(key).await {
+            Ok(generation) => generation.unwrap_or_else(|| INITIAL_GENERATION.to_string()),
+            Err(_) => INITIAL_GENERATION.to_string(),
+        }
+    }
+
+    async fn lookup<T: DeserializeOwned>(&self, cache: &'static str, key: String) -> Lookup<T> {
+        match decode(cache, self.redis.clone().get(&key).await) {
+            Some(value) => Lookup::Hit(value),
+            None => Lookup::Miss(CacheSlot(key)),
+        }
+    }
+
+    async fn put<T: Serialize>(&self, cache: &'static str, slot: CacheSlot, value: &T) {
+        let Some(json) = encode(cache, value) else {
+            return;
+        };
+        let result = self
+            .redis
+            .clone()
+            .set_with_expiry(&slot.0, &json, self.ttl_secs)
+            .await;
+        log_failure(cache, "write", result);
+    }
+
+    /// Move each generation key to a fresh random generation, orphaning the
+    /// entries stored under the old one until they expire
+    ///
+    /// Generations are random rather than counted so that one expiring and
+    /// starting over can't bring back an entry from before. They outlive the
+    /// entries written under them, so once a generation key expires nothing
+    /// stored under [`INITIAL_GENERATION`] before it was set is left.
+    async fn advance(&self, cache: &'static str, keys: impl IntoIterator<Item = String>) {
+        let entries: Vec<(String, String)> = keys
+            .into_iter()
+            .map(|key| (key, Uuid::new_v4().simple().to_string()))
+            .collect();
+        let result = self
+            .redis
+            .clone()
+            .set_many_with_expiry(&entries, self.ttl_secs * 2)
+            .await;
+        log_failure(cache, "invalidate", result);
+    }
+}
+
+fn note_generation_key(user_id: Uuid, note_id: Uuid) -> String {
+    format!("cache:gen:note:{}:{}", user_id, note_id)
+}
+
+/// All list pages of a user share one generation so they can be dropped together
+fn list_generation_key(user_id: Uuid) -> String {
+    format!("cache:gen:notes:{}", user_id)
+}
+
+/// Versioned, so a renderer change doesn't serve HTML from the old one
+fn html_key(content_hash: &str) -> String {
+    format!("cache:html:v{}:{}", text::HTML_RENDER_VERSION, content_hash)
+}
+
+pub(super) fn decode<T: DeserializeOwned>(
+    cache: &'static str,
+    cached: Result<Option<String>>,
+) -> Option<T> {
+    let (result, value) = match cached {
+        Ok(None) => ("miss", None),
+        Ok(Some(json)) => match serde_json::from_str(&json) {
+            Ok(value) => ("hit", Some(value)),
+            Err(e) => {
+                tracing::warn!("Ignoring undecodable {} cache entry: {}", cache, e);
+                ("error", None)
+            }
+        },
+        Err(e) => {
+            tracing::warn!("{} cache read failed: {}", cache, e);
+            ("error", None)
+        }
+    };
+
+    metrics::counter!(CACHE_REQUESTS_TOTAL, "cache" => cache, "result" => result).increment(1);
+    value
+}
+
+pub(super) fn encode<T: Serialize>(cache: &'static str, value: &T) -> Option<String> {
+    serde_json::to_string(value)
+        .map_err(|e| tracing::warn!("Failed to encode {} cache entry: {}", cache, e))
+        .ok()
+}
+
+pub(super) fn log_failure(cache: &'static str, action: &str, result: Result<()>) {
+    if let Err(e) = result {
+        tracing::warn!("{} cache {} failed: {}", cache, action, e);
+    }
+}
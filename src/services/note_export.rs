@@ -0,0 +1,248 @@
+//! Notes as downloadable files, alone or streamed as a ZIP archive
+
+use axum::body::Bytes;
+use futures::Stream;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use tokio::sync::mpsc;
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+use crate::models::note::{ExportFormat, NoteResponse};
+use crate::utils::text;
+
+/// Longest file name stem taken from a note's title, in characters
+const MAX_STEM_CHARS: usize = 60;
+
+/// Archive chunks, about one note each, buffered ahead of the client
+const ZIP_CHUNKS_AHEAD: usize = 4;
+
+/// One exported note
+pub struct ExportFile {
+    pub name: String,
+    pub content_type: &'static str,
+    pub content: String,
+}
+
+impl ExportFile {
+    pub fn new(note: &NoteResponse, format: ExportFormat) -> Self {
+        let content = match format {
+            ExportFormat::Markdown => markdown(note),
+            ExportFormat::Html => html_page(note),
+            ExportFormat::Text => plain_text(note),
+            ExportFormat::Json => serde_json::to_string_pretty(note).unwrap_or_default(),
+        };
+        Self {
+            name: format!("{}.{}", file_stem(note), format.extension()),
+            content_type: format.content_type(),
+            content,
+        }
+    }
+}
+
+/// Stream `notes` as a ZIP archive with one file per note
+///
+/// The archive is written on a blocking thread and handed over a note at a
+/// time, so it is never held in memory whole. A dropped stream stops the
+/// writer at its next note.
+pub fn zip_stream(
+    notes: Vec<NoteResponse>,
+    format: ExportFormat,
+) -> impl Stream<Item = io::Result<Bytes>> + Send + 'static {
+    let (tx, mut rx) = mpsc::channel(ZIP_CHUNKS_AHEAD);
+
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) = send_zip(&tx, &notes, format) {
+            tracing::warn!("Export archive of {} note(s) failed: {}", notes.len(), e);
+            // Fails the response body, so the client sees a broken download
+            // rather than a truncated archive
+            let _ = tx.blocking_send(Err(e));
+        }
+    });
+
+    futures::stream::poll_fn(move |cx| rx.poll_recv(cx))
+}
+
+/// Write `notes` as a ZIP archive down `tx`, about a note per chunk
+///
+/// Blocks while the channel is full, so it must run off the async runtime.
+/// Fails with `BrokenPipe` once the receiver is dropped.
+pub fn send_zip(
+    tx: &mpsc::Sender<io::Result<Bytes>>,
+    notes: &[NoteResponse],
+    format: ExportFormat,
+) -> io::Result<()> {
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    let mut zip = ZipWriter::new(ChunkSink::new(tx.clone()));
+    zip.set_flush_on_finish_file(true);
+
+    for note in notes {
+        let file = ExportFile::new(note, format);
+        zip.start_file(file.name, options)?;
+        zip.write_all(file.content.as_bytes())?;
+    }
+
+    zip.finish()?.flush()
+}
+
+/// `title-of-the-note-1a2b3c4d`: readable, safe on any file system, and
+/// unique thanks to the start of the note's ID
+fn file_stem(note: &NoteResponse) -> String {
+    let mut stem = String::new();
+    for c in note.title.chars().take(MAX_STEM_CHARS) {
+        if c.is_alphanumeric() {
+            stem.extend(c.to_lowercase());
+        } else if !stem.is_empty() && !stem.ends_with('-') {
+            stem.push('-');
+        }
+    }
+    let stem = stem.trim_end_matches('-');
+    let id = note.id.simple().to_string();
+
+    if stem.is_empty() {
+        format!("note-{}", &id[..8])
+    } else {
+        format!("{}-{}", stem, &id[..8])
+    }
+}
+
+fn markdown(note: &NoteResponse) -> String {
+    // JSON strings are valid YAML double-quoted scalars, escapes included
+    let quote = |s: &str| serde_json::to_string(s).unwrap_or_default();
+    let tags: Vec<String> = note.tags.iter().map(|tag| quote(tag)).collect();
+
+    format!(
+        "---\ntitle: {}\ntags: [{}]\ncreated: {}\nupdated: {}\n---\n\n{}\n",
+        quote(&note.title),
+        tags.join(", "),
+        note.created_at.to_rfc3339(),
+        note.updated_at.to_rfc3339(),
+        note.content.trim_end()
+    )
+}
+
+/// A page that opens on its own, with no scripts or outside resources
+fn html_page(note: &NoteResponse) -> String {
+    let title = escape_html(&note.title);
+
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<meta name="created" content="{created}">
+<meta name="modified" content="{updated}">
+<title>{title}</title>
+<style>
+body {{ max-width: 46rem; margin: 2rem auto; padding: 0 1rem; font-family: system-ui, sans-serif; line-height: 1.6; }}
+pre {{ overflow-x: auto; padding: 0.75rem; background: #f5f5f5; }}
+table {{ border-collapse: collapse; }}
+th, td {{ border: 1px solid #ccc; padding: 0.25rem 0.5rem; }}
+blockquote {{ margin-left: 0; padding-left: 1rem; border-left: 4px solid #ddd; }}
+</style>
+</head>
+<body>
+<article>
+<h1>{title}</h1>
+{body}</article>
+</body>
+</html>
+"#,
+        created = note.created_at.to_rfc3339(),
+        updated = note.updated_at.to_rfc3339(),
+        title = title,
+        body = text::render_html(&note.content),
+    )
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn plain_text(note: &NoteResponse) -> String {
+    let mut out = format!("{}\n\n", note.title);
+    if !note.tags.is_empty() {
+        out.push_str(&format!("Tags: {}\n\n", note.tags.join(", ")));
+    }
+    out.push_str(&text::readable_text(&note.content));
+    out
+}
+
+/// Sends everything written to it down a channel at each flush
+///
+/// `ZipWriter` seeks back to fill in each entry's header once the entry is
+/// written; with `flush_on_finish_file` it only does so within the entry
+/// still being written, so whatever was flushed before can be sent on.
+struct ChunkSink {
+    tx: mpsc::Sender<io::Result<Bytes>>,
+    /// Bytes already sent
+    sent: u64,
+    buf: Vec<u8>,
+    /// Write position within `buf`
+    pos: usize,
+}
+
+impl ChunkSink {
+    fn new(tx: mpsc::Sender<io::Result<Bytes>>) -> Self {
+        Self {
+            tx,
+            sent: 0,
+            buf: Vec::new(),
+            pos: 0,
+        }
+    }
+}
+
+impl Write for ChunkSink {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let end = self.pos + data.len();
+        if end > self.buf.len() {
+            self.buf.resize(end, 0);
+        }
+        self.buf[self.pos..end].copy_from_slice(data);
+        self.pos = end;
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        let chunk = Bytes::from(std::mem::take(&mut self.buf));
+        self.sent += chunk.len() as u64;
+        self.pos = 0;
+        self.tx
+            .blocking_send(Ok(chunk))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "export download was dropped"))
+    }
+}
+
+/// `ZipWriter` only reads back when copying entries, which exports never do
+impl Read for ChunkSink {
+    fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "export archives are write-only",
+        ))
+    }
+}
+
+impl Seek for ChunkSink {
+    fn seek(&mut self, to: SeekFrom) -> io::Result<u64> {
+        let target = match to {
+            SeekFrom::Start(offset) => offset as i128,
+            SeekFrom::End(delta) => (self.sent + self.buf.len() as u64) as i128 + delta as i128,
+            SeekFrom::Current(delta) => (self.sent + self.pos as u64) as i128 + delta as i128,
+        };
+        if target < self.sent as i128 {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "cannot seek into data already sent",
+            ));
+        }
+        self.pos = (target - self.sent as i128) as usize;
+        Ok(target as u64)
+    }
+}
@@ -1,19 +1,50 @@
-use sqlx::PgPool;
+use axum::body::Bytes;
+use futures::Stream;
+use sha2::{Digest, Sha256};
+use sqlx::{FromRow, PgConnection, PgPool, Postgres, QueryBuilder};
+use std::collections::{HashMap, HashSet};
+use std::io;
+use tracing::Instrument;
 use uuid::Uuid;
 use crate::models::note::*;
-use crate::utils::{errors::{AppError, Result}, validation};
+use crate::models::notebook::*;
+use crate::utils::{errors::{AppError, ErrorCode, Result}, text, validation};
 use crate::config::Config;
+use crate::db::db_span;
+use crate::metrics::{NOTES_CREATED_TOTAL, NOTES_DELETED_TOTAL};
+use crate::services::{note_cache::Lookup, note_export, ExportFile, NoteCache};
+
+/// Longest plain-text excerpt in list summaries, in characters
+const EXCERPT_CHARS: usize = 200;
+
+/// A listed note along with the key it was sorted by
+#[derive(FromRow)]
+struct ListedNote {
+    #[sqlx(flatten)]
+    note: Note,
+    /// `lower(title)` as Postgres computed it, so title cursors compare
+    /// exactly the way the database orders
+    title_key: Option<String>,
+}
 
 pub struct NoteService {
     pool: PgPool,
     config: Config,
+    cache: Option<NoteCache>,
 }
 
 impl NoteService {
     pub fn new(pool: PgPool, config: Config) -> Self {
-        Self { pool, config }
+        Self { pool, config, cache: None }
+    }
+
+    /// Serve note reads through `cache`, invalidating it on every write
+    pub fn with_cache(mut self, cache: NoteCache) -> Self {
+        self.cache = Some(cache);
+        self
     }
 
+    #[tracing::instrument(skip(self, req))]
     pub async fn create(&self, user_id: Uuid, req: CreateNoteRequest) -> Result<NoteResponse> {
         // Check note limit
         let count = sqlx::query!(
@@ -21,48 +52,78 @@ impl NoteService {
             user_id
         )
         .fetch_one(&self.pool)
+        .instrument(db_span!("SELECT", "notes"))
         .await?;
 
         if count.count.unwrap_or(0) >= self.config.max_notes_per_user {
-            return Err(AppError::Forbidden("Note limit reached".to_string()));
+            return Err(AppError::problem(ErrorCode::NoteLimitReached, "Note limit reached"));
         }
         
         let title = validation::sanitize_string(&req.title);
-        validation::validate_note_title(&title)?;
         
         let content = req.content.unwrap_or_default();
         validation::validate_note_content(&content, self.config.max_note_size)?;
+
+        if let Some(notebook_id) = req.notebook_id {
+            owned_notebook(&mut *self.pool.acquire().await?, notebook_id, user_id).await?;
+        }
         
+        // New notes go after the notebook's existing ones
         let note = sqlx::query_as!(
             Note,
-            r#"INSERT INTO notes (user_id, title, content, last_edited_by)
-               VALUES ($1, $2, $3, $1)
-               RETURNING id, user_id, title, content, last_edited_by, is_deleted, created_at, updated_at"#,
-            user_id, title, content
+            r#"INSERT INTO notes (user_id, title, content, last_edited_by, notebook_id, position)
+               VALUES ($1, $2, $3, $1, $4, (
+                   SELECT COALESCE(MAX(position) + 1, 0) FROM notes
+                   WHERE user_id = $1 AND notebook_id IS NOT DISTINCT FROM $4 AND is_deleted = false
+               ))
+               RETURNING id, user_id, title, content, last_edited_by, is_deleted, created_at, updated_at,
+                         notebook_id, position, is_pinned, is_archived, is_favorite"#,
+            user_id, title, content, req.notebook_id
         )
         .fetch_one(&self.pool)
+        .instrument(db_span!("INSERT", "notes"))
         .await?;
         
+        metrics::counter!(NOTES_CREATED_TOTAL).increment(1);
+        if let Some(cache) = &self.cache {
+            cache.invalidate_lists(user_id).await;
+        }
+        
         Ok(NoteResponse {
             id: note.id,
             title: note.title,
             content: note.content,
             last_edited_by: note.last_edited_by,
+            notebook_id: note.notebook_id,
+            pinned: note.is_pinned,
+            archived: note.is_archived,
+            favorite: note.is_favorite,
             created_at: note.created_at,
             updated_at: note.updated_at,
             tags: vec![],
+            html: None,
         })
     }
     
+    #[tracing::instrument(skip(self))]
     pub async fn get(&self, note_id: Uuid, user_id: Uuid) -> Result<NoteResponse> {
+        let mut slot = None;
+        if let Some(cache) = &self.cache {
+            match cache.get_note(user_id, note_id).await {
+                Lookup::Hit(note) => return Ok(note),
+                Lookup::Miss(miss) => slot = Some(miss),
+            }
+        }
+
         let note = sqlx::query_as!(
             Note,
             "SELECT * FROM notes WHERE id = $1 AND is_deleted = false",
             note_id
         )
         .fetch_optional(&self.pool)
+        .instrument(db_span!("SELECT", "notes"))
         .await?
-        .ok_or_else(|| AppError::NotFound("Note not found".to_string()))?;
+        .ok_or_else(|| AppError::problem(ErrorCode::NoteNotFound, "Note not found"))?;
         
         if note.user_id != user_id {
             return Err(AppError::Forbidden("Not authorized to access this note".to_string()));
@@ -76,66 +137,255 @@ impl NoteService {
             note_id
         )
         .fetch_all(&self.pool)
+        .instrument(db_span!("SELECT", "tags"))
         .await?
         .into_iter()
         .map(|r| r.name)
         .collect();
         
-        Ok(NoteResponse {
+        let response = NoteResponse {
             id: note.id,
             title: note.title,
             content: note.content,
             last_edited_by: note.last_edited_by,
+            notebook_id: note.notebook_id,
+            pinned: note.is_pinned,
+            archived: note.is_archived,
+            favorite: note.is_favorite,
             created_at: note.created_at,
             updated_at: note.updated_at,
             tags,
-        })
+            html: None,
+        };
+
+        // Only cached after the ownership check, under the owner's key
+        if let (Some(cache), Some(slot)) = (&self.cache, slot) {
+            cache.put_note(slot, &response).await;
+        }
+
+        Ok(response)
     }
     
+    #[tracing::instrument(skip(self))]
     pub async fn list(&self, user_id: Uuid, params: NoteQueryParams) -> Result<NoteListResponse> {
+        if params.cursor.is_some() && params.page.is_some() {
+            return Err(AppError::invalid_field(
+                "cursor",
+                "conflict",
+                "Use either cursor or page, not both",
+            ));
+        }
+        if params.view.is_some() && params.fields.is_some() {
+            return Err(AppError::invalid_field(
+                "fields",
+                "conflict",
+                "Use either view or fields, not both",
+            ));
+        }
+
+        let fields = match params.fields.as_deref() {
+            Some(list) => parse_fields(list)?,
+            None => params.view.unwrap_or_default().fields().to_vec(),
+        };
+        let sort = params.sort.unwrap_or_default();
+        let order = params.order.unwrap_or(sort.default_order());
+
+        let cursor = params
+            .cursor
+            .as_deref()
+            .map(|c| {
+                NoteCursor::decode(c).ok_or_else(|| {
+                    AppError::invalid_field("cursor", "invalid", "Malformed cursor")
+                })
+            })
+            .transpose()?;
+        if cursor.as_ref().is_some_and(|c| c.sort != sort || c.order != order) {
+            return Err(AppError::invalid_field(
+                "cursor",
+                "mismatch",
+                "Cursor was issued for a different sort order",
+            ));
+        }
+
+        let limit = params.limit.unwrap_or(20).clamp(1, 100);
+        // Offset paging only for clients that still ask for a page number
         let page = params.page.unwrap_or(1).max(1);
-        let limit = params.limit.unwrap_or(20).min(100);
         let offset = (page - 1) * limit;
+        // The count is an extra query, so only run it when asked; `page` implies
+        // it for clients written before cursors, unless they opt out
+        let include_total = params.include_total.unwrap_or(params.page.is_some());
+
+        let page_key = format!(
+            "cursor={}&page={}&limit={}&total={}&sort={:?}&order={:?}&fields={}&notebook={}\
+             &pinned={:?}&favorite={:?}&archived={:?}&q={:?}&tag={:?}",
+            params.cursor.as_deref().unwrap_or(""),
+            page,
+            limit,
+            include_total,
+            sort,
+            order,
+            fields.iter().map(NoteField::as_str).collect::<Vec<_>>().join(","),
+            params.notebook_id.map(|n| n.to_string()).unwrap_or_default(),
+            params.pinned,
+            params.favorite,
+            params.archived,
+            params.q,
+            params.tag
+        );
+        let mut slot = None;
+        if let Some(cache) = &self.cache {
+            match cache.get_list(user_id, &page_key).await {
+                Lookup::Hit(list) => return Ok(list),
+                Lookup::Miss(miss) => slot = Some(miss),
+            }
+        }
+
+        // Someone else's notebook would just list empty; say so instead
+        if let Some(NotebookFilter::Notebook(notebook_id)) = params.notebook_id {
+            owned_notebook(&mut *self.pool.acquire().await?, notebook_id, user_id).await?;
+        }
         
-        let notes = sqlx::query_as!(
-            Note,
-            r#"SELECT * FROM notes 
-               WHERE user_id = $1 AND is_deleted = false
-               ORDER BY updated_at DESC
-               LIMIT $2 OFFSET $3"#,
-            user_id, limit, offset
-        )
-        .fetch_all(&self.pool)
-        .await?;
+        let needs_content = fields
+            .iter()
+            .any(|f| matches!(f, NoteField::Content | NoteField::Excerpt | NoteField::WordCount));
+        let column = match sort {
+            NoteSort::UpdatedAt => "updated_at",
+            NoteSort::CreatedAt => "created_at",
+            NoteSort::Title => "lower(title)",
+            NoteSort::Position => "position",
+        };
+        let (direction, after) = match order {
+            SortOrder::Asc => ("ASC", ">"),
+            SortOrder::Desc => ("DESC", "<"),
+        };
+
+        // Sort column and direction come from the enums above, never from input
+        let mut query = QueryBuilder::<Postgres>::new("SELECT id, user_id, title, ");
+        query.push(if needs_content { "content" } else { "'' AS content" });
+        query.push(
+            ", last_edited_by, is_deleted, created_at, updated_at, notebook_id, position, \
+             is_pinned, is_archived, is_favorite, ",
+        );
+        query.push(if sort == NoteSort::Title { "lower(title)" } else { "NULL::text" });
+        query.push(" AS title_key FROM notes WHERE user_id = ");
+        query.push_bind(user_id);
+        query.push(" AND is_deleted = false");
+        push_filters(&mut query, &params);
+        if let Some(cursor) = cursor {
+            // Past the cursor's pinned notes come the unpinned ones
+            query.push(if cursor.pinned {
+                " AND (NOT is_pinned OR "
+            } else {
+                " AND NOT is_pinned AND ("
+            });
+            query.push(format!("({}, id) {} (", column, after));
+            match (sort, cursor.key) {
+                (NoteSort::Title, CursorKey::Text(key)) => query.push_bind(key),
+                (NoteSort::UpdatedAt | NoteSort::CreatedAt, CursorKey::Time(key)) => {
+                    query.push_bind(key)
+                }
+                (NoteSort::Position, CursorKey::Number(key)) => query.push_bind(key),
+                _ => {
+                    return Err(AppError::invalid_field("cursor", "invalid", "Malformed cursor"))
+                }
+            };
+            query.push(", ");
+            query.push_bind(cursor.id);
+            query.push("))");
+        }
+        query.push(format!(
+            " ORDER BY is_pinned DESC, {0} {1}, id {1} LIMIT ",
+            column, direction
+        ));
+        // One extra row tells whether another page follows
+        query.push_bind(limit + 1);
+        query.push(" OFFSET ");
+        query.push_bind(offset);
+
+        let mut rows: Vec<ListedNote> = query
+            .build_query_as()
+            .fetch_all(&self.pool)
+            .instrument(db_span!("SELECT", "notes"))
+            .await?;
         
-        let total = sqlx::query!(
-            "SELECT COUNT(*) as count FROM notes WHERE user_id = $1 AND is_deleted = false",
-            user_id
-        )
-        .fetch_one(&self.pool)
-        .await?
-        .count
-        .unwrap_or(0);
+        let next_cursor = if rows.len() as i64 > limit {
+            rows.truncate(limit as usize);
+            rows.last().map(|last| {
+                let key = match sort {
+                    NoteSort::UpdatedAt => CursorKey::Time(last.note.updated_at),
+                    NoteSort::CreatedAt => CursorKey::Time(last.note.created_at),
+                    NoteSort::Title => CursorKey::Text(last.title_key.clone().unwrap_or_default()),
+                    NoteSort::Position => CursorKey::Number(last.note.position.into()),
+                };
+                NoteCursor {
+                    sort,
+                    order,
+                    pinned: last.note.is_pinned,
+                    key,
+                    id: last.note.id,
+                }
+                .encode()
+            })
+        } else {
+            None
+        };
         
-        let mut responses = vec![];
-        for note in notes {
-            responses.push(NoteResponse {
-                id: note.id,
-                title: note.title,
-                content: note.content,
-                last_edited_by: note.last_edited_by,
-                created_at: note.created_at,
-                updated_at: note.updated_at,
-                tags: vec![],
-            });
+        let total = if include_total {
+            let mut count = QueryBuilder::<Postgres>::new("SELECT COUNT(*) FROM notes WHERE user_id = ");
+            count.push_bind(user_id);
+            count.push(" AND is_deleted = false");
+            push_filters(&mut count, &params);
+            Some(
+                count
+                    .build_query_scalar::<i64>()
+                    .fetch_one(&self.pool)
+                    .instrument(db_span!("SELECT", "notes"))
+                    .await?,
+            )
+        } else {
+            None
+        };
+
+        let mut tags: HashMap<Uuid, Vec<String>> = HashMap::new();
+        if fields.contains(&NoteField::Tags) {
+            let ids: Vec<Uuid> = rows.iter().map(|row| row.note.id).collect();
+            let tag_rows = sqlx::query!(
+                r#"SELECT nt.note_id, t.name FROM note_tags nt
+                   INNER JOIN tags t ON t.id = nt.tag_id
+                   WHERE nt.note_id = ANY($1)
+                   ORDER BY t.name"#,
+                &ids
+            )
+            .fetch_all(&self.pool)
+            .instrument(db_span!("SELECT", "note_tags"))
+            .await?;
+            for row in tag_rows {
+                tags.entry(row.note_id).or_default().push(row.name);
+            }
         }
         
-        Ok(NoteListResponse {
-            notes: responses,
+        let notes = rows
+            .into_iter()
+            .map(|row| {
+                let note_tags = tags.remove(&row.note.id);
+                list_item(row.note, &fields, note_tags)
+            })
+            .collect();
+        
+        let list = NoteListResponse {
+            notes,
             total,
-        })
+            next_cursor,
+        };
+
+        if let (Some(cache), Some(slot)) = (&self.cache, slot) {
+            cache.put_list(slot, &list).await;
+        }
+
+        Ok(list)
     }
     
+    #[tracing::instrument(skip(self, req))]
     pub async fn update(&self, note_id: Uuid, user_id: Uuid, req: UpdateNoteRequest) -> Result<NoteResponse> {
         let note = sqlx::query_as!(
             Note,
@@ -143,8 +393,9 @@ impl NoteService {
             note_id
         )
         .fetch_optional(&self.pool)
+        .instrument(db_span!("SELECT", "notes"))
         .await?
-        .ok_or_else(|| AppError::NotFound("Note not found".to_string()))?;
+        .ok_or_else(|| AppError::problem(ErrorCode::NoteNotFound, "Note not found"))?;
         
         if note.user_id != user_id {
             return Err(AppError::Forbidden("Not authorized".to_string()));
@@ -153,7 +404,6 @@ impl NoteService {
         let title = req.title.unwrap_or(note.title);
         let content = req.content.unwrap_or(note.content);
         
-        validation::validate_note_title(&title)?;
         validation::validate_note_content(&content, self.config.max_note_size)?;
         
         sqlx::query!(
@@ -163,19 +413,26 @@ impl NoteService {
             title, content, user_id, note_id
         )
         .execute(&self.pool)
+        .instrument(db_span!("UPDATE", "notes"))
         .await?;
         
+        if let Some(cache) = &self.cache {
+            cache.invalidate_note(user_id, note_id).await;
+        }
+        
         self.get(note_id, user_id).await
     }
     
+    #[tracing::instrument(skip(self))]
     pub async fn delete(&self, note_id: Uuid, user_id: Uuid) -> Result<()> {
         let note = sqlx::query!(
             "SELECT user_id FROM notes WHERE id = $1",
             note_id
         )
         .fetch_optional(&self.pool)
+        .instrument(db_span!("SELECT", "notes"))
         .await?
-        .ok_or_else(|| AppError::NotFound("Note not found".to_string()))?;
+        .ok_or_else(|| AppError::problem(ErrorCode::NoteNotFound, "Note not found"))?;
         
         if note.user_id != user_id {
             return Err(AppError::Forbidden("Not authorized".to_string()));
@@ -183,8 +440,905 @@ impl NoteService {
         
         sqlx::query!("UPDATE notes SET is_deleted = true WHERE id = $1", note_id)
             .execute(&self.pool)
+            .instrument(db_span!("UPDATE", "notes"))
             .await?;
         
+        metrics::counter!(NOTES_DELETED_TOTAL).increment(1);
+        if let Some(cache) = &self.cache {
+            cache.invalidate_note(user_id, note_id).await;
+        }
+        
         Ok(())
     }
-}
\ No newline at end of file
+    
+    /// Apply one action to many of the caller's notes in a single transaction
+    ///
+    /// Notes that can't take part, e.g. someone else's, are reported and
+    /// skipped; the changes to the rest are committed together.
+    #[tracing::instrument(skip(self, req), fields(notes = req.note_ids.len()))]
+    pub async fn bulk(&self, user_id: Uuid, req: BulkNoteRequest) -> Result<BulkNoteResponse> {
+        let tags = match &req.action {
+            BulkNoteAction::Tag { tags } | BulkNoteAction::Untag { tags } => parse_tag_names(tags)?,
+            BulkNoteAction::Delete | BulkNoteAction::Restore => Vec::new(),
+        };
+
+        let mut ids: Vec<Uuid> = Vec::with_capacity(req.note_ids.len());
+        for id in req.note_ids {
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+
+        let mut tx = self.pool.begin().await?;
+
+        let notes: HashMap<Uuid, (Uuid, bool)> = sqlx::query!(
+            "SELECT id, user_id, is_deleted FROM notes WHERE id = ANY($1) FOR UPDATE",
+            &ids
+        )
+        .fetch_all(&mut *tx)
+        .instrument(db_span!("SELECT", "notes"))
+        .await?
+        .into_iter()
+        .map(|row| (row.id, (row.user_id, row.is_deleted)))
+        .collect();
+
+        // Tags can't be changed on trashed notes, as they can't be read either
+        let trash_allowed = matches!(req.action, BulkNoteAction::Delete | BulkNoteAction::Restore);
+        let mut failures: HashMap<Uuid, (ErrorCode, &'static str)> = HashMap::new();
+        for id in &ids {
+            let failure = match notes.get(id) {
+                None => Some((ErrorCode::NoteNotFound, "Note not found")),
+                Some((owner, _)) if *owner != user_id => {
+                    Some((ErrorCode::Forbidden, "Not authorized"))
+                }
+                Some((_, true)) if !trash_allowed => Some((ErrorCode::NoteNotFound, "Note not found")),
+                Some(_) => None,
+            };
+            if let Some(failure) = failure {
+                failures.insert(*id, failure);
+            }
+        }
+        let mut targets: Vec<Uuid> = ids.iter().filter(|id| !failures.contains_key(id)).copied().collect();
+
+        match &req.action {
+            BulkNoteAction::Delete => {
+                let deleted = sqlx::query!(
+                    "UPDATE notes SET is_deleted = true WHERE id = ANY($1) AND is_deleted = false",
+                    &targets
+                )
+                .execute(&mut *tx)
+                .instrument(db_span!("UPDATE", "notes"))
+                .await?
+                .rows_affected();
+
+                metrics::counter!(NOTES_DELETED_TOTAL).increment(deleted);
+            }
+            BulkNoteAction::Restore => {
+                // Restored notes take new positions alongside the live ones
+                lock_notebooks(&mut tx, user_id).await?;
+
+                let live = sqlx::query!(
+                    r#"SELECT COUNT(*) as "count!" FROM notes WHERE user_id = $1 AND is_deleted = false"#,
+                    user_id
+                )
+                .fetch_one(&mut *tx)
+                .instrument(db_span!("SELECT", "notes"))
+                .await?
+                .count;
+
+                // Restore in request order for as long as the note limit allows
+                let room = (self.config.max_notes_per_user - live).max(0) as usize;
+                let trashed: Vec<Uuid> = targets.iter().filter(|id| notes[id].1).copied().collect();
+                for id in trashed.iter().skip(room) {
+                    failures.insert(*id, (ErrorCode::NoteLimitReached, "Note limit reached"));
+                }
+                targets.retain(|id| !failures.contains_key(id));
+
+                // Back after each notebook's live notes, in request order; their
+                // old positions may have been taken while they were in the trash
+                let restoring: Vec<Uuid> = trashed.into_iter().take(room).collect();
+                sqlx::query!(
+                    r#"WITH restoring AS (
+                           SELECT n.id, n.notebook_id,
+                                  ROW_NUMBER() OVER (PARTITION BY n.notebook_id ORDER BY r.ord) AS rank
+                           FROM unnest($1::uuid[]) WITH ORDINALITY AS r(id, ord)
+                           JOIN notes n ON n.id = r.id
+                       )
+                       UPDATE notes n
+                       SET is_deleted = false,
+                           position = rs.rank - 1 + (
+                               SELECT COALESCE(MAX(live.position) + 1, 0) FROM notes live
+                               WHERE live.user_id = $2 AND live.is_deleted = false
+                                 AND live.notebook_id IS NOT DISTINCT FROM rs.notebook_id
+                           )
+                       FROM restoring rs
+                       WHERE n.id = rs.id"#,
+                    &restoring,
+                    user_id
+                )
+                .execute(&mut *tx)
+                .instrument(db_span!("UPDATE", "notes"))
+                .await?;
+            }
+            BulkNoteAction::Tag { .. } => {
+                sqlx::query!(
+                    r#"INSERT INTO tags (user_id, name)
+                       SELECT $1, unnest($2::text[])
+                       ON CONFLICT (user_id, name) DO NOTHING"#,
+                    user_id,
+                    &tags
+                )
+                .execute(&mut *tx)
+                .instrument(db_span!("INSERT", "tags"))
+                .await?;
+
+                sqlx::query!(
+                    r#"INSERT INTO note_tags (note_id, tag_id)
+                       SELECT n.id, t.id FROM unnest($1::uuid[]) AS n(id)
+                       CROSS JOIN tags t
+                       WHERE t.user_id = $2 AND t.name = ANY($3)
+                       ON CONFLICT DO NOTHING"#,
+                    &targets,
+                    user_id,
+                    &tags
+                )
+                .execute(&mut *tx)
+                .instrument(db_span!("INSERT", "note_tags"))
+                .await?;
+            }
+            BulkNoteAction::Untag { .. } => {
+                sqlx::query!(
+                    r#"DELETE FROM note_tags
+                       WHERE note_id = ANY($1)
+                         AND tag_id IN (SELECT id FROM tags WHERE user_id = $2 AND name = ANY($3))"#,
+                    &targets,
+                    user_id,
+                    &tags
+                )
+                .execute(&mut *tx)
+                .instrument(db_span!("DELETE", "note_tags"))
+                .await?;
+            }
+        }
+
+        tx.commit().await?;
+
+        if let Some(cache) = &self.cache {
+            cache.invalidate_notes(user_id, &targets).await;
+        }
+
+        let results: Vec<BulkNoteResult> = ids
+            .into_iter()
+            .map(|id| match failures.remove(&id) {
+                Some((code, detail)) => BulkNoteResult {
+                    id,
+                    ok: false,
+                    code: Some(code.as_str()),
+                    detail: Some(detail.to_string()),
+                },
+                None => BulkNoteResult {
+                    id,
+                    ok: true,
+                    code: None,
+                    detail: None,
+                },
+            })
+            .collect();
+        let succeeded = results.iter().filter(|r| r.ok).count();
+
+        Ok(BulkNoteResponse {
+            succeeded,
+            failed: results.len() - succeeded,
+            results,
+        })
+    }
+
+    /// File a note in another notebook, or reorder it within its own
+    #[tracing::instrument(skip(self, req))]
+    pub async fn move_note(&self, note_id: Uuid, user_id: Uuid, req: MoveNoteRequest) -> Result<NoteResponse> {
+        let mut tx = self.pool.begin().await?;
+        lock_notebooks(&mut tx, user_id).await?;
+
+        let note = sqlx::query!(
+            "SELECT user_id, notebook_id FROM notes WHERE id = $1 AND is_deleted = false",
+            note_id
+        )
+        .fetch_optional(&mut *tx)
+        .instrument(db_span!("SELECT", "notes"))
+        .await?
+        .ok_or_else(|| AppError::problem(ErrorCode::NoteNotFound, "Note not found"))?;
+
+        if note.user_id != user_id {
+            return Err(AppError::Forbidden("Not authorized".to_string()));
+        }
+        if let Some(notebook_id) = req.notebook_id {
+            owned_notebook(&mut tx, notebook_id, user_id).await?;
+        }
+
+        sqlx::query!(
+            "UPDATE notes SET notebook_id = $1 WHERE id = $2",
+            req.notebook_id, note_id
+        )
+        .execute(&mut *tx)
+        .instrument(db_span!("UPDATE", "notes"))
+        .await?;
+
+        let mut siblings = note_ids_in(&mut tx, user_id, req.notebook_id, note_id).await?;
+        insert_at(&mut siblings, note_id, req.position);
+        renumber(&mut tx, "notes", &siblings).await?;
+        if note.notebook_id != req.notebook_id {
+            let left_behind = note_ids_in(&mut tx, user_id, note.notebook_id, note_id).await?;
+            renumber(&mut tx, "notes", &left_behind).await?;
+        }
+        tx.commit().await?;
+
+        // Siblings shifted too, and the lists are invalidated along with the note
+        if let Some(cache) = &self.cache {
+            cache.invalidate_note(user_id, note_id).await;
+        }
+
+        self.get(note_id, user_id).await
+    }
+
+    /// Render Markdown to sanitized HTML, reusing earlier renders of the same content
+    #[tracing::instrument(skip(self, content), fields(bytes = content.len()))]
+    pub async fn render_html(&self, content: &str) -> String {
+        let hash = format!("{:x}", Sha256::digest(content.as_bytes()));
+
+        if let Some(cache) = &self.cache {
+            if let Some(html) = cache.get_html(&hash).await {
+                return html;
+            }
+        }
+
+        let html = text::render_html(content);
+
+        if let Some(cache) = &self.cache {
+            cache.put_html(&hash, &html).await;
+        }
+        html
+    }
+
+    /// A note as a file to download
+    pub async fn export_note(
+        &self,
+        note_id: Uuid,
+        user_id: Uuid,
+        format: ExportFormat,
+    ) -> Result<ExportFile> {
+        let note = self.get(note_id, user_id).await?;
+        Ok(ExportFile::new(&note, format))
+    }
+
+    /// Notes picked by ID or tag, as a ZIP archive streamed while it's written
+    ///
+    /// Trashed notes are never exported; archived ones are.
+    #[tracing::instrument(skip(self, req))]
+    pub async fn export_notes(
+        &self,
+        user_id: Uuid,
+        req: ExportNotesRequest,
+    ) -> Result<impl Stream<Item = io::Result<Bytes>> + Send + 'static> {
+        let tag = req.tag.as_deref().map(str::trim);
+        let notes = match (&req.note_ids, tag) {
+            (Some(ids), None) => {
+                let mut ids = ids.clone();
+                ids.sort();
+                ids.dedup();
+
+                let notes = sqlx::query_as!(
+                    Note,
+                    r#"SELECT * FROM notes
+                       WHERE user_id = $1 AND id = ANY($2) AND is_deleted = false
+                       ORDER BY created_at, id"#,
+                    user_id,
+                    &ids
+                )
+                .fetch_all(&self.pool)
+                .instrument(db_span!("SELECT", "notes"))
+                .await?;
+
+                // Missing, trashed and other users' notes are not told apart
+                let missing = ids.len() - notes.len();
+                if missing > 0 {
+                    return Err(AppError::problem(
+                        ErrorCode::NoteNotFound,
+                        format!("{} of the requested notes were not found", missing),
+                    ));
+                }
+                notes
+            }
+            (None, Some(tag)) if !tag.is_empty() => {
+                let notes = sqlx::query_as!(
+                    Note,
+                    r#"SELECT n.* FROM notes n
+                       WHERE n.user_id = $1 AND n.is_deleted = false
+                         AND EXISTS (
+                             SELECT 1 FROM note_tags nt
+                             INNER JOIN tags t ON t.id = nt.tag_id
+                             WHERE nt.note_id = n.id AND t.user_id = $1 AND t.name = $2
+                         )
+                       ORDER BY n.created_at, n.id"#,
+                    user_id,
+                    tag
+                )
+                .fetch_all(&self.pool)
+                .instrument(db_span!("SELECT", "notes"))
+                .await?;
+
+                if notes.is_empty() {
+                    return Err(AppError::problem(
+                        ErrorCode::NotFound,
+                        format!("No notes are tagged '{}'", tag),
+                    ));
+                }
+                notes
+            }
+            _ => {
+                return Err(AppError::invalid_field(
+                    "note_ids",
+                    "required",
+                    "Give either note_ids or tag",
+                ))
+            }
+        };
+
+        let ids: Vec<Uuid> = notes.iter().map(|note| note.id).collect();
+        let mut tags: HashMap<Uuid, Vec<String>> = HashMap::new();
+        let tag_rows = sqlx::query!(
+            r#"SELECT nt.note_id, t.name FROM note_tags nt
+               INNER JOIN tags t ON t.id = nt.tag_id
+               WHERE nt.note_id = ANY($1)
+               ORDER BY t.name"#,
+            &ids
+        )
+        .fetch_all(&self.pool)
+        .instrument(db_span!("SELECT", "note_tags"))
+        .await?;
+        for row in tag_rows {
+            tags.entry(row.note_id).or_default().push(row.name);
+        }
+
+        let notes = notes
+            .into_iter()
+            .map(|note| NoteResponse {
+                tags: tags.remove(&note.id).unwrap_or_default(),
+                id: note.id,
+                title: note.title,
+                content: note.content,
+                last_edited_by: note.last_edited_by,
+                notebook_id: note.notebook_id,
+                pinned: note.is_pinned,
+                archived: note.is_archived,
+                favorite: note.is_favorite,
+                created_at: note.created_at,
+                updated_at: note.updated_at,
+                html: None,
+            })
+            .collect();
+
+        Ok(note_export::zip_stream(notes, req.format))
+    }
+
+    /// Render unsaved Markdown the way a saved note would be
+    pub async fn preview(&self, req: PreviewRequest) -> Result<PreviewResponse> {
+        validation::validate_note_content(&req.content, self.config.max_note_size)?;
+        Ok(PreviewResponse {
+            html: self.render_html(&req.content).await,
+        })
+    }
+
+    /// Pin, archive or favorite a note, or undo any of those
+    #[tracing::instrument(skip(self))]
+    pub async fn set_state(&self, note_id: Uuid, user_id: Uuid, req: NoteStateRequest) -> Result<NoteResponse> {
+        let updated = self.bulk_set_state(user_id, BulkNoteStateRequest {
+            note_ids: vec![note_id],
+            state: req,
+        })
+        .await?;
+
+        if updated.updated.is_empty() {
+            // Tell a missing note apart from someone else's, as the other endpoints do
+            let owner = sqlx::query!(
+                "SELECT user_id FROM notes WHERE id = $1 AND is_deleted = false",
+                note_id
+            )
+            .fetch_optional(&self.pool)
+            .instrument(db_span!("SELECT", "notes"))
+            .await?;
+            return Err(match owner {
+                Some(_) => AppError::Forbidden("Not authorized".to_string()),
+                None => AppError::problem(ErrorCode::NoteNotFound, "Note not found"),
+            });
+        }
+
+        self.get(note_id, user_id).await
+    }
+
+    /// Apply the same state change to many of the caller's notes at once
+    #[tracing::instrument(skip(self, req), fields(notes = req.note_ids.len()))]
+    pub async fn bulk_set_state(&self, user_id: Uuid, req: BulkNoteStateRequest) -> Result<BulkNoteStateResponse> {
+        if req.state.is_empty() {
+            return Err(AppError::problem(
+                ErrorCode::ValidationFailed,
+                "Set at least one of pinned, archived or favorite",
+            ));
+        }
+
+        let updated = sqlx::query_scalar!(
+            r#"UPDATE notes
+               SET is_pinned = COALESCE($1, is_pinned),
+                   is_archived = COALESCE($2, is_archived),
+                   is_favorite = COALESCE($3, is_favorite)
+               WHERE id = ANY($4) AND user_id = $5 AND is_deleted = false
+               RETURNING id"#,
+            req.state.pinned,
+            req.state.archived,
+            req.state.favorite,
+            &req.note_ids,
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .instrument(db_span!("UPDATE", "notes"))
+        .await?;
+
+        if let Some(cache) = &self.cache {
+            cache.invalidate_notes(user_id, &updated).await;
+        }
+
+        // Report each missing ID once, in the order it was first asked for
+        let mut seen: HashSet<Uuid> = updated.iter().copied().collect();
+        let mut not_found = req.note_ids;
+        not_found.retain(|id| seen.insert(*id));
+
+        Ok(BulkNoteStateResponse { updated, not_found })
+    }
+
+    /// The caller's notebooks depth-first, each followed by its children in order
+    #[tracing::instrument(skip(self))]
+    pub async fn list_notebooks(&self, user_id: Uuid) -> Result<NotebookListResponse> {
+        let notebooks = sqlx::query!(
+            r#"WITH RECURSIVE tree AS (
+                   SELECT id, ARRAY[lpad(position::text, 10, '0') || id::text] AS path
+                   FROM notebooks
+                   WHERE user_id = $1 AND parent_id IS NULL
+                   UNION ALL
+                   SELECT nb.id, tree.path || (lpad(nb.position::text, 10, '0') || nb.id::text)
+                   FROM notebooks nb
+                   INNER JOIN tree ON nb.parent_id = tree.id
+               )
+               SELECT nb.id, nb.parent_id, nb.name, nb.position, nb.created_at, nb.updated_at,
+                      (SELECT COUNT(*) FROM notes n
+                       WHERE n.notebook_id = nb.id AND n.is_deleted = false) AS "note_count!"
+               FROM tree
+               INNER JOIN notebooks nb ON nb.id = tree.id
+               ORDER BY tree.path"#,
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .instrument(db_span!("SELECT", "notebooks"))
+        .await?
+        .into_iter()
+        .map(|row| NotebookResponse {
+            id: row.id,
+            parent_id: row.parent_id,
+            name: row.name,
+            position: row.position,
+            note_count: row.note_count,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        })
+        .collect();
+
+        Ok(NotebookListResponse { notebooks })
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn get_notebook(&self, notebook_id: Uuid, user_id: Uuid) -> Result<NotebookResponse> {
+        let mut conn = self.pool.acquire().await?;
+        let notebook = owned_notebook(&mut conn, notebook_id, user_id).await?;
+
+        let note_count = sqlx::query!(
+            r#"SELECT COUNT(*) as "count!" FROM notes WHERE notebook_id = $1 AND is_deleted = false"#,
+            notebook_id
+        )
+        .fetch_one(&mut *conn)
+        .instrument(db_span!("SELECT", "notes"))
+        .await?
+        .count;
+
+        Ok(NotebookResponse {
+            id: notebook.id,
+            parent_id: notebook.parent_id,
+            name: notebook.name,
+            position: notebook.position,
+            note_count,
+            created_at: notebook.created_at,
+            updated_at: notebook.updated_at,
+        })
+    }
+
+    /// Create a notebook after its future siblings
+    #[tracing::instrument(skip(self, req))]
+    pub async fn create_notebook(&self, user_id: Uuid, req: CreateNotebookRequest) -> Result<NotebookResponse> {
+        let name = validation::sanitize_string(&req.name);
+
+        let mut tx = self.pool.begin().await?;
+        lock_notebooks(&mut tx, user_id).await?;
+        if let Some(parent_id) = req.parent_id {
+            owned_notebook(&mut tx, parent_id, user_id).await?;
+        }
+
+        let notebook = sqlx::query_as!(
+            Notebook,
+            r#"INSERT INTO notebooks (user_id, parent_id, name, position)
+               VALUES ($1, $2, $3, (
+                   SELECT COALESCE(MAX(position) + 1, 0) FROM notebooks
+                   WHERE user_id = $1 AND parent_id IS NOT DISTINCT FROM $2
+               ))
+               RETURNING *"#,
+            user_id, req.parent_id, name
+        )
+        .fetch_one(&mut *tx)
+        .instrument(db_span!("INSERT", "notebooks"))
+        .await?;
+        tx.commit().await?;
+
+        Ok(NotebookResponse {
+            id: notebook.id,
+            parent_id: notebook.parent_id,
+            name: notebook.name,
+            position: notebook.position,
+            note_count: 0,
+            created_at: notebook.created_at,
+            updated_at: notebook.updated_at,
+        })
+    }
+
+    #[tracing::instrument(skip(self, req))]
+    pub async fn rename_notebook(&self, notebook_id: Uuid, user_id: Uuid, req: UpdateNotebookRequest) -> Result<NotebookResponse> {
+        let mut tx = self.pool.begin().await?;
+        // Keeps a concurrent delete from removing the notebook between the
+        // ownership check and the update
+        lock_notebooks(&mut tx, user_id).await?;
+        owned_notebook(&mut tx, notebook_id, user_id).await?;
+
+        sqlx::query!(
+            "UPDATE notebooks SET name = $1 WHERE id = $2",
+            validation::sanitize_string(&req.name), notebook_id
+        )
+        .execute(&mut *tx)
+        .instrument(db_span!("UPDATE", "notebooks"))
+        .await?;
+        tx.commit().await?;
+
+        self.get_notebook(notebook_id, user_id).await
+    }
+
+    /// Move a notebook, with everything in it, under another parent or to
+    /// another position among its siblings
+    #[tracing::instrument(skip(self, req))]
+    pub async fn move_notebook(&self, notebook_id: Uuid, user_id: Uuid, req: MoveNotebookRequest) -> Result<NotebookResponse> {
+        let mut tx = self.pool.begin().await?;
+        // Held until commit, so two concurrent moves can't each pass the
+        // cycle check and together form a loop
+        lock_notebooks(&mut tx, user_id).await?;
+        let notebook = owned_notebook(&mut tx, notebook_id, user_id).await?;
+
+        if let Some(parent_id) = req.parent_id {
+            owned_notebook(&mut tx, parent_id, user_id).await?;
+
+            let cycle = sqlx::query!(
+                r#"WITH RECURSIVE ancestors AS (
+                       SELECT id, parent_id FROM notebooks WHERE id = $1
+                       UNION ALL
+                       SELECT nb.id, nb.parent_id FROM notebooks nb
+                       INNER JOIN ancestors a ON nb.id = a.parent_id
+                   )
+                   SELECT EXISTS(SELECT 1 FROM ancestors WHERE id = $2) AS "cycle!""#,
+                parent_id, notebook_id
+            )
+            .fetch_one(&mut *tx)
+            .instrument(db_span!("SELECT", "notebooks"))
+            .await?
+            .cycle;
+
+            if cycle {
+                return Err(AppError::invalid_field(
+                    "parent_id",
+                    "cycle",
+                    "A notebook can't be moved into itself or one of its descendants",
+                ));
+            }
+        }
+
+        sqlx::query!(
+            "UPDATE notebooks SET parent_id = $1 WHERE id = $2",
+            req.parent_id, notebook_id
+        )
+        .execute(&mut *tx)
+        .instrument(db_span!("UPDATE", "notebooks"))
+        .await?;
+
+        let mut siblings = notebook_ids_in(&mut tx, user_id, req.parent_id, notebook_id).await?;
+        insert_at(&mut siblings, notebook_id, req.position);
+        renumber(&mut tx, "notebooks", &siblings).await?;
+        if notebook.parent_id != req.parent_id {
+            let left_behind = notebook_ids_in(&mut tx, user_id, notebook.parent_id, notebook_id).await?;
+            renumber(&mut tx, "notebooks", &left_behind).await?;
+        }
+        tx.commit().await?;
+
+        self.get_notebook(notebook_id, user_id).await
+    }
+
+    /// Delete an empty notebook; trashed notes in it move to the top level
+    #[tracing::instrument(skip(self))]
+    pub async fn delete_notebook(&self, notebook_id: Uuid, user_id: Uuid) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        lock_notebooks(&mut tx, user_id).await?;
+        owned_notebook(&mut tx, notebook_id, user_id).await?;
+
+        let not_empty = sqlx::query!(
+            r#"SELECT EXISTS(SELECT 1 FROM notebooks WHERE parent_id = $1)
+                   OR EXISTS(SELECT 1 FROM notes WHERE notebook_id = $1 AND is_deleted = false)
+                   AS "not_empty!""#,
+            notebook_id
+        )
+        .fetch_one(&mut *tx)
+        .instrument(db_span!("SELECT", "notebooks"))
+        .await?
+        .not_empty;
+
+        if not_empty {
+            return Err(AppError::problem(
+                ErrorCode::NotebookNotEmpty,
+                "Move or delete the notebook's notes and notebooks first",
+            ));
+        }
+
+        sqlx::query!("DELETE FROM notebooks WHERE id = $1", notebook_id)
+            .execute(&mut *tx)
+            .instrument(db_span!("DELETE", "notebooks"))
+            .await?;
+        tx.commit().await?;
+
+        // Cached lists filtered by this notebook must not outlive it
+        if let Some(cache) = &self.cache {
+            cache.invalidate_lists(user_id).await;
+        }
+
+        Ok(())
+    }
+    
+    /// Permanently remove notes soft-deleted more than `older_than` ago,
+    /// along with their revisions, tags and sessions
+    #[tracing::instrument(skip(self))]
+    pub async fn purge_deleted(&self, older_than: chrono::Duration, dry_run: bool) -> Result<u64> {
+        // Deleting a note bumps updated_at, so it doubles as the deletion time
+        let cutoff = chrono::Utc::now() - older_than;
+        
+        if dry_run {
+            let count = sqlx::query!(
+                r#"SELECT COUNT(*) as "count!" FROM notes WHERE is_deleted = true AND updated_at < $1"#,
+                cutoff
+            )
+            .fetch_one(&self.pool)
+            .instrument(db_span!("SELECT", "notes"))
+            .await?
+            .count;
+            return Ok(count as u64);
+        }
+        
+        let result = sqlx::query!(
+            "DELETE FROM notes WHERE is_deleted = true AND updated_at < $1",
+            cutoff
+        )
+        .execute(&self.pool)
+        .instrument(db_span!("DELETE", "notes"))
+        .await?;
+        
+        Ok(result.rows_affected())
+    }
+}
+
+/// Parse a comma-separated `fields` selector into sorted, distinct fields
+fn parse_fields(list: &str) -> Result<Vec<NoteField>> {
+    let mut fields = list
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty() && *name != "id")
+        .map(|name| {
+            name.parse::<NoteField>()
+                .map_err(|message| AppError::invalid_field("fields", "unknown", message))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    fields.sort();
+    fields.dedup();
+    Ok(fields)
+}
+
+/// Serialize changes to one user's notebook tree and note order until the
+/// transaction ends
+async fn lock_notebooks(conn: &mut PgConnection, user_id: Uuid) -> Result<()> {
+    sqlx::query("SELECT pg_advisory_xact_lock(hashtextextended($1, 0))")
+        .bind(format!("notebooks:{}", user_id))
+        .execute(conn)
+        .instrument(db_span!("SELECT", "pg_advisory_xact_lock"))
+        .await?;
+    Ok(())
+}
+
+/// A notebook, checked to belong to `user_id`
+async fn owned_notebook(conn: &mut PgConnection, notebook_id: Uuid, user_id: Uuid) -> Result<Notebook> {
+    let notebook = sqlx::query_as!(
+        Notebook,
+        "SELECT * FROM notebooks WHERE id = $1",
+        notebook_id
+    )
+    .fetch_optional(conn)
+    .instrument(db_span!("SELECT", "notebooks"))
+    .await?
+    .ok_or_else(|| AppError::problem(ErrorCode::NotebookNotFound, "Notebook not found"))?;
+
+    if notebook.user_id != user_id {
+        return Err(AppError::Forbidden("Not authorized to access this notebook".to_string()));
+    }
+    Ok(notebook)
+}
+
+/// Child notebooks of `parent_id` (top level if `None`) in order, except `except`
+async fn notebook_ids_in(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    parent_id: Option<Uuid>,
+    except: Uuid,
+) -> Result<Vec<Uuid>> {
+    Ok(sqlx::query_scalar!(
+        r#"SELECT id FROM notebooks
+           WHERE user_id = $1 AND parent_id IS NOT DISTINCT FROM $2 AND id <> $3
+           ORDER BY position, id"#,
+        user_id, parent_id, except
+    )
+    .fetch_all(conn)
+    .instrument(db_span!("SELECT", "notebooks"))
+    .await?)
+}
+
+/// Live notes in `notebook_id` (top level if `None`) in order, except `except`
+async fn note_ids_in(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    notebook_id: Option<Uuid>,
+    except: Uuid,
+) -> Result<Vec<Uuid>> {
+    Ok(sqlx::query_scalar!(
+        r#"SELECT id FROM notes
+           WHERE user_id = $1 AND notebook_id IS NOT DISTINCT FROM $2 AND id <> $3
+             AND is_deleted = false
+           ORDER BY position, id"#,
+        user_id, notebook_id, except
+    )
+    .fetch_all(conn)
+    .instrument(db_span!("SELECT", "notes"))
+    .await?)
+}
+
+/// Trimmed, distinct tag names, each checked against the tag name limits
+fn parse_tag_names(names: &[String]) -> Result<Vec<String>> {
+    if names.is_empty() {
+        return Err(AppError::invalid_field("tags", "length", "Give at least one tag"));
+    }
+
+    let mut tags: Vec<String> = Vec::with_capacity(names.len());
+    for name in names {
+        let name = validation::sanitize_string(name);
+        if name.is_empty() || name.chars().count() > 50 {
+            return Err(AppError::invalid_field(
+                "tags",
+                "length",
+                "Tag names must be between 1 and 50 characters",
+            ));
+        }
+        if !tags.contains(&name) {
+            tags.push(name);
+        }
+    }
+    Ok(tags)
+}
+
+/// Place `id` at `position` among `siblings`, or last if omitted or past the end
+fn insert_at(siblings: &mut Vec<Uuid>, id: Uuid, position: Option<i32>) {
+    let index = position.map_or(siblings.len(), |p| (p.max(0) as usize).min(siblings.len()));
+    siblings.insert(index, id);
+}
+
+/// Store the order of `ids` as positions 0, 1, 2... in `table` (`notes` or
+/// `notebooks`), writing only rows that move
+async fn renumber(conn: &mut PgConnection, table: &'static str, ids: &[Uuid]) -> Result<()> {
+    sqlx::query(&format!(
+        r#"UPDATE {table} t SET position = o.ord - 1
+           FROM unnest($1::uuid[]) WITH ORDINALITY AS o(id, ord)
+           WHERE t.id = o.id AND t.position <> o.ord - 1"#
+    ))
+    .bind(ids)
+    .execute(conn)
+    .instrument(db_span!("UPDATE", table))
+    .await?;
+    Ok(())
+}
+
+/// Apply the list filters of `params` to a notes query
+fn push_filters<'a>(query: &mut QueryBuilder<'a, Postgres>, params: &'a NoteQueryParams) {
+    match params.notebook_id {
+        Some(NotebookFilter::Root) => {
+            query.push(" AND notebook_id IS NULL");
+        }
+        Some(NotebookFilter::Notebook(notebook_id)) => {
+            query.push(" AND notebook_id = ");
+            query.push_bind(notebook_id);
+        }
+        None => {}
+    }
+
+    let q = params.q.as_deref().map(str::trim).filter(|q| !q.is_empty());
+    // Archived notes stay findable by search
+    let archived = params.archived.or(if q.is_some() { None } else { Some(false) });
+    for (column, value) in [
+        ("is_pinned", params.pinned),
+        ("is_favorite", params.favorite),
+        ("is_archived", archived),
+    ] {
+        if let Some(value) = value {
+            query.push(format!(" AND {} = ", column));
+            query.push_bind(value);
+        }
+    }
+
+    if let Some(q) = q {
+        // Matches the expressions of the full-text indexes, so both are used
+        query.push(" AND (to_tsvector('english', title) @@ websearch_to_tsquery('english', ");
+        query.push_bind(q);
+        query.push(") OR to_tsvector('english', content) @@ websearch_to_tsquery('english', ");
+        query.push_bind(q);
+        query.push("))");
+    }
+
+    if let Some(tag) = params.tag.as_deref().map(str::trim).filter(|t| !t.is_empty()) {
+        query.push(
+            " AND EXISTS (SELECT 1 FROM note_tags nt INNER JOIN tags t ON t.id = nt.tag_id \
+             WHERE nt.note_id = notes.id AND t.name = ",
+        );
+        query.push_bind(tag);
+        query.push(")");
+    }
+}
+
+/// Project a note onto the selected fields
+fn list_item(note: Note, fields: &[NoteField], tags: Option<Vec<String>>) -> NoteListItem {
+    let has = |field| fields.contains(&field);
+    let plain = (has(NoteField::Excerpt) || has(NoteField::WordCount))
+        .then(|| text::plain_text(&note.content));
+
+    NoteListItem {
+        id: note.id,
+        excerpt: plain
+            .as_deref()
+            .filter(|_| has(NoteField::Excerpt))
+            .map(|plain| text::excerpt(plain, EXCERPT_CHARS)),
+        word_count: plain
+            .as_deref()
+            .filter(|_| has(NoteField::WordCount))
+            .map(text::word_count),
+        title: has(NoteField::Title).then_some(note.title),
+        content: has(NoteField::Content).then_some(note.content),
+        tags: has(NoteField::Tags).then(|| tags.unwrap_or_default()),
+        last_edited_by: has(NoteField::LastEditedBy).then_some(note.last_edited_by),
+        notebook_id: has(NoteField::NotebookId).then_some(note.notebook_id),
+        pinned: has(NoteField::Pinned).then_some(note.is_pinned),
+        archived: has(NoteField::Archived).then_some(note.is_archived),
+        favorite: has(NoteField::Favorite).then_some(note.is_favorite),
+        created_at: has(NoteField::CreatedAt).then_some(note.created_at),
+        updated_at: has(NoteField::UpdatedAt).then_some(note.updated_at),
+    }
+}
@@ -0,0 +1,101 @@
+//! Short-lived cache of authenticated users, so protected requests don't
+//! each query the users table
+
+use dashmap::DashMap;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use super::note_cache::{decode, encode, log_failure};
+use crate::db::RedisManager;
+use crate::metrics::CACHE_REQUESTS_TOTAL;
+use crate::models::user::AuthUser;
+
+const AUTH_USER: &str = "auth_user";
+
+/// In-memory entries beyond which expired ones are swept on insert
+const MEMORY_SWEEP_THRESHOLD: usize = 10_000;
+
+pub struct UserCache {
+    ttl: Duration,
+    store: Store,
+}
+
+enum Store {
+    /// Entries keyed by user ID with their expiry
+    Memory(DashMap<Uuid, (Instant, AuthUser)>),
+    Redis(RedisManager),
+}
+
+impl UserCache {
+    /// Per-process cache; changes made elsewhere show up once entries expire
+    pub fn memory(ttl_secs: u64) -> Self {
+        Self {
+            ttl: Duration::from_secs(ttl_secs),
+            store: Store::Memory(DashMap::new()),
+        }
+    }
+
+    /// Cache shared by every replica and the admin CLI
+    pub fn redis(redis: RedisManager, ttl_secs: u64) -> Self {
+        Self {
+            ttl: Duration::from_secs(ttl_secs),
+            store: Store::Redis(redis),
+        }
+    }
+
+    pub async fn get(&self, user_id: Uuid) -> Option<AuthUser> {
+        match &self.store {
+            Store::Memory(entries) => {
+                let now = Instant::now();
+                let user = entries
+                    .get(&user_id)
+                    .filter(|entry| entry.0 > now)
+                    .map(|entry| entry.1.clone());
+                let result = if user.is_some() { "hit" } else { "miss" };
+                metrics::counter!(CACHE_REQUESTS_TOTAL, "cache" => AUTH_USER, "result" => result)
+                    .increment(1);
+                user
+            }
+            Store::Redis(redis) => decode(AUTH_USER, redis.clone().get(&key(user_id)).await),
+        }
+    }
+
+    pub async fn put(&self, user: &AuthUser) {
+        match &self.store {
+            Store::Memory(entries) => {
+                let now = Instant::now();
+                if entries.len() >= MEMORY_SWEEP_THRESHOLD {
+                    entries.retain(|_, (expires, _)| *expires > now);
+                }
+                entries.insert(user.id, (now + self.ttl, user.clone()));
+            }
+            Store::Redis(redis) => {
+                let Some(json) = encode(AUTH_USER, user) else {
+                    return;
+                };
+                let result = redis
+                    .clone()
+                    .set_with_expiry(&key(user.id), &json, self.ttl.as_secs() as usize)
+                    .await;
+                log_failure(AUTH_USER, "write", result);
+            }
+        }
+    }
+
+    /// Forget a user after their account changes
+    pub async fn invalidate(&self, user_id: Uuid) {
+        match &self.store {
+            Store::Memory(entries) => {
+                entries.remove(&user_id);
+            }
+            Store::Redis(redis) => {
+                let result = redis.clone().delete(&key(user_id)).await;
+                log_failure(AUTH_USER, "invalidate", result);
+            }
+        }
+    }
+}
+
+fn key(user_id: Uuid) -> String {
+    format!("cache:auth_user:{}", user_id)
+}
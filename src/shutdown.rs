@@ -0,0 +1,32 @@
+//! Process shutdown coordination.
+
+use tokio_util::sync::CancellationToken;
+
+/// Wait for SIGINT or SIGTERM, then cancel `token` so every task watching it
+/// starts shutting down
+pub async fn wait_for_signal(token: CancellationToken) {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => tracing::info!("🛑 Received SIGINT, shutting down..."),
+        _ = terminate => tracing::info!("🛑 Received SIGTERM, shutting down..."),
+        _ = token.cancelled() => return,
+    }
+
+    token.cancel();
+}
@@ -0,0 +1,125 @@
+//! Logging and distributed tracing setup.
+
+use axum::{
+    body::Body,
+    http::{HeaderMap, HeaderName, Request},
+};
+use opentelemetry::{global, propagation::Extractor, trace::TracerProvider as _, KeyValue};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{propagation::TraceContextPropagator, runtime, trace, Resource};
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+use crate::config::Config;
+use crate::utils::errors::{AppError, Result};
+
+/// Header carrying the per-request correlation ID
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Handle to the installed tracing pipeline; flush it with [`Telemetry::shutdown`]
+pub struct Telemetry {
+    provider: Option<trace::TracerProvider>,
+}
+
+impl Telemetry {
+    /// Flush buffered spans to the collector
+    pub fn shutdown(self) {
+        if self.provider.is_some() {
+            global::shutdown_tracer_provider();
+        }
+    }
+}
+
+/// Install the global subscriber: env-filtered fmt logs, plus OTLP span
+/// export when `otel_exporter_endpoint` is configured
+///
+/// Must be called from within the tokio runtime.
+pub fn init(config: &Config) -> Result<Telemetry> {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let provider = config
+        .otel_exporter_endpoint
+        .as_deref()
+        .map(|endpoint| otlp_provider(endpoint, &config.otel_service_name))
+        .transpose()?;
+
+    let otel_layer = provider.as_ref().map(|provider| {
+        tracing_opentelemetry::layer().with_tracer(provider.tracer(crate::APP_NAME))
+    });
+
+    tracing_subscriber::registry()
+        .with(
+            EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| "noteflow_backend=debug,tower_http=debug,sqlx=info".into()),
+        )
+        .with(tracing_subscriber::fmt::layer())
+        .with(otel_layer)
+        .init();
+
+    if let Some(endpoint) = &config.otel_exporter_endpoint {
+        tracing::info!("📡 Exporting traces via OTLP to {}", endpoint);
+    }
+
+    Ok(Telemetry { provider })
+}
+
+fn otlp_provider(endpoint: &str, service_name: &str) -> Result<trace::TracerProvider> {
+    let provider = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(endpoint),
+        )
+        .with_trace_config(trace::Config::default().with_resource(Resource::new(vec![
+            KeyValue::new("service.name", service_name.to_string()),
+            KeyValue::new("service.version", crate::VERSION),
+        ])))
+        .install_batch(runtime::Tokio)
+        .map_err(|e| AppError::InternalError(format!("OTLP exporter setup failed: {}", e)))?;
+
+    global::set_tracer_provider(provider.clone());
+    Ok(provider)
+}
+
+/// Root span for an HTTP request, for use with `TraceLayer::make_span_with`
+///
+/// Records the request ID so every event logged while handling the request
+/// carries it, and continues the caller's trace when a W3C `traceparent`
+/// header is present. Only the path is recorded: query strings can carry
+/// search terms, cursors and tokens that don't belong in logs or traces.
+pub fn make_request_span(req: &Request<Body>) -> Span {
+    let request_id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("-");
+
+    let span = tracing::info_span!(
+        "request",
+        method = %req.method(),
+        path = %req.uri().path(),
+        version = ?req.version(),
+        request_id = %request_id,
+    );
+
+    let parent = global::get_text_map_propagator(|propagator| {
+        propagator.extract(&HeaderExtractor(req.headers()))
+    });
+    span.set_parent(parent);
+
+    span
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|v| v.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|k| k.as_str()).collect()
+    }
+}
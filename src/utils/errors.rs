@@ -1,10 +1,149 @@
 use axum::{
-    http::StatusCode,
+    extract::rejection::{JsonRejection, PathRejection, QueryRejection},
+    http::{header, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
-use serde_json::json;
+use serde::Serialize;
+use utoipa::ToSchema;
+use std::borrow::Cow;
 use std::fmt;
+use validator::{ValidationErrors, ValidationErrorsKind};
+
+use crate::middleware::request_context::RequestContext;
+
+/// Prefix of the problem `type` URI; the error code completes it
+const PROBLEM_TYPE_PREFIX: &str = "urn:noteflow:problem:";
+
+/// Stable, machine-readable error identifiers
+///
+/// Clients should branch on these rather than on `detail` text. Codes are
+/// never renamed or reused; new ones may be added.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    BadRequest,
+    InvalidJson,
+    ValidationFailed,
+    PayloadTooLarge,
+    UnsupportedMediaType,
+    Unauthorized,
+    MissingToken,
+    InvalidToken,
+    TokenExpired,
+    InvalidCredentials,
+    Forbidden,
+    AccountDisabled,
+    NoteLimitReached,
+    NotFound,
+    NoteNotFound,
+    NotebookNotFound,
+    UserNotFound,
+    Conflict,
+    EmailTaken,
+    NotebookNotEmpty,
+    RateLimited,
+    InternalError,
+}
+
+impl ErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::BadRequest => "bad_request",
+            ErrorCode::InvalidJson => "invalid_json",
+            ErrorCode::PayloadTooLarge => "payload_too_large",
+            ErrorCode::UnsupportedMediaType => "unsupported_media_type",
+            ErrorCode::ValidationFailed => "validation_failed",
+            ErrorCode::Unauthorized => "unauthorized",
+            ErrorCode::MissingToken => "missing_token",
+            ErrorCode::InvalidToken => "invalid_token",
+            ErrorCode::TokenExpired => "token_expired",
+            ErrorCode::InvalidCredentials => "invalid_credentials",
+            ErrorCode::Forbidden => "forbidden",
+            ErrorCode::AccountDisabled => "account_disabled",
+            ErrorCode::NoteLimitReached => "note_limit_reached",
+            ErrorCode::NotFound => "not_found",
+            ErrorCode::NoteNotFound => "note_not_found",
+            ErrorCode::NotebookNotFound => "notebook_not_found",
+            ErrorCode::UserNotFound => "user_not_found",
+            ErrorCode::Conflict => "conflict",
+            ErrorCode::EmailTaken => "email_taken",
+            ErrorCode::NotebookNotEmpty => "notebook_not_empty",
+            ErrorCode::RateLimited => "rate_limited",
+            ErrorCode::InternalError => "internal_error",
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ErrorCode::BadRequest | ErrorCode::InvalidJson | ErrorCode::ValidationFailed => {
+                StatusCode::BAD_REQUEST
+            }
+            ErrorCode::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            ErrorCode::UnsupportedMediaType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ErrorCode::Unauthorized
+            | ErrorCode::MissingToken
+            | ErrorCode::InvalidToken
+            | ErrorCode::TokenExpired
+            | ErrorCode::InvalidCredentials => StatusCode::UNAUTHORIZED,
+            ErrorCode::Forbidden | ErrorCode::AccountDisabled | ErrorCode::NoteLimitReached => {
+                StatusCode::FORBIDDEN
+            }
+            ErrorCode::NotFound
+            | ErrorCode::NoteNotFound
+            | ErrorCode::NotebookNotFound
+            | ErrorCode::UserNotFound => StatusCode::NOT_FOUND,
+            ErrorCode::Conflict | ErrorCode::EmailTaken | ErrorCode::NotebookNotEmpty => {
+                StatusCode::CONFLICT
+            }
+            ErrorCode::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            ErrorCode::InternalError => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Short summary that is the same for every occurrence of the code
+    pub fn title(&self) -> &'static str {
+        match self {
+            ErrorCode::BadRequest => "Bad request",
+            ErrorCode::InvalidJson => "Invalid JSON body",
+            ErrorCode::PayloadTooLarge => "Payload too large",
+            ErrorCode::UnsupportedMediaType => "Unsupported media type",
+            ErrorCode::ValidationFailed => "Validation failed",
+            ErrorCode::Unauthorized => "Authentication required",
+            ErrorCode::MissingToken => "Missing access token",
+            ErrorCode::InvalidToken => "Invalid token",
+            ErrorCode::TokenExpired => "Token expired",
+            ErrorCode::InvalidCredentials => "Invalid credentials",
+            ErrorCode::Forbidden => "Forbidden",
+            ErrorCode::AccountDisabled => "Account disabled",
+            ErrorCode::NoteLimitReached => "Note limit reached",
+            ErrorCode::NotFound => "Not found",
+            ErrorCode::NoteNotFound => "Note not found",
+            ErrorCode::NotebookNotFound => "Notebook not found",
+            ErrorCode::UserNotFound => "User not found",
+            ErrorCode::Conflict => "Conflict",
+            ErrorCode::EmailTaken => "Email already registered",
+            ErrorCode::NotebookNotEmpty => "Notebook not empty",
+            ErrorCode::RateLimited => "Rate limit exceeded",
+            ErrorCode::InternalError => "Internal server error",
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// One invalid input field
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct FieldError {
+    /// Field name as it appears in the request body or query
+    pub field: String,
+    /// Machine-readable reason, e.g. `length` or `email`
+    pub code: String,
+    pub message: String,
+}
 
 #[derive(Debug)]
 pub enum AppError {
@@ -12,12 +151,52 @@ pub enum AppError {
     RedisError(redis::RedisError),
     AuthenticationError(String),
     ValidationError(String),
+    /// One or more request fields failed validation
+    InvalidFields(Vec<FieldError>),
     NotFound(String),
     Forbidden(String),
     InternalError(String),
     BadRequest(String),
     Conflict(String),
     RateLimitExceeded,
+    /// An error with a specific catalogue code and a human-readable detail
+    Problem(ErrorCode, String),
+}
+
+impl AppError {
+    pub fn problem(code: ErrorCode, detail: impl Into<String>) -> Self {
+        AppError::Problem(code, detail.into())
+    }
+
+    pub fn invalid_field(
+        field: impl Into<String>,
+        code: impl Into<String>,
+        message: impl Into<String>,
+    ) -> Self {
+        AppError::InvalidFields(vec![FieldError {
+            field: field.into(),
+            code: code.into(),
+            message: message.into(),
+        }])
+    }
+
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            AppError::DatabaseError(_) | AppError::RedisError(_) | AppError::InternalError(_) => {
+                ErrorCode::InternalError
+            }
+            AppError::AuthenticationError(_) => ErrorCode::Unauthorized,
+            AppError::ValidationError(_) | AppError::InvalidFields(_) => {
+                ErrorCode::ValidationFailed
+            }
+            AppError::NotFound(_) => ErrorCode::NotFound,
+            AppError::Forbidden(_) => ErrorCode::Forbidden,
+            AppError::BadRequest(_) => ErrorCode::BadRequest,
+            AppError::Conflict(_) => ErrorCode::Conflict,
+            AppError::RateLimitExceeded => ErrorCode::RateLimited,
+            AppError::Problem(code, _) => *code,
+        }
+    }
 }
 
 impl fmt::Display for AppError {
@@ -27,12 +206,20 @@ impl fmt::Display for AppError {
             AppError::RedisError(e) => write!(f, "Redis error: {}", e),
             AppError::AuthenticationError(msg) => write!(f, "Authentication error: {}", msg),
             AppError::ValidationError(msg) => write!(f, "Validation error: {}", msg),
+            AppError::InvalidFields(errors) => {
+                write!(f, "Validation error:")?;
+                for e in errors {
+                    write!(f, " {}: {};", e.field, e.message)?;
+                }
+                Ok(())
+            }
             AppError::NotFound(msg) => write!(f, "Not found: {}", msg),
             AppError::Forbidden(msg) => write!(f, "Forbidden: {}", msg),
             AppError::InternalError(msg) => write!(f, "Internal error: {}", msg),
             AppError::BadRequest(msg) => write!(f, "Bad request: {}", msg),
             AppError::Conflict(msg) => write!(f, "Conflict: {}", msg),
             AppError::RateLimitExceeded => write!(f, "Rate limit exceeded"),
+            AppError::Problem(code, msg) => write!(f, "{}: {}", code.title(), msg),
         }
     }
 }
@@ -48,38 +235,81 @@ impl std::error::Error for AppError {
     }
 }
 
+/// RFC 7807 problem details body
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ProblemDetails {
+    /// URI identifying the problem type
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    pub title: &'static str,
+    pub status: u16,
+    pub detail: String,
+    /// Path of the request that failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+    /// Stable error code, e.g. `note_limit_reached`
+    pub code: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    /// Per-field failures for `validation_failed`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>,
+}
+
+/// Rendered as `application/problem+json`
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        let (status, error_message) = match self {
+        let code = self.code();
+        let status = code.status();
+
+        let (detail, errors) = match self {
             AppError::DatabaseError(e) => {
                 tracing::error!("Database error: {:?}", e);
-                (StatusCode::INTERNAL_SERVER_ERROR, "Database error occurred".to_string())
+                ("Database error occurred".to_string(), vec![])
             }
             AppError::RedisError(e) => {
                 tracing::error!("Redis error: {:?}", e);
-                (StatusCode::INTERNAL_SERVER_ERROR, "Cache error occurred".to_string())
+                ("Cache error occurred".to_string(), vec![])
             }
-            AppError::AuthenticationError(msg) => (StatusCode::UNAUTHORIZED, msg),
-            AppError::ValidationError(msg) => (StatusCode::BAD_REQUEST, msg),
-            AppError::NotFound(msg) => (StatusCode::NOT_FOUND, msg),
-            AppError::Forbidden(msg) => (StatusCode::FORBIDDEN, msg),
             AppError::InternalError(msg) => {
                 tracing::error!("Internal error: {}", msg);
-                (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error".to_string())
+                ("Internal server error".to_string(), vec![])
             }
-            AppError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
-            AppError::Conflict(msg) => (StatusCode::CONFLICT, msg),
-            AppError::RateLimitExceeded => {
-                (StatusCode::TOO_MANY_REQUESTS, "Rate limit exceeded".to_string())
+            AppError::InvalidFields(errors) => {
+                let detail = match errors.as_slice() {
+                    [only] => only.message.clone(),
+                    _ => format!("{} fields are invalid", errors.len()),
+                };
+                (detail, errors)
             }
+            AppError::RateLimitExceeded => ("Rate limit exceeded".to_string(), vec![]),
+            AppError::AuthenticationError(msg)
+            | AppError::ValidationError(msg)
+            | AppError::NotFound(msg)
+            | AppError::Forbidden(msg)
+            | AppError::BadRequest(msg)
+            | AppError::Conflict(msg)
+            | AppError::Problem(_, msg) => (msg, vec![]),
         };
 
-        let body = Json(json!({
-            "error": error_message,
-            "status": status.as_u16()
-        }));
+        let context = RequestContext::current();
+        let body = ProblemDetails {
+            problem_type: format!("{}{}", PROBLEM_TYPE_PREFIX, code),
+            title: code.title(),
+            status: status.as_u16(),
+            detail,
+            instance: context.as_ref().map(|c| c.path.clone()),
+            code: code.as_str(),
+            request_id: context.and_then(|c| c.request_id),
+            errors,
+        };
 
-        (status, body).into_response()
+        (
+            status,
+            [(header::CONTENT_TYPE, "application/problem+json")],
+            Json(body),
+        )
+            .into_response()
     }
 }
 
@@ -95,4 +325,96 @@ impl From<redis::RedisError> for AppError {
     }
 }
 
-pub type Result<T> = std::result::Result<T, AppError>;
\ No newline at end of file
+/// Malformed bodies from axum's `Json` extractor
+impl From<JsonRejection> for AppError {
+    fn from(rejection: JsonRejection) -> Self {
+        let code = match &rejection {
+            JsonRejection::JsonSyntaxError(_) | JsonRejection::JsonDataError(_) => {
+                ErrorCode::InvalidJson
+            }
+            JsonRejection::MissingJsonContentType(_) => ErrorCode::UnsupportedMediaType,
+            _ if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE => {
+                ErrorCode::PayloadTooLarge
+            }
+            _ => ErrorCode::BadRequest,
+        };
+        AppError::problem(code, rejection.body_text())
+    }
+}
+
+/// Malformed query strings from axum's `Query` extractor
+impl From<QueryRejection> for AppError {
+    fn from(rejection: QueryRejection) -> Self {
+        AppError::problem(ErrorCode::BadRequest, rejection.body_text())
+    }
+}
+
+/// Path segments that don't parse, e.g. a malformed note ID
+impl From<PathRejection> for AppError {
+    fn from(rejection: PathRejection) -> Self {
+        match rejection {
+            PathRejection::FailedToDeserializePathParams(e) => {
+                AppError::problem(ErrorCode::BadRequest, e.body_text())
+            }
+            // Only a route and handler that disagree end up here
+            other => AppError::InternalError(other.body_text()),
+        }
+    }
+}
+
+/// Failed `validator` rules, one entry per violated rule
+impl From<ValidationErrors> for AppError {
+    fn from(errors: ValidationErrors) -> Self {
+        let mut fields = Vec::new();
+        collect_field_errors("", &errors, &mut fields);
+        fields.sort_by(|a, b| a.field.cmp(&b.field));
+        AppError::InvalidFields(fields)
+    }
+}
+
+fn collect_field_errors(prefix: &str, errors: &ValidationErrors, out: &mut Vec<FieldError>) {
+    for (field, kind) in errors.errors() {
+        let path = if prefix.is_empty() {
+            field.to_string()
+        } else {
+            format!("{}.{}", prefix, field)
+        };
+        match kind {
+            ValidationErrorsKind::Field(errors) => {
+                out.extend(errors.iter().map(|e| FieldError {
+                    message: e
+                        .message
+                        .as_ref()
+                        .map(Cow::to_string)
+                        .unwrap_or_else(|| describe_rule(&path, e)),
+                    field: path.clone(),
+                    code: e.code.to_string(),
+                }));
+            }
+            ValidationErrorsKind::Struct(nested) => collect_field_errors(&path, nested, out),
+            ValidationErrorsKind::List(items) => {
+                for (index, nested) in items {
+                    collect_field_errors(&format!("{}[{}]", path, index), nested, out);
+                }
+            }
+        }
+    }
+}
+
+/// Default message for a rule that doesn't set its own
+fn describe_rule(field: &str, error: &validator::ValidationError) -> String {
+    let param = |name: &str| error.params.get(name).map(|v| v.to_string());
+    match (error.code.as_ref(), param("min"), param("max")) {
+        ("length", Some(min), Some(max)) => {
+            format!("{} must be between {} and {} characters", field, min, max)
+        }
+        ("length", Some(min), None) if min == "1" => format!("{} is required", field),
+        ("length", Some(min), None) => format!("{} must be at least {} characters", field, min),
+        ("length", None, Some(max)) => format!("{} must be at most {} characters", field, max),
+        ("email", _, _) => format!("{} must be a valid email address", field),
+        ("blank", _, _) => format!("{} must not be blank", field),
+        _ => format!("{} is invalid", field),
+    }
+}
+
+pub type Result<T> = std::result::Result<T, AppError>;
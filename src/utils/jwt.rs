@@ -1,8 +1,8 @@
 use chrono::{Duration, Utc};
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use jsonwebtoken::{decode, encode, errors::ErrorKind, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use crate::utils::errors::{AppError, Result};
+use crate::utils::errors::{AppError, ErrorCode, Result};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
@@ -75,14 +75,20 @@ impl JwtManager {
             &Validation::default(),
         )
         .map(|data| data.claims)
-        .map_err(|e| AppError::AuthenticationError(format!("Invalid token: {}", e)))
+        .map_err(|e| match e.kind() {
+            ErrorKind::ExpiredSignature => {
+                AppError::problem(ErrorCode::TokenExpired, "Token has expired")
+            }
+            _ => AppError::problem(ErrorCode::InvalidToken, format!("Invalid token: {}", e)),
+        })
     }
 
     pub fn verify_access_token(&self, token: &str) -> Result<Claims> {
         let claims = self.verify_token(token)?;
         if claims.token_type != TokenType::Access {
-            return Err(AppError::AuthenticationError(
-                "Invalid token type".to_string(),
+            return Err(AppError::problem(
+                ErrorCode::InvalidToken,
+                "Invalid token type",
             ));
         }
         Ok(claims)
@@ -91,8 +97,9 @@ impl JwtManager {
     pub fn verify_refresh_token(&self, token: &str) -> Result<Claims> {
         let claims = self.verify_token(token)?;
         if claims.token_type != TokenType::Refresh {
-            return Err(AppError::AuthenticationError(
-                "Invalid token type".to_string(),
+            return Err(AppError::problem(
+                ErrorCode::InvalidToken,
+                "Invalid token type",
             ));
         }
         Ok(claims)
@@ -1,6 +1,7 @@
 pub mod errors;
 pub mod jwt;
+pub mod text;
 pub mod validation;
 
-pub use errors::{AppError, Result};
+pub use errors::{AppError, ErrorCode, FieldError, ProblemDetails, Result};
 pub use jwt::JwtManager;
\ No newline at end of file
@@ -0,0 +1,227 @@
+use once_cell::sync::Lazy;
+use pulldown_cmark::{html, Event, Options, Parser, Tag, TagEnd};
+use std::borrow::Cow;
+
+/// Bump whenever rendering or sanitizing changes, so cached HTML is re-rendered
+pub const HTML_RENDER_VERSION: u32 = 1;
+
+/// Prefix for ids in rendered notes, so they can't clobber the page's own
+const ID_PREFIX: &str = "note-";
+
+/// Classes the renderer emits that are safe to keep
+const ALLOWED_CLASSES: [&str; 8] = [
+    "footnote-definition",
+    "footnote-definition-label",
+    "footnote-reference",
+    "markdown-alert-note",
+    "markdown-alert-tip",
+    "markdown-alert-important",
+    "markdown-alert-warning",
+    "markdown-alert-caution",
+];
+
+static SANITIZER: Lazy<ammonia::Builder<'static>> = Lazy::new(|| {
+    let mut builder = ammonia::Builder::default();
+    builder
+        // Task list checkboxes; any other input in the note becomes one too
+        .add_tags(["input"])
+        .add_tag_attributes("input", ["checked"])
+        .set_tag_attribute_value("input", "type", "checkbox")
+        .set_tag_attribute_value("input", "disabled", "")
+        .add_tag_attributes("div", ["id", "class"])
+        .add_tag_attributes("sup", ["class"])
+        .add_tag_attributes("code", ["class"])
+        .add_tag_attributes("blockquote", ["class"])
+        .add_tag_attributes("th", ["style"])
+        .add_tag_attributes("td", ["style"])
+        .id_prefix(Some(ID_PREFIX))
+        .attribute_filter(filter_attribute);
+    builder
+});
+
+/// Reduce Markdown to its readable text on a single line
+///
+/// Markup, link targets and HTML are dropped; block boundaries become spaces.
+pub fn plain_text(markdown: &str) -> String {
+    let mut text = String::with_capacity(markdown.len());
+
+    for event in Parser::new_ext(markdown, Options::all()) {
+        match event {
+            Event::Text(s) | Event::Code(s) => text.push_str(&s),
+            Event::SoftBreak | Event::HardBreak | Event::Rule => text.push(' '),
+            Event::End(
+                TagEnd::Emphasis
+                | TagEnd::Strong
+                | TagEnd::Strikethrough
+                | TagEnd::Link
+                | TagEnd::Image,
+            ) => {}
+            Event::End(_) => text.push(' '),
+            _ => {}
+        }
+    }
+
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Reduce Markdown to readable text, keeping its paragraphs and lines
+///
+/// Lists keep their bullets or numbers and task items a `[x]` or `[ ]`;
+/// emphasis, link targets, images and HTML are dropped.
+pub fn readable_text(markdown: &str) -> String {
+    let mut text = String::with_capacity(markdown.len());
+    // Next number of each open list; `None` for bullet lists
+    let mut lists: Vec<Option<u64>> = Vec::new();
+
+    for event in Parser::new_ext(markdown, Options::all()) {
+        match event {
+            Event::Text(s) | Event::Code(s) => text.push_str(&s),
+            Event::SoftBreak | Event::HardBreak => text.push('\n'),
+            Event::Rule => {
+                end_block(&mut text);
+                text.push_str("---");
+                end_block(&mut text);
+            }
+            Event::TaskListMarker(done) => text.push_str(if done { "[x] " } else { "[ ] " }),
+            Event::FootnoteReference(label) => {
+                text.push('[');
+                text.push_str(&label);
+                text.push(']');
+            }
+            Event::Start(Tag::FootnoteDefinition(label)) => {
+                end_block(&mut text);
+                text.push('[');
+                text.push_str(&label);
+                text.push_str("]: ");
+            }
+            Event::Start(Tag::List(start)) => {
+                end_line(&mut text);
+                lists.push(start);
+            }
+            Event::Start(Tag::Item) => {
+                end_line(&mut text);
+                let depth = lists.len().saturating_sub(1);
+                text.push_str(&"  ".repeat(depth));
+                match lists.last_mut() {
+                    Some(Some(number)) => {
+                        text.push_str(&format!("{}. ", number));
+                        *number += 1;
+                    }
+                    _ => text.push_str("- "),
+                }
+            }
+            Event::End(TagEnd::List(_)) => {
+                lists.pop();
+                if lists.is_empty() {
+                    end_block(&mut text);
+                }
+            }
+            Event::End(TagEnd::Item) => end_line(&mut text),
+            Event::End(TagEnd::TableCell) => text.push('\t'),
+            Event::End(TagEnd::TableHead | TagEnd::TableRow) => {
+                if text.ends_with('\t') {
+                    text.pop();
+                }
+                end_line(&mut text);
+            }
+            // Paragraphs inside list items stay on the item's lines
+            Event::End(TagEnd::Paragraph) if !lists.is_empty() => end_line(&mut text),
+            Event::End(
+                TagEnd::Paragraph
+                | TagEnd::Heading(_)
+                | TagEnd::CodeBlock
+                | TagEnd::BlockQuote(_)
+                | TagEnd::Table
+                | TagEnd::FootnoteDefinition,
+            ) => end_block(&mut text),
+            _ => {}
+        }
+    }
+
+    let mut text = text.trim().to_string();
+    text.push('\n');
+    text
+}
+
+/// Start a new line unless already at the start of one
+fn end_line(text: &mut String) {
+    if !text.is_empty() && !text.ends_with('\n') {
+        text.push('\n');
+    }
+}
+
+/// Leave a blank line before whatever comes next
+fn end_block(text: &mut String) {
+    end_line(text);
+    if !text.is_empty() && !text.ends_with("\n\n") {
+        text.push('\n');
+    }
+}
+
+/// Render Markdown to HTML that is safe to insert into a page
+///
+/// CommonMark with the GitHub extensions: tables, task lists, footnotes,
+/// strikethrough and alerts. Raw HTML in the note only survives as far as
+/// the sanitizer allows, so scripts, event handlers and `javascript:` URLs
+/// are removed.
+pub fn render_html(markdown: &str) -> String {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_GFM;
+
+    let mut unsafe_html = String::with_capacity(markdown.len() * 3 / 2);
+    html::push_html(&mut unsafe_html, Parser::new_ext(markdown, options));
+    SANITIZER.clean(&unsafe_html).to_string()
+}
+
+/// Narrow the attributes the sanitizer lets through to what the renderer emits
+fn filter_attribute<'a>(element: &str, attribute: &str, value: &'a str) -> Option<Cow<'a, str>> {
+    match (element, attribute) {
+        ("th" | "td", "style") => matches!(
+            value,
+            "text-align: left" | "text-align: center" | "text-align: right"
+        )
+        .then_some(value.into()),
+        (_, "class") => {
+            let classes: Vec<&str> = value
+                .split_whitespace()
+                .filter(|class| ALLOWED_CLASSES.contains(class) || is_language_class(class))
+                .collect();
+            (!classes.is_empty()).then(|| classes.join(" ").into())
+        }
+        // Footnote links must follow their targets' prefixed ids
+        ("a", "href") if value.starts_with('#') => {
+            Some(format!("#{}{}", ID_PREFIX, &value[1..]).into())
+        }
+        _ => Some(value.into()),
+    }
+}
+
+/// `language-rust` and the like, from fenced code blocks
+fn is_language_class(class: &str) -> bool {
+    class.strip_prefix("language-").is_some_and(|lang| {
+        !lang.is_empty()
+            && lang
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '+' | '#' | '.'))
+    })
+}
+
+/// At most `max_chars` characters of `text`, cut at a word boundary and
+/// marked with an ellipsis when shortened
+pub fn excerpt(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        None => text.to_string(),
+        Some((cut, _)) => {
+            let head = &text[..cut];
+            let head = head.rsplit_once(' ').map_or(head, |(words, _)| words);
+            format!("{}…", head.trim_end())
+        }
+    }
+}
+
+pub fn word_count(text: &str) -> usize {
+    text.split_whitespace().count()
+}
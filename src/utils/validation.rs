@@ -1,56 +1,71 @@
+use axum::{
+    async_trait,
+    extract::{FromRequest, FromRequestParts, Path, Request},
+    http::request::Parts,
+    Json,
+};
+use serde::de::DeserializeOwned;
+use validator::{Validate, ValidationError};
+
 use crate::utils::errors::{AppError, Result};
 
-pub fn validate_email(email: &str) -> Result<()> {
-    if !email.contains('@') || email.len() < 5 || email.len() > 255 {
-        return Err(AppError::ValidationError(
-            "Invalid email format".to_string(),
-        ));
-    }
-    Ok(())
-}
+/// JSON body that is deserialized and then checked against its `validator` rules
+///
+/// Malformed bodies and rule violations are both rejected through [`AppError`],
+/// the latter with every failing field listed.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ValidatedJson<T>(pub T);
 
-pub fn validate_password(password: &str) -> Result<()> {
-    if password.len() < 8 {
-        return Err(AppError::ValidationError(
-            "Password must be at least 8 characters".to_string(),
-        ));
-    }
-    if password.len() > 128 {
-        return Err(AppError::ValidationError(
-            "Password must be less than 128 characters".to_string(),
-        ));
+#[async_trait]
+impl<T, S> FromRequest<S> for ValidatedJson<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self> {
+        let Json(value) = Json::<T>::from_request(req, state).await?;
+        value.validate()?;
+        Ok(ValidatedJson(value))
     }
-    Ok(())
 }
 
-pub fn validate_note_content(content: &str, max_size: usize) -> Result<()> {
-    if content.len() > max_size {
-        return Err(AppError::ValidationError(format!(
-            "Note content exceeds maximum size of {} bytes",
-            max_size
-        )));
+/// Path parameters, with segments that don't parse rejected through [`AppError`]
+///
+/// axum's own `Path` answers those with a plain-text 400 instead.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ValidatedPath<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for ValidatedPath<T>
+where
+    T: DeserializeOwned + Send,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self> {
+        let Path(value) = Path::<T>::from_request_parts(parts, state).await?;
+        Ok(ValidatedPath(value))
     }
-    Ok(())
 }
 
-pub fn validate_note_title(title: &str) -> Result<()> {
-    if title.is_empty() {
-        return Err(AppError::ValidationError(
-            "Note title cannot be empty".to_string(),
-        ));
-    }
-    if title.len() > 255 {
-        return Err(AppError::ValidationError(
-            "Note title must be less than 255 characters".to_string(),
-        ));
+/// Rejects strings that are empty once surrounding whitespace is trimmed
+pub fn not_blank(value: &str) -> std::result::Result<(), ValidationError> {
+    if value.trim().is_empty() {
+        return Err(ValidationError::new("blank"));
     }
     Ok(())
 }
 
-pub fn validate_tag_name(name: &str) -> Result<()> {
-    if name.is_empty() || name.len() > 50 {
-        return Err(AppError::ValidationError(
-            "Tag name must be between 1 and 50 characters".to_string(),
+/// Content size depends on configuration, so it can't be a derive rule
+pub fn validate_note_content(content: &str, max_size: usize) -> Result<()> {
+    if content.len() > max_size {
+        return Err(AppError::invalid_field(
+            "content",
+            "length",
+            format!("Note content exceeds maximum size of {} bytes", max_size),
         ));
     }
     Ok(())
@@ -58,4 +73,4 @@ pub fn validate_tag_name(name: &str) -> Result<()> {
 
 pub fn sanitize_string(input: &str) -> String {
     input.trim().to_string()
-}
\ No newline at end of file
+}
@@ -0,0 +1,168 @@
+//! Client IP resolution through `Forwarded` / `X-Forwarded-For` and the
+//! trusted proxy walk.
+
+use axum::http::{HeaderMap, HeaderValue};
+use std::net::IpAddr;
+
+use noteflow_backend::middleware::TrustedProxies;
+
+/// The load balancer in front of the app and an internal proxy network
+fn proxies() -> TrustedProxies {
+    TrustedProxies::new(vec![
+        "10.0.0.0/8".parse().unwrap(),
+        "fd00::/8".parse().unwrap(),
+    ])
+}
+
+fn ip(s: &str) -> IpAddr {
+    s.parse().unwrap()
+}
+
+fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for (name, value) in pairs {
+        headers.append(*name, HeaderValue::from_static(value));
+    }
+    headers
+}
+
+const PROXY: &str = "10.0.0.1";
+
+fn resolve(pairs: &[(&'static str, &'static str)]) -> IpAddr {
+    proxies().resolve(ip(PROXY), &headers(pairs))
+}
+
+#[test]
+fn untrusted_peers_cannot_spoof_headers() {
+    let resolved = proxies().resolve(
+        ip("203.0.113.9"),
+        &headers(&[("x-forwarded-for", "198.51.100.1")]),
+    );
+    assert_eq!(resolved, ip("203.0.113.9"));
+}
+
+#[test]
+fn trusted_peer_without_headers_is_the_client() {
+    assert_eq!(resolve(&[]), ip(PROXY));
+}
+
+#[test]
+fn x_forwarded_for_takes_the_nearest_untrusted_hop() {
+    assert_eq!(resolve(&[("x-forwarded-for", "198.51.100.1")]), ip("198.51.100.1"));
+    // A client-supplied entry in front of the real one is ignored
+    assert_eq!(
+        resolve(&[("x-forwarded-for", "1.1.1.1, 198.51.100.1, 10.0.0.2")]),
+        ip("198.51.100.1")
+    );
+}
+
+#[test]
+fn repeated_x_forwarded_for_headers_form_one_list() {
+    assert_eq!(
+        resolve(&[
+            ("x-forwarded-for", "1.1.1.1"),
+            ("x-forwarded-for", "198.51.100.1, 10.0.0.2"),
+        ]),
+        ip("198.51.100.1")
+    );
+}
+
+#[test]
+fn x_forwarded_for_accepts_ports_and_ipv6() {
+    assert_eq!(resolve(&[("x-forwarded-for", "198.51.100.1:4711")]), ip("198.51.100.1"));
+    assert_eq!(resolve(&[("x-forwarded-for", "2001:db8::1")]), ip("2001:db8::1"));
+    assert_eq!(resolve(&[("x-forwarded-for", "[2001:db8::1]:4711")]), ip("2001:db8::1"));
+}
+
+#[test]
+fn forwarded_parses_quoted_nodes_ports_and_brackets() {
+    assert_eq!(resolve(&[("forwarded", "for=198.51.100.1")]), ip("198.51.100.1"));
+    assert_eq!(resolve(&[("forwarded", "For=\"198.51.100.1:4711\"")]), ip("198.51.100.1"));
+    assert_eq!(
+        resolve(&[("forwarded", "for=\"[2001:db8::1]:4711\";proto=https")]),
+        ip("2001:db8::1")
+    );
+    assert_eq!(
+        resolve(&[("forwarded", "proto=https;for=198.51.100.1;by=10.0.0.1")]),
+        ip("198.51.100.1")
+    );
+}
+
+#[test]
+fn forwarded_takes_precedence_over_x_forwarded_for() {
+    assert_eq!(
+        resolve(&[
+            ("x-forwarded-for", "192.0.2.7"),
+            ("forwarded", "for=198.51.100.1"),
+        ]),
+        ip("198.51.100.1")
+    );
+}
+
+#[test]
+fn unknown_and_obfuscated_nodes_end_the_chain_of_trust() {
+    // The hop behind an unidentifiable node can't be vouched for, so the
+    // last proxy that could be is taken as the client
+    assert_eq!(
+        resolve(&[("forwarded", "for=198.51.100.1, for=unknown, for=10.0.0.2")]),
+        ip("10.0.0.2")
+    );
+    assert_eq!(
+        resolve(&[("forwarded", "for=198.51.100.1, for=_hidden")]),
+        ip(PROXY)
+    );
+    assert_eq!(
+        resolve(&[("x-forwarded-for", "198.51.100.1, garbage")]),
+        ip(PROXY)
+    );
+}
+
+#[test]
+fn forwarded_elements_without_for_still_count_as_hops() {
+    // The middle proxy only added `by=`; skipping it would make the trusted
+    // 10.0.0.2 look like it reported 198.51.100.1 directly
+    assert_eq!(
+        resolve(&[(
+            "forwarded",
+            "for=198.51.100.1, by=10.0.0.3;proto=https, for=10.0.0.2"
+        )]),
+        ip("10.0.0.2")
+    );
+}
+
+#[test]
+fn trusted_proxy_depth_is_walked_to_the_first_untrusted_hop() {
+    assert_eq!(
+        resolve(&[("x-forwarded-for", "198.51.100.1, 10.1.0.1, 10.2.0.1, fd00::5")]),
+        ip("198.51.100.1")
+    );
+    // Only trusted hops: the outermost one is the best guess
+    assert_eq!(
+        resolve(&[("x-forwarded-for", "10.1.0.1, 10.2.0.1")]),
+        ip("10.1.0.1")
+    );
+}
+
+#[test]
+fn ipv4_mapped_addresses_match_ipv4_proxies() {
+    // A dual-stack listener reports IPv4 peers as `::ffff:a.b.c.d`
+    assert_eq!(
+        proxies().resolve(ip("::ffff:10.0.0.1"), &headers(&[("x-forwarded-for", "198.51.100.1")])),
+        ip("198.51.100.1")
+    );
+    assert_eq!(
+        resolve(&[("x-forwarded-for", "198.51.100.1, ::ffff:10.0.0.2")]),
+        ip("198.51.100.1")
+    );
+    assert!(proxies().contains(&ip("::ffff:10.9.9.9")));
+
+    // Mapped clients come back as plain IPv4, so they share a rate limit key
+    assert_eq!(
+        resolve(&[("x-forwarded-for", "::ffff:198.51.100.1")]),
+        ip("198.51.100.1")
+    );
+    assert_eq!(
+        proxies().resolve(ip("::ffff:203.0.113.9"), &headers(&[])),
+        ip("203.0.113.9")
+    );
+}
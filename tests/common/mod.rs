@@ -0,0 +1,51 @@
+//! Setup shared by the tests that run against a database
+
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use noteflow_backend::{config::Config, services::NoteService, utils::errors::AppError};
+
+fn config() -> Config {
+    let config_path = std::env::temp_dir().join(format!(
+        "noteflow-db-test-{}-{}.toml",
+        std::process::id(),
+        Uuid::new_v4()
+    ));
+    std::fs::write(
+        &config_path,
+        r#"
+        database_url = "postgres://localhost/noteflow"
+        redis_url = "redis://localhost"
+        jwt_secret = "database-test-secret-0123456789abcdef"
+        "#,
+    )
+    .unwrap();
+    let config = Config::load(Some(&config_path)).unwrap();
+    std::fs::remove_file(&config_path).ok();
+    config
+}
+
+pub fn service(pool: &PgPool) -> NoteService {
+    NoteService::new(pool.clone(), config())
+}
+
+pub async fn user(pool: &PgPool) -> Uuid {
+    sqlx::query_scalar(
+        "INSERT INTO users (email, password_hash, display_name) VALUES ($1, 'x', 'Test') RETURNING id",
+    )
+    .bind(format!("{}@example.com", Uuid::new_v4()))
+    .fetch_one(pool)
+    .await
+    .unwrap()
+}
+
+/// Field and code of a single-field validation error
+pub fn field_error(error: AppError) -> (String, String) {
+    match error {
+        AppError::InvalidFields(mut errors) if errors.len() == 1 => {
+            let e = errors.remove(0);
+            (e.field, e.code)
+        }
+        other => panic!("expected a field error, got {:?}", other),
+    }
+}
@@ -0,0 +1,166 @@
+//! Loading configuration from a TOML file overridden by the environment,
+//! and reporting every problem at once.
+
+use std::ffi::OsString;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use noteflow_backend::config::{Config, RateLimitBackendKind};
+
+/// The environment is process-wide, so tests touching it take turns
+static ENV: Mutex<()> = Mutex::new(());
+
+const BASE: &str = r#"
+database_url = "postgres://localhost/noteflow"
+redis_url = "redis://localhost"
+jwt_secret = "config-test-secret-0123456789abcdef"
+"#;
+
+/// Load `BASE` plus `extra` from a temporary file, with `env` as the only
+/// environment variables
+///
+/// The rest of the environment is cleared for the load and restored after, so
+/// neither the caller's shell nor other tests can leak settings in.
+fn load(name: &str, extra: &str, env: &[(&str, &str)]) -> Result<Config, Vec<String>> {
+    let _guard = ENV.lock().unwrap_or_else(|e| e.into_inner());
+
+    let path: PathBuf = std::env::temp_dir().join(format!(
+        "noteflow-config-test-{}-{}.toml",
+        name,
+        std::process::id()
+    ));
+    std::fs::write(&path, format!("{}\n{}", BASE, extra)).unwrap();
+
+    let saved: Vec<(OsString, OsString)> = std::env::vars_os().collect();
+    for (key, _) in &saved {
+        std::env::remove_var(key);
+    }
+    for (key, value) in env {
+        std::env::set_var(key, value);
+    }
+    let result = Config::load(Some(&path)).map_err(|e| e.0);
+    for (key, _) in env {
+        std::env::remove_var(key);
+    }
+    for (key, value) in saved {
+        std::env::set_var(key, value);
+    }
+    std::fs::remove_file(&path).ok();
+
+    result
+}
+
+#[test]
+fn file_values_override_defaults() {
+    let config = load(
+        "file",
+        r#"
+        port = 9000
+
+        [rate_limit]
+        login = 3
+        backend = "gcra"
+        "#,
+        &[],
+    )
+    .unwrap();
+
+    assert_eq!(config.port, 9000);
+    assert_eq!(config.rate_limit_login, 3);
+    assert_eq!(config.rate_limit_backend, RateLimitBackendKind::Gcra);
+    // Untouched settings keep their defaults
+    assert_eq!(config.rate_limit_register, 5);
+}
+
+#[test]
+fn environment_overrides_the_file() {
+    let config = load(
+        "env",
+        r#"
+        port = 9000
+        rate_limit_login = 3
+        "#,
+        &[("PORT", "9100"), ("RATE_LIMIT_LOGIN", "")],
+    )
+    .unwrap();
+
+    assert_eq!(config.port, 9100);
+    // An empty variable counts as unset, so the file still applies
+    assert_eq!(config.rate_limit_login, 3);
+}
+
+#[test]
+fn unknown_file_keys_are_rejected() {
+    let errors = load(
+        "unknown",
+        r#"
+        prot = 9000
+
+        [rate_limit]
+        logins = 3
+        "#,
+        &[],
+    )
+    .unwrap_err();
+
+    assert_eq!(
+        errors,
+        [
+            "unknown setting 'prot' in config file",
+            "unknown setting 'rate_limit_logins' in config file",
+        ]
+    );
+}
+
+#[test]
+fn every_invalid_setting_is_reported_together() {
+    let errors = load(
+        "invalid",
+        r#"
+        port = "eighty"
+        cache_ttl = 0
+
+        [rate_limit]
+        backend = "memcached"
+        "#,
+        &[("JWT_SECRET", "too-short"), ("RATE_LIMIT_WRITE_COST", "0")],
+    )
+    .unwrap_err();
+
+    assert_eq!(errors.len(), 5, "{:#?}", errors);
+    assert!(errors[0].starts_with("PORT: invalid value 'eighty'"));
+    assert!(errors[1].starts_with("RATE_LIMIT_BACKEND: invalid value 'memcached'"));
+    assert!(errors.iter().any(|e| e.starts_with("CACHE_TTL must be")));
+    assert!(errors.iter().any(|e| e.starts_with("JWT_SECRET must be")));
+    assert!(errors.iter().any(|e| e.starts_with("RATE_LIMIT_WRITE_COST must be")));
+}
+
+#[test]
+fn collaborator_limit_is_loaded_but_not_bounded() {
+    assert_eq!(load("collab-default", "", &[]).unwrap().max_collaborators_per_note, 10);
+
+    let config = load(
+        "collab-env",
+        "max_collaborators_per_note = 4",
+        &[("MAX_COLLABORATORS_PER_NOTE", "500")],
+    )
+    .unwrap();
+    assert_eq!(config.max_collaborators_per_note, 500);
+}
+
+#[test]
+fn the_callers_environment_does_not_leak_in() {
+    // Set outside `load`, as a shell or another test might
+    {
+        let _guard = ENV.lock().unwrap_or_else(|e| e.into_inner());
+        std::env::set_var("MAX_NOTE_SIZE", "0");
+    }
+    let result = load("isolated", "", &[]);
+    {
+        let _guard = ENV.lock().unwrap_or_else(|e| e.into_inner());
+        assert_eq!(std::env::var("MAX_NOTE_SIZE").as_deref(), Ok("0"));
+        std::env::remove_var("MAX_NOTE_SIZE");
+    }
+
+    assert_eq!(result.unwrap().max_note_size, 102400);
+}
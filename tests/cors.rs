@@ -0,0 +1,176 @@
+//! Origin allowlist patterns and the CORS settings built from them.
+
+use axum::{
+    body::Body,
+    http::{header, Request},
+    routing::get,
+    Router,
+};
+use tower::ServiceExt;
+
+use noteflow_backend::{
+    config::Config,
+    middleware::cors::{self, OriginPattern},
+};
+
+fn pattern(s: &str) -> OriginPattern {
+    s.parse().unwrap()
+}
+
+/// Load a config with `extra` settings from a temporary file
+fn config(name: &str, extra: &str) -> Result<Config, Vec<String>> {
+    let path = std::env::temp_dir().join(format!(
+        "noteflow-cors-test-{}-{}.toml",
+        name,
+        std::process::id()
+    ));
+    std::fs::write(
+        &path,
+        format!(
+            r#"
+            database_url = "postgres://localhost/noteflow"
+            redis_url = "redis://localhost"
+            jwt_secret = "cors-test-secret-0123456789abcdef"
+            {}
+            "#,
+            extra
+        ),
+    )
+    .unwrap();
+    let result = Config::load(Some(&path)).map_err(|e| e.0);
+    std::fs::remove_file(&path).ok();
+    result
+}
+
+#[test]
+fn wildcards_match_subdomains_but_not_the_apex() {
+    let subdomains = pattern("https://*.example.com");
+
+    assert!(subdomains.matches("https://app.example.com"));
+    assert!(subdomains.matches("https://a.b.example.com"));
+    assert!(subdomains.matches("https://pr-42.example.com"));
+    assert!(!subdomains.matches("https://example.com"));
+    assert!(!subdomains.matches("https://.example.com"));
+}
+
+#[test]
+fn lookalike_hosts_do_not_match() {
+    let subdomains = pattern("https://*.example.com");
+    let exact = pattern("https://example.com");
+
+    for origin in [
+        "https://evil-example.com",
+        "https://evilexample.com",
+        "https://example.com.evil.com",
+        "https://app.example.com.evil.com",
+        "https://evil.com/.example.com",
+        "https://user@app.example.com",
+    ] {
+        assert!(!subdomains.matches(origin), "{}", origin);
+        assert!(!exact.matches(origin), "{}", origin);
+    }
+}
+
+#[test]
+fn schemes_and_ports_must_match() {
+    let subdomains = pattern("https://*.example.com");
+    assert!(!subdomains.matches("http://app.example.com"));
+    assert!(!subdomains.matches("https://app.example.com:8443"));
+
+    let with_port = pattern("https://*.example.com:8443");
+    assert!(with_port.matches("https://app.example.com:8443"));
+    assert!(!with_port.matches("https://app.example.com"));
+    assert!(!with_port.matches("https://app.example.com:9443"));
+
+    let exact = pattern("http://localhost:3000");
+    assert!(exact.matches("http://localhost:3000"));
+    assert!(!exact.matches("https://localhost:3000"));
+    assert!(!exact.matches("http://localhost:3001"));
+    assert!(!exact.matches("http://localhost"));
+}
+
+#[test]
+fn hosts_compare_without_case() {
+    assert!(pattern("HTTPS://*.Example.COM").matches("https://app.example.com"));
+    assert!(pattern("https://*.example.com").matches("HTTPS://App.Example.Com"));
+    assert!(pattern("https://App.Example.com/").matches("https://app.EXAMPLE.com"));
+}
+
+#[test]
+fn malformed_patterns_are_rejected() {
+    for s in [
+        "example.com",
+        "ftp://example.com",
+        "https://",
+        "https://example.com/app",
+        "https://*.example.com/app",
+        "https://*example.com",
+        "https://app.*.example.com",
+        "https://*.*.example.com",
+        "https://app.example.*",
+        "https://*",
+    ] {
+        assert!(s.parse::<OriginPattern>().is_err(), "{}", s);
+    }
+
+    assert_eq!(pattern("*"), OriginPattern::Any);
+    assert_eq!(pattern("https://*.example.com").to_string(), "https://*.example.com");
+}
+
+#[test]
+fn credentials_need_an_explicit_origin_list() {
+    let errors = config("any-credentials", "[cors]\nallow_credentials = true").unwrap_err();
+    assert_eq!(
+        errors,
+        ["CORS_ALLOW_CREDENTIALS requires an explicit CORS_ALLOWED_ORIGINS list, not '*'"]
+    );
+
+    let errors = config(
+        "listed-any-credentials",
+        "[cors]\nallowed_origins = [\"https://app.example.com\", \"*\"]\nallow_credentials = true",
+    )
+    .unwrap_err();
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+
+    let config = config(
+        "listed-credentials",
+        "[cors]\nallowed_origins = [\"https://*.example.com\"]\nallow_credentials = true",
+    )
+    .unwrap();
+    assert_eq!(config.cors_allowed_origins, [pattern("https://*.example.com")]);
+}
+
+#[tokio::test]
+async fn only_listed_origins_are_echoed_back() {
+    let config = config(
+        "layer",
+        "[cors]\nallowed_origins = [\"https://*.example.com\"]\nallow_credentials = true",
+    )
+    .unwrap();
+    let app = Router::new()
+        .route("/", get(|| async { "ok" }))
+        .layer(cors::authenticated(&config));
+
+    let allowed = |origin: &'static str| {
+        let app = app.clone();
+        async move {
+            let request = Request::builder()
+                .uri("/")
+                .header(header::ORIGIN, origin)
+                .body(Body::empty())
+                .unwrap();
+            let response = app.oneshot(request).await.unwrap();
+            response
+                .headers()
+                .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+                .map(|v| v.to_str().unwrap().to_string())
+        }
+    };
+
+    assert_eq!(
+        allowed("https://app.example.com").await.as_deref(),
+        Some("https://app.example.com")
+    );
+    assert_eq!(allowed("https://example.com.evil.com").await, None);
+    assert_eq!(allowed("https://example.com").await, None);
+}
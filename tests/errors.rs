@@ -0,0 +1,105 @@
+//! Requests the extractors reject still get `application/problem+json` bodies.
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{header, Method, Request, StatusCode},
+    Extension, Router,
+};
+use sqlx::postgres::PgPoolOptions;
+use std::sync::Arc;
+use tower::ServiceExt;
+use uuid::Uuid;
+
+use noteflow_backend::{config::Config, models::AuthUser, routes, services::NoteService};
+
+const METHODS: [&str; 5] = ["get", "post", "put", "patch", "delete"];
+
+/// The note routes as a signed-in user; the pool connects lazily and requests
+/// here are rejected before they touch it
+fn app() -> Router {
+    let config_path =
+        std::env::temp_dir().join(format!("noteflow-errors-test-{}.toml", std::process::id()));
+    std::fs::write(
+        &config_path,
+        r#"
+        database_url = "postgres://localhost/noteflow"
+        redis_url = "redis://localhost"
+        jwt_secret = "errors-test-secret-0123456789abcdef"
+        "#,
+    )
+    .unwrap();
+    let config = Config::load(Some(&config_path)).unwrap();
+    std::fs::remove_file(&config_path).ok();
+
+    let pool = PgPoolOptions::new()
+        .connect_lazy(&config.database_url)
+        .unwrap();
+
+    Router::from(routes::notes())
+        .with_state(Arc::new(NoteService::new(pool, config)))
+        .layer(Extension(AuthUser {
+            id: Uuid::new_v4(),
+            email: "test@example.com".to_string(),
+            display_name: "Test".to_string(),
+        }))
+}
+
+/// Status, content type and body of `method path`
+async fn send(app: &Router, method: &str, path: &str) -> (StatusCode, String, serde_json::Value) {
+    let request = Request::builder()
+        .method(Method::from_bytes(method.to_uppercase().as_bytes()).unwrap())
+        .uri(path)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from("{}"))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+
+    let status = response.status();
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .map(|v| v.to_str().unwrap().to_string())
+        .unwrap_or_default();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, content_type, serde_json::from_slice(&body).unwrap_or_default())
+}
+
+#[tokio::test]
+async fn malformed_note_ids_are_problems() {
+    let (status, content_type, body) = send(&app(), "get", "/api/v1/notes/not-a-uuid").await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(content_type, "application/problem+json");
+    assert_eq!(body["code"], "bad_request");
+    assert_eq!(body["status"], 400);
+    assert!(body["detail"].as_str().unwrap().contains("UUID"), "{}", body);
+}
+
+#[tokio::test]
+async fn every_id_in_a_path_is_checked_the_same_way() {
+    let app = app();
+    let spec = serde_json::to_value(routes::openapi()).unwrap();
+
+    for (path, item) in spec["paths"].as_object().unwrap() {
+        if !path.contains('{') {
+            continue;
+        }
+        let malformed: Vec<&str> = path
+            .split('/')
+            .map(|segment| if segment.starts_with('{') { "not-a-uuid" } else { segment })
+            .collect();
+        let malformed = malformed.join("/");
+
+        for method in METHODS.iter().filter(|m| item.get(**m).is_some()) {
+            let (status, content_type, body) = send(&app, method, &malformed).await;
+            assert_eq!(
+                (status, content_type.as_str()),
+                (StatusCode::BAD_REQUEST, "application/problem+json"),
+                "{} {}",
+                method.to_uppercase(),
+                malformed
+            );
+            assert_eq!(body["code"], "bad_request", "{} {}", method.to_uppercase(), malformed);
+        }
+    }
+}
@@ -0,0 +1,110 @@
+//! Note exports streamed as ZIP archives.
+
+use chrono::{TimeZone, Utc};
+use futures::TryStreamExt;
+use std::io::{self, Cursor, Read};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use noteflow_backend::{
+    models::{note::ExportFormat, NoteResponse},
+    services::note_export::{send_zip, zip_stream},
+};
+
+fn note(id: &str, title: &str, content: &str, tags: &[&str]) -> NoteResponse {
+    let at = Utc.with_ymd_and_hms(2026, 10, 18, 12, 0, 0).unwrap();
+    NoteResponse {
+        id: Uuid::parse_str(id).unwrap(),
+        title: title.to_string(),
+        content: content.to_string(),
+        last_edited_by: None,
+        notebook_id: None,
+        pinned: false,
+        archived: false,
+        favorite: false,
+        created_at: at,
+        updated_at: at,
+        tags: tags.iter().map(|tag| tag.to_string()).collect(),
+        html: None,
+    }
+}
+
+async fn archive(notes: Vec<NoteResponse>, format: ExportFormat) -> zip::ZipArchive<Cursor<Vec<u8>>> {
+    let chunks: Vec<_> = zip_stream(notes, format).try_collect().await.unwrap();
+    let bytes = chunks.concat();
+    zip::ZipArchive::new(Cursor::new(bytes)).unwrap()
+}
+
+fn read(archive: &mut zip::ZipArchive<Cursor<Vec<u8>>>, name: &str) -> String {
+    let mut content = String::new();
+    archive.by_name(name).unwrap().read_to_string(&mut content).unwrap();
+    content
+}
+
+#[tokio::test]
+async fn archives_hold_one_readable_file_per_note() {
+    let notes = vec![
+        note("1a2b3c4d-0000-4000-8000-000000000001", "Weekly plan", "# Plan\n\n- [ ] ship", &[]),
+        // Same title as the first; the ID keeps the names apart
+        note("5e6f7a8b-0000-4000-8000-000000000002", "Weekly plan", "Second", &[]),
+        // Nothing usable in the title
+        note("9c0d1e2f-0000-4000-8000-000000000003", "?!/", "Untitled", &[]),
+        note("deadbeef-0000-4000-8000-000000000004", "Ünïcode: «Straße»", &"x".repeat(100_000), &[]),
+    ];
+    let mut archive = archive(notes, ExportFormat::Markdown).await;
+
+    let mut names: Vec<&str> = archive.file_names().collect();
+    names.sort();
+    assert_eq!(
+        names,
+        [
+            "note-9c0d1e2f.md",
+            "weekly-plan-1a2b3c4d.md",
+            "weekly-plan-5e6f7a8b.md",
+            "ünïcode-straße-deadbeef.md",
+        ]
+    );
+
+    assert!(read(&mut archive, "weekly-plan-1a2b3c4d.md").ends_with("# Plan\n\n- [ ] ship\n"));
+    assert!(read(&mut archive, "weekly-plan-5e6f7a8b.md").ends_with("Second\n"));
+    // Large notes span several writes and still come back whole
+    assert!(read(&mut archive, "ünïcode-straße-deadbeef.md").contains(&"x".repeat(100_000)));
+}
+
+#[tokio::test]
+async fn markdown_front_matter_escapes_titles_and_tags() {
+    let notes = vec![note(
+        "1a2b3c4d-0000-4000-8000-000000000001",
+        r#"Say "hi" \ bye"#,
+        "Body",
+        &["work", r#"a "b""#],
+    )];
+    let mut archive = archive(notes, ExportFormat::Markdown).await;
+
+    assert_eq!(
+        read(&mut archive, "say-hi-bye-1a2b3c4d.md"),
+        concat!(
+            "---\n",
+            r#"title: "Say \"hi\" \\ bye""#,
+            "\n",
+            r#"tags: ["work", "a \"b\""]"#,
+            "\n",
+            "created: 2026-10-18T12:00:00+00:00\n",
+            "updated: 2026-10-18T12:00:00+00:00\n",
+            "---\n\nBody\n",
+        )
+    );
+}
+
+#[test]
+fn dropped_downloads_stop_the_writer() {
+    let (tx, rx) = mpsc::channel(1);
+    drop(rx);
+    let notes = vec![
+        note("1a2b3c4d-0000-4000-8000-000000000001", "a", "one", &[]),
+        note("5e6f7a8b-0000-4000-8000-000000000002", "b", "two", &[]),
+    ];
+
+    let error = send_zip(&tx, &notes, ExportFormat::Text).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::BrokenPipe);
+}
@@ -0,0 +1,187 @@
+//! The Redis read-through cache for notes: per-user keys and invalidation by
+//! generation.
+//!
+//! These need a Redis server, so they are ignored by default. Run them with
+//! `REDIS_URL=redis://127.0.0.1 cargo test --test note_cache -- --ignored`.
+
+use chrono::Utc;
+use uuid::Uuid;
+
+use noteflow_backend::{
+    db::{create_redis_client, RedisManager},
+    models::{NoteListItem, NoteListResponse, NoteResponse},
+    services::{note_cache::Lookup, NoteCache},
+};
+
+const TTL_SECS: u64 = 60;
+
+async fn redis() -> RedisManager {
+    let url = std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1".to_string());
+    RedisManager::new(create_redis_client(&url).await.unwrap())
+}
+
+async fn cache() -> NoteCache {
+    NoteCache::new(redis().await, TTL_SECS)
+}
+
+fn note(id: Uuid, title: &str) -> NoteResponse {
+    NoteResponse {
+        id,
+        title: title.to_string(),
+        content: String::new(),
+        last_edited_by: None,
+        notebook_id: None,
+        pinned: false,
+        archived: false,
+        favorite: false,
+        created_at: Utc::now(),
+        updated_at: Utc::now(),
+        tags: Vec::new(),
+        html: None,
+    }
+}
+
+fn list(ids: &[Uuid]) -> NoteListResponse {
+    NoteListResponse {
+        notes: ids
+            .iter()
+            .map(|id| NoteListItem {
+                id: *id,
+                ..Default::default()
+            })
+            .collect(),
+        total: None,
+        next_cursor: None,
+    }
+}
+
+/// Title of a cached note, or `None` on a miss
+async fn cached_title(cache: &NoteCache, user_id: Uuid, note_id: Uuid) -> Option<String> {
+    match cache.get_note(user_id, note_id).await {
+        Lookup::Hit(note) => Some(note.title),
+        Lookup::Miss(_) => None,
+    }
+}
+
+async fn cache_note(cache: &NoteCache, user_id: Uuid, note_id: Uuid, title: &str) {
+    match cache.get_note(user_id, note_id).await {
+        Lookup::Miss(slot) => cache.put_note(slot, &note(note_id, title)).await,
+        Lookup::Hit(_) => panic!("note {} was already cached", note_id),
+    }
+}
+
+async fn list_is_cached(cache: &NoteCache, user_id: Uuid, page_key: &str) -> bool {
+    matches!(cache.get_list(user_id, page_key).await, Lookup::Hit(_))
+}
+
+async fn cache_list(cache: &NoteCache, user_id: Uuid, page_key: &str, ids: &[Uuid]) {
+    match cache.get_list(user_id, page_key).await {
+        Lookup::Miss(slot) => cache.put_list(slot, &list(ids)).await,
+        Lookup::Hit(_) => panic!("list page {} was already cached", page_key),
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs a Redis server at REDIS_URL"]
+async fn notes_are_cached_per_user() {
+    let cache = cache().await;
+    let (owner, other) = (Uuid::new_v4(), Uuid::new_v4());
+    let note_id = Uuid::new_v4();
+
+    assert_eq!(cached_title(&cache, owner, note_id).await, None);
+    cache_note(&cache, owner, note_id, "Plan").await;
+
+    assert_eq!(cached_title(&cache, owner, note_id).await.as_deref(), Some("Plan"));
+    // A hit never needs an ownership check, because nobody else can get one
+    assert_eq!(cached_title(&cache, other, note_id).await, None);
+}
+
+#[tokio::test]
+#[ignore = "needs a Redis server at REDIS_URL"]
+async fn invalidating_a_note_drops_it_and_its_owners_lists() {
+    let cache = cache().await;
+    let (owner, other) = (Uuid::new_v4(), Uuid::new_v4());
+    let (changed, untouched) = (Uuid::new_v4(), Uuid::new_v4());
+    cache_note(&cache, owner, changed, "Changed").await;
+    cache_note(&cache, owner, untouched, "Untouched").await;
+    cache_list(&cache, owner, "page=1", &[changed, untouched]).await;
+    cache_list(&cache, other, "page=1", &[]).await;
+
+    cache.invalidate_note(owner, changed).await;
+
+    assert_eq!(cached_title(&cache, owner, changed).await, None);
+    assert!(!list_is_cached(&cache, owner, "page=1").await);
+    assert_eq!(
+        cached_title(&cache, owner, untouched).await.as_deref(),
+        Some("Untouched")
+    );
+    assert!(list_is_cached(&cache, other, "page=1").await);
+
+    // Cached again under the new generation
+    cache_note(&cache, owner, changed, "Changed again").await;
+    assert_eq!(
+        cached_title(&cache, owner, changed).await.as_deref(),
+        Some("Changed again")
+    );
+}
+
+#[tokio::test]
+#[ignore = "needs a Redis server at REDIS_URL"]
+async fn invalidating_lists_keeps_the_notes() {
+    let cache = cache().await;
+    let owner = Uuid::new_v4();
+    let note_id = Uuid::new_v4();
+    cache_note(&cache, owner, note_id, "Plan").await;
+    cache_list(&cache, owner, "page=1", &[note_id]).await;
+    cache_list(&cache, owner, "page=2", &[]).await;
+
+    cache.invalidate_lists(owner).await;
+
+    assert!(!list_is_cached(&cache, owner, "page=1").await);
+    assert!(!list_is_cached(&cache, owner, "page=2").await);
+    assert_eq!(cached_title(&cache, owner, note_id).await.as_deref(), Some("Plan"));
+}
+
+#[tokio::test]
+#[ignore = "needs a Redis server at REDIS_URL"]
+async fn values_loaded_before_an_invalidation_are_never_served() {
+    let cache = cache().await;
+    let owner = Uuid::new_v4();
+    let note_id = Uuid::new_v4();
+
+    // A read misses and loads the note, then an update lands before it stores it
+    let Lookup::Miss(slot) = cache.get_note(owner, note_id).await else {
+        panic!("note was already cached");
+    };
+    cache.invalidate_note(owner, note_id).await;
+    cache.put_note(slot, &note(note_id, "Stale")).await;
+
+    assert_eq!(cached_title(&cache, owner, note_id).await, None);
+}
+
+#[tokio::test]
+#[ignore = "needs a Redis server at REDIS_URL"]
+async fn generations_outlive_the_entries_written_under_them() {
+    let cache = cache().await;
+    let owner = Uuid::new_v4();
+    let note_id = Uuid::new_v4();
+    cache.invalidate_note(owner, note_id).await;
+
+    let mut redis = redis().await;
+    for key in [
+        format!("cache:gen:note:{}:{}", owner, note_id),
+        format!("cache:gen:notes:{}", owner),
+    ] {
+        let ttl: i64 = redis::cmd("TTL")
+            .arg(&key)
+            .query_async(&mut redis.conn)
+            .await
+            .unwrap();
+        assert!(
+            ttl > TTL_SECS as i64 && ttl <= 2 * TTL_SECS as i64,
+            "{} expires in {}s",
+            key,
+            ttl
+        );
+    }
+}
@@ -0,0 +1,129 @@
+//! Notebook nesting and ordering against a real database.
+
+use sqlx::PgPool;
+use uuid::Uuid;
+
+mod common;
+
+use common::{field_error, service, user};
+use noteflow_backend::{
+    models::{CreateNotebookRequest, MoveNotebookRequest, UpdateNotebookRequest},
+    services::NoteService,
+};
+
+async fn notebook(service: &NoteService, user_id: Uuid, name: &str, parent_id: Option<Uuid>) -> Uuid {
+    service
+        .create_notebook(
+            user_id,
+            CreateNotebookRequest {
+                name: name.to_string(),
+                parent_id,
+            },
+        )
+        .await
+        .unwrap()
+        .id
+}
+
+async fn move_to(
+    service: &NoteService,
+    user_id: Uuid,
+    notebook_id: Uuid,
+    parent_id: Option<Uuid>,
+    position: Option<i32>,
+) -> noteflow_backend::utils::errors::Result<()> {
+    service
+        .move_notebook(notebook_id, user_id, MoveNotebookRequest { parent_id, position })
+        .await
+        .map(|_| ())
+}
+
+/// Names in listing order with their positions
+async fn tree(service: &NoteService, user_id: Uuid) -> Vec<(String, i32)> {
+    service
+        .list_notebooks(user_id)
+        .await
+        .unwrap()
+        .notebooks
+        .into_iter()
+        .map(|nb| (nb.name, nb.position))
+        .collect()
+}
+
+fn expected(entries: &[(&str, i32)]) -> Vec<(String, i32)> {
+    entries.iter().map(|(name, pos)| (name.to_string(), *pos)).collect()
+}
+
+#[sqlx::test]
+async fn notebooks_cannot_move_into_themselves_or_their_descendants(pool: PgPool) {
+    let service = service(&pool);
+    let user_id = user(&pool).await;
+    let a = notebook(&service, user_id, "a", None).await;
+    let b = notebook(&service, user_id, "b", Some(a)).await;
+    let c = notebook(&service, user_id, "c", Some(b)).await;
+
+    for parent in [a, b, c] {
+        let error = move_to(&service, user_id, a, Some(parent), None).await.unwrap_err();
+        assert_eq!(field_error(error), ("parent_id".to_string(), "cycle".to_string()));
+    }
+
+    // Deeper within its own subtree, or back to the top, is fine
+    move_to(&service, user_id, c, Some(a), None).await.unwrap();
+    move_to(&service, user_id, b, None, None).await.unwrap();
+    assert_eq!(
+        tree(&service, user_id).await,
+        expected(&[("a", 0), ("c", 0), ("b", 1)])
+    );
+}
+
+#[sqlx::test]
+async fn notebooks_list_depth_first_in_position_order(pool: PgPool) {
+    let service = service(&pool);
+    let user_id = user(&pool).await;
+    let a = notebook(&service, user_id, "a", None).await;
+    let b = notebook(&service, user_id, "b", None).await;
+    notebook(&service, user_id, "a1", Some(a)).await;
+    let a2 = notebook(&service, user_id, "a2", Some(a)).await;
+
+    assert_eq!(
+        tree(&service, user_id).await,
+        expected(&[("a", 0), ("a1", 0), ("a2", 1), ("b", 1)])
+    );
+
+    // Reordering among siblings renumbers them
+    move_to(&service, user_id, b, None, Some(0)).await.unwrap();
+    move_to(&service, user_id, a2, Some(a), Some(0)).await.unwrap();
+    assert_eq!(
+        tree(&service, user_id).await,
+        expected(&[("b", 0), ("a", 1), ("a2", 0), ("a1", 1)])
+    );
+
+    // Moving out closes the gap it leaves; positions past the end append
+    move_to(&service, user_id, a2, None, Some(1)).await.unwrap();
+    move_to(&service, user_id, b, Some(a), Some(99)).await.unwrap();
+    assert_eq!(
+        tree(&service, user_id).await,
+        expected(&[("a2", 0), ("a", 1), ("a1", 0), ("b", 1)])
+    );
+}
+
+#[sqlx::test]
+async fn renaming_keeps_the_notebook_in_place(pool: PgPool) {
+    let service = service(&pool);
+    let user_id = user(&pool).await;
+    let a = notebook(&service, user_id, "a", None).await;
+    notebook(&service, user_id, "b", None).await;
+
+    let renamed = service
+        .rename_notebook(a, user_id, UpdateNotebookRequest { name: "  z  ".to_string() })
+        .await
+        .unwrap();
+    assert_eq!(renamed.name, "z");
+    assert_eq!(tree(&service, user_id).await, expected(&[("z", 0), ("b", 1)]));
+
+    let stranger = user(&pool).await;
+    assert!(service
+        .rename_notebook(a, stranger, UpdateNotebookRequest { name: "x".to_string() })
+        .await
+        .is_err());
+}
@@ -0,0 +1,647 @@
+//! Note listing, sorting, paging, states and bulk changes against a real database.
+//!
+//! Each `#[sqlx::test]` gets a fresh, migrated database created through
+//! `DATABASE_URL`.
+
+use chrono::Utc;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+mod common;
+
+use common::{field_error, service, user};
+use noteflow_backend::{
+    models::{
+        BulkNoteAction, BulkNoteRequest, BulkNoteStateRequest, CreateNoteRequest, CursorKey,
+        MoveNoteRequest, NoteCursor, NoteQueryParams, NoteSort, NoteStateRequest, NoteView,
+        SortOrder, UpdateNoteRequest,
+    },
+    services::NoteService,
+    utils::{
+        errors::{AppError, ErrorCode},
+        text,
+    },
+};
+use validator::Validate;
+
+async fn note(service: &NoteService, user_id: Uuid, title: &str) -> Uuid {
+    service
+        .create(
+            user_id,
+            CreateNoteRequest {
+                title: title.to_string(),
+                content: Some(format!("About {}", title)),
+                notebook_id: None,
+            },
+        )
+        .await
+        .unwrap()
+        .id
+}
+
+fn by_title(cursor: Option<String>, limit: i64) -> NoteQueryParams {
+    NoteQueryParams {
+        cursor,
+        limit: Some(limit),
+        sort: Some(NoteSort::Title),
+        ..Default::default()
+    }
+}
+
+/// Live notes' titles and positions, in position order
+async fn positions(pool: &PgPool, user_id: Uuid) -> Vec<(String, i32)> {
+    sqlx::query_as(
+        "SELECT title, position FROM notes WHERE user_id = $1 AND is_deleted = false ORDER BY position, title",
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await
+    .unwrap()
+}
+
+fn bulk(note_ids: Vec<Uuid>, action: BulkNoteAction) -> BulkNoteRequest {
+    BulkNoteRequest { note_ids, action }
+}
+
+/// Titles of every page, following `next_cursor` to the end
+async fn titles_by_page(service: &NoteService, user_id: Uuid, limit: i64) -> Vec<Vec<String>> {
+    let mut pages = Vec::new();
+    let mut cursor = None;
+    loop {
+        let page = service.list(user_id, by_title(cursor, limit)).await.unwrap();
+        pages.push(page.notes.into_iter().map(|n| n.title.unwrap()).collect());
+        match page.next_cursor {
+            Some(next) => cursor = Some(next),
+            None => return pages,
+        }
+    }
+}
+
+#[test]
+fn cursors_round_trip() {
+    for key in [
+        CursorKey::Time(Utc::now()),
+        CursorKey::Text("naïve café".to_string()),
+        CursorKey::Number(-3),
+    ] {
+        let cursor = NoteCursor {
+            sort: NoteSort::Title,
+            order: SortOrder::Desc,
+            pinned: true,
+            key,
+            id: Uuid::new_v4(),
+        };
+        let encoded = cursor.encode();
+        assert!(encoded
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+
+        let decoded = NoteCursor::decode(&encoded).unwrap();
+        assert_eq!(decoded.sort, cursor.sort);
+        assert_eq!(decoded.order, cursor.order);
+        assert_eq!(decoded.pinned, cursor.pinned);
+        assert_eq!(decoded.id, cursor.id);
+        assert_eq!(
+            serde_json::to_value(&decoded.key).unwrap(),
+            serde_json::to_value(&cursor.key).unwrap()
+        );
+    }
+}
+
+#[test]
+fn malformed_cursors_do_not_decode() {
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+
+    let valid = NoteCursor {
+        sort: NoteSort::UpdatedAt,
+        order: SortOrder::Desc,
+        pinned: false,
+        key: CursorKey::Time(Utc::now()),
+        id: Uuid::new_v4(),
+    }
+    .encode();
+
+    assert!(NoteCursor::decode("not a cursor!").is_none());
+    assert!(NoteCursor::decode(&valid[..valid.len() / 2]).is_none());
+    assert!(NoteCursor::decode(&URL_SAFE_NO_PAD.encode("{}")).is_none());
+    assert!(NoteCursor::decode(&URL_SAFE_NO_PAD.encode(
+        r#"{"sort":"random","order":"asc","key":{"number":1},"id":"00000000-0000-0000-0000-000000000000"}"#
+    ))
+    .is_none());
+}
+
+#[sqlx::test]
+async fn tampered_cursors_are_rejected(pool: PgPool) {
+    let service = service(&pool);
+    let user_id = user(&pool).await;
+    note(&service, user_id, "a").await;
+
+    let garbage = service
+        .list(user_id, by_title(Some("garbage".to_string()), 10))
+        .await
+        .unwrap_err();
+    assert_eq!(field_error(garbage), ("cursor".to_string(), "invalid".to_string()));
+
+    // Decodes, but its key can't be a title
+    let wrong_key = NoteCursor {
+        sort: NoteSort::Title,
+        order: SortOrder::Asc,
+        pinned: false,
+        key: CursorKey::Number(7),
+        id: Uuid::new_v4(),
+    };
+    let error = service
+        .list(user_id, by_title(Some(wrong_key.encode()), 10))
+        .await
+        .unwrap_err();
+    assert_eq!(field_error(error), ("cursor".to_string(), "invalid".to_string()));
+
+    // Issued for another ordering
+    let other_sort = NoteCursor {
+        sort: NoteSort::UpdatedAt,
+        order: SortOrder::Desc,
+        pinned: false,
+        key: CursorKey::Time(Utc::now()),
+        id: Uuid::new_v4(),
+    };
+    let error = service
+        .list(user_id, by_title(Some(other_sort.encode()), 10))
+        .await
+        .unwrap_err();
+    assert_eq!(field_error(error), ("cursor".to_string(), "mismatch".to_string()));
+}
+
+#[sqlx::test]
+async fn notes_sort_by_each_key_in_either_direction(pool: PgPool) {
+    let service = service(&pool);
+    let user_id = user(&pool).await;
+    let b = note(&service, user_id, "b").await;
+    note(&service, user_id, "A").await;
+    let c = note(&service, user_id, "c").await;
+    service
+        .move_note(c, user_id, MoveNoteRequest { notebook_id: None, position: Some(0) })
+        .await
+        .unwrap();
+    service
+        .update(
+            b,
+            user_id,
+            UpdateNoteRequest {
+                title: None,
+                content: Some("Edited".to_string()),
+            },
+        )
+        .await
+        .unwrap();
+
+    for (sort, ascending) in [
+        (NoteSort::CreatedAt, ["b", "A", "c"]),
+        (NoteSort::UpdatedAt, ["A", "c", "b"]),
+        // Case-insensitive
+        (NoteSort::Title, ["A", "b", "c"]),
+        (NoteSort::Position, ["c", "b", "A"]),
+    ] {
+        let mut descending = ascending;
+        descending.reverse();
+        for (order, expected) in [(SortOrder::Asc, ascending), (SortOrder::Desc, descending)] {
+            let titles: Vec<String> = service
+                .list(
+                    user_id,
+                    NoteQueryParams {
+                        sort: Some(sort),
+                        order: Some(order),
+                        ..Default::default()
+                    },
+                )
+                .await
+                .unwrap()
+                .notes
+                .into_iter()
+                .map(|n| n.title.unwrap())
+                .collect();
+            assert_eq!(titles, expected, "{:?} {:?}", sort, order);
+        }
+    }
+
+    // Without an order, dates list newest first and titles alphabetically
+    let first = |sort| {
+        let service = &service;
+        async move {
+            let params = NoteQueryParams {
+                sort: Some(sort),
+                ..Default::default()
+            };
+            service.list(user_id, params).await.unwrap().notes[0].title.clone().unwrap()
+        }
+    };
+    assert_eq!(first(NoteSort::UpdatedAt).await, "b");
+    assert_eq!(first(NoteSort::CreatedAt).await, "c");
+    assert_eq!(first(NoteSort::Title).await, "A");
+    assert_eq!(first(NoteSort::Position).await, "c");
+}
+
+#[sqlx::test]
+async fn fields_select_what_list_items_carry(pool: PgPool) {
+    let service = service(&pool);
+    let user_id = user(&pool).await;
+    note(&service, user_id, "a").await;
+    let fields = |fields: &str| NoteQueryParams {
+        fields: Some(fields.to_string()),
+        ..Default::default()
+    };
+
+    let list = service.list(user_id, fields(" title , updated_at,id,title")).await.unwrap();
+    let item = serde_json::to_value(&list.notes[0]).unwrap();
+    let mut keys: Vec<&str> = item.as_object().unwrap().keys().map(String::as_str).collect();
+    keys.sort();
+    assert_eq!(keys, ["id", "title", "updated_at"]);
+
+    let error = service.list(user_id, fields("title,bogus")).await.unwrap_err();
+    assert_eq!(field_error(error), ("fields".to_string(), "unknown".to_string()));
+
+    let error = service
+        .list(
+            user_id,
+            NoteQueryParams {
+                view: Some(NoteView::Summary),
+                ..fields("title")
+            },
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(field_error(error), ("fields".to_string(), "conflict".to_string()));
+}
+
+#[sqlx::test]
+async fn summaries_carry_an_excerpt_instead_of_the_content(pool: PgPool) {
+    let service = service(&pool);
+    let user_id = user(&pool).await;
+    let content = format!("# Heading\n\n**Bold** start, then {}", "word ".repeat(100));
+    service
+        .create(
+            user_id,
+            CreateNoteRequest {
+                title: "Long".to_string(),
+                content: Some(content),
+                notebook_id: None,
+            },
+        )
+        .await
+        .unwrap();
+
+    let list = service
+        .list(
+            user_id,
+            NoteQueryParams {
+                view: Some(NoteView::Summary),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+    let item = &list.notes[0];
+    assert_eq!(item.title.as_deref(), Some("Long"));
+    assert_eq!(item.content, None);
+    assert_eq!(item.word_count, Some(104));
+    assert_eq!(item.tags.as_deref(), Some(&[][..]));
+
+    let excerpt = item.excerpt.as_deref().unwrap();
+    assert!(excerpt.starts_with("Heading Bold start, then word"), "{}", excerpt);
+    assert!(excerpt.ends_with("word…"), "{}", excerpt);
+    assert!(excerpt.chars().count() <= 201, "{}", excerpt);
+}
+
+#[test]
+fn excerpts_cut_at_a_word_within_the_limit() {
+    assert_eq!(text::excerpt("short text", 20), "short text");
+    assert_eq!(text::excerpt("exactly ten", 11), "exactly ten");
+    assert_eq!(text::excerpt("one two three", 9), "one two…");
+
+    // Counted in characters, and never cut inside one
+    assert_eq!(text::excerpt("été à côté de l'île", 10), "été à…");
+    assert_eq!(text::excerpt("日本語のテキスト", 3), "日本語…");
+    assert_eq!(text::excerpt("🦀🦀🦀 🦀", 4), "🦀🦀🦀…");
+}
+
+#[sqlx::test]
+async fn totals_are_counted_only_when_asked_for(pool: PgPool) {
+    let service = service(&pool);
+    let user_id = user(&pool).await;
+    for title in ["a", "b", "c"] {
+        note(&service, user_id, title).await;
+    }
+    let total = |page: Option<i64>, cursor: Option<String>, include_total: Option<bool>| {
+        let service = &service;
+        async move {
+            service
+                .list(
+                    user_id,
+                    NoteQueryParams {
+                        include_total,
+                        page,
+                        ..by_title(cursor, 2)
+                    },
+                )
+                .await
+                .unwrap()
+                .total
+        }
+    };
+
+    assert_eq!(total(None, None, None).await, None);
+    assert_eq!(total(None, None, Some(false)).await, None);
+    assert_eq!(total(None, None, Some(true)).await, Some(3));
+
+    // Offset paging keeps its total unless told otherwise
+    assert_eq!(total(Some(2), None, None).await, Some(3));
+    assert_eq!(total(Some(1), None, Some(false)).await, None);
+
+    let next = service.list(user_id, by_title(None, 2)).await.unwrap().next_cursor;
+    assert_eq!(total(None, next.clone(), None).await, None);
+    assert_eq!(total(None, next, Some(true)).await, Some(3));
+}
+
+#[sqlx::test]
+async fn cursor_paging_crosses_from_pinned_to_unpinned_notes(pool: PgPool) {
+    let service = service(&pool);
+    let user_id = user(&pool).await;
+    for title in ["a", "b", "c", "d", "e"] {
+        let id = note(&service, user_id, title).await;
+        if title == "b" || title == "d" {
+            service
+                .set_state(
+                    id,
+                    user_id,
+                    NoteStateRequest {
+                        pinned: Some(true),
+                        ..Default::default()
+                    },
+                )
+                .await
+                .unwrap();
+        }
+    }
+
+    // Pinned notes first, each group by title, whatever the page size
+    assert_eq!(
+        titles_by_page(&service, user_id, 1).await,
+        [["b"], ["d"], ["a"], ["c"], ["e"]]
+    );
+    assert_eq!(
+        titles_by_page(&service, user_id, 3).await,
+        [vec!["b", "d", "a"], vec!["c", "e"]]
+    );
+    assert_eq!(
+        titles_by_page(&service, user_id, 2).await,
+        [vec!["b", "d"], vec!["a", "c"], vec!["e"]]
+    );
+}
+
+#[test]
+fn bulk_requests_take_at_most_100_notes() {
+    let ids = |n| (0..n).map(|_| Uuid::new_v4()).collect::<Vec<_>>();
+
+    assert!(bulk(ids(100), BulkNoteAction::Delete).validate().is_ok());
+    assert!(bulk(ids(101), BulkNoteAction::Delete).validate().is_err());
+    assert!(bulk(ids(0), BulkNoteAction::Delete).validate().is_err());
+    assert!(BulkNoteStateRequest {
+        note_ids: ids(101),
+        state: NoteStateRequest {
+            pinned: Some(true),
+            ..Default::default()
+        },
+    }
+    .validate()
+    .is_err());
+}
+
+#[sqlx::test]
+async fn bulk_changes_skip_and_report_other_users_notes(pool: PgPool) {
+    let service = service(&pool);
+    let user_id = user(&pool).await;
+    let stranger = user(&pool).await;
+    let mine = note(&service, user_id, "mine").await;
+    let theirs = note(&service, stranger, "theirs").await;
+    let missing = Uuid::new_v4();
+
+    let tagged = service
+        .bulk(
+            user_id,
+            bulk(
+                vec![mine, theirs, missing, mine],
+                BulkNoteAction::Tag {
+                    tags: vec!["work".to_string()],
+                },
+            ),
+        )
+        .await
+        .unwrap();
+    assert_eq!((tagged.succeeded, tagged.failed), (1, 2));
+    let outcomes: Vec<_> = tagged.results.iter().map(|r| (r.id, r.ok, r.code)).collect();
+    assert_eq!(
+        outcomes,
+        [
+            (mine, true, None),
+            (theirs, false, Some("forbidden")),
+            (missing, false, Some("note_not_found")),
+        ]
+    );
+    assert_eq!(service.get(mine, user_id).await.unwrap().tags, ["work"]);
+    assert!(service.get(theirs, stranger).await.unwrap().tags.is_empty());
+
+    let trashed = service
+        .bulk(user_id, bulk(vec![theirs], BulkNoteAction::Delete))
+        .await
+        .unwrap();
+    assert_eq!((trashed.succeeded, trashed.failed), (0, 1));
+    service.get(theirs, stranger).await.unwrap();
+
+    let pinned = service
+        .bulk_set_state(
+            user_id,
+            BulkNoteStateRequest {
+                note_ids: vec![mine, theirs],
+                state: NoteStateRequest {
+                    pinned: Some(true),
+                    archived: Some(true),
+                    ..Default::default()
+                },
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!((pinned.updated, pinned.not_found), (vec![mine], vec![theirs]));
+    let note = service.get(mine, user_id).await.unwrap();
+    assert!(note.pinned && note.archived);
+    let note = service.get(theirs, stranger).await.unwrap();
+    assert!(!note.pinned && !note.archived);
+}
+
+#[sqlx::test]
+async fn trashed_notes_come_back_after_the_live_ones(pool: PgPool) {
+    let service = service(&pool);
+    let user_id = user(&pool).await;
+    note(&service, user_id, "a").await;
+    let b = note(&service, user_id, "b").await;
+    let c = note(&service, user_id, "c").await;
+
+    let trashed = service
+        .bulk(user_id, bulk(vec![c, b], BulkNoteAction::Delete))
+        .await
+        .unwrap();
+    assert_eq!((trashed.succeeded, trashed.failed), (2, 0));
+    assert!(service.get(b, user_id).await.is_err());
+    // Trashed notes can't be tagged, as they can't be read either
+    let tagged = service
+        .bulk(
+            user_id,
+            bulk(vec![b], BulkNoteAction::Tag { tags: vec!["x".to_string()] }),
+        )
+        .await
+        .unwrap();
+    assert_eq!(tagged.results[0].code, Some("note_not_found"));
+
+    // Takes the position `b` had before it was trashed
+    note(&service, user_id, "d").await;
+    assert_eq!(
+        positions(&pool, user_id).await,
+        [("a".to_string(), 0), ("d".to_string(), 1)]
+    );
+
+    let restored = service
+        .bulk(user_id, bulk(vec![c, b], BulkNoteAction::Restore))
+        .await
+        .unwrap();
+    assert_eq!((restored.succeeded, restored.failed), (2, 0));
+    assert_eq!(service.get(b, user_id).await.unwrap().title, "b");
+    assert_eq!(
+        positions(&pool, user_id).await,
+        [
+            ("a".to_string(), 0),
+            ("d".to_string(), 1),
+            ("c".to_string(), 2),
+            ("b".to_string(), 3),
+        ]
+    );
+}
+
+#[sqlx::test]
+async fn note_states_are_set_and_filtered_on(pool: PgPool) {
+    let service = service(&pool);
+    let user_id = user(&pool).await;
+    let stranger = user(&pool).await;
+    let a = note(&service, user_id, "a").await;
+    note(&service, user_id, "b").await;
+    let theirs = note(&service, stranger, "theirs").await;
+    let state = |pinned, favorite| NoteStateRequest {
+        pinned,
+        favorite,
+        ..Default::default()
+    };
+
+    let updated = service.set_state(a, user_id, state(Some(true), Some(true))).await.unwrap();
+    assert!(updated.pinned && updated.favorite && !updated.archived);
+    // Fields left out keep their value
+    let updated = service.set_state(a, user_id, state(None, Some(false))).await.unwrap();
+    assert!(updated.pinned && !updated.favorite);
+
+    let titles = |params: NoteQueryParams| {
+        let service = &service;
+        async move {
+            let page = service.list(user_id, params).await.unwrap();
+            page.notes.into_iter().map(|n| n.title.unwrap()).collect::<Vec<_>>()
+        }
+    };
+    let filter = |pinned, favorite| NoteQueryParams {
+        pinned,
+        favorite,
+        sort: Some(NoteSort::Title),
+        ..Default::default()
+    };
+    assert_eq!(titles(filter(Some(true), None)).await, ["a"]);
+    assert_eq!(titles(filter(Some(false), None)).await, ["b"]);
+    assert!(titles(filter(None, Some(true))).await.is_empty());
+
+    let code = |result: Result<_, AppError>| result.unwrap_err().code();
+    assert_eq!(
+        code(service.set_state(theirs, user_id, state(Some(true), None)).await),
+        ErrorCode::Forbidden
+    );
+    assert_eq!(
+        code(service.set_state(Uuid::new_v4(), user_id, state(Some(true), None)).await),
+        ErrorCode::NoteNotFound
+    );
+    // Asking for no change at all is about the whole body, not one field
+    assert_eq!(
+        code(service.set_state(a, user_id, state(None, None)).await),
+        ErrorCode::ValidationFailed
+    );
+}
+
+#[sqlx::test]
+async fn bulk_state_changes_report_each_missing_note_once(pool: PgPool) {
+    let service = service(&pool);
+    let user_id = user(&pool).await;
+    let stranger = user(&pool).await;
+    let mine = note(&service, user_id, "mine").await;
+    let theirs = note(&service, stranger, "theirs").await;
+    let missing = Uuid::new_v4();
+
+    let response = service
+        .bulk_set_state(
+            user_id,
+            BulkNoteStateRequest {
+                note_ids: vec![missing, mine, theirs, missing, mine, theirs],
+                state: NoteStateRequest {
+                    archived: Some(true),
+                    ..Default::default()
+                },
+            },
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.updated, [mine]);
+    assert_eq!(response.not_found, [missing, theirs]);
+    assert!(service.get(mine, user_id).await.unwrap().archived);
+    assert!(!service.get(theirs, stranger).await.unwrap().archived);
+}
+
+#[sqlx::test]
+async fn archived_notes_are_hidden_unless_searched_for(pool: PgPool) {
+    let service = service(&pool);
+    let user_id = user(&pool).await;
+    let budget = note(&service, user_id, "Quarterly budget").await;
+    note(&service, user_id, "Team offsite").await;
+    service
+        .set_state(
+            budget,
+            user_id,
+            NoteStateRequest {
+                archived: Some(true),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+    let titles = |q: Option<&str>, archived| {
+        let service = &service;
+        let params = NoteQueryParams {
+            q: q.map(str::to_string),
+            archived,
+            sort: Some(NoteSort::Title),
+            ..Default::default()
+        };
+        async move {
+            let page = service.list(user_id, params).await.unwrap();
+            page.notes.into_iter().map(|n| n.title.unwrap()).collect::<Vec<_>>()
+        }
+    };
+
+    assert_eq!(titles(None, None).await, ["Team offsite"]);
+    assert_eq!(titles(None, Some(true)).await, ["Quarterly budget"]);
+    assert_eq!(titles(Some("budget"), None).await, ["Quarterly budget"]);
+    // An explicit filter still applies to searches
+    assert!(titles(Some("budget"), Some(false)).await.is_empty());
+}
@@ -0,0 +1,160 @@
+//! Keeps the OpenAPI document and the router in step: every documented
+//! operation must be routed, and documented paths must not serve
+//! undocumented methods.
+
+use axum::{
+    body::Body,
+    http::{Method, Request, StatusCode},
+    Router,
+};
+use sqlx::postgres::PgPoolOptions;
+use std::sync::Arc;
+use tower::ServiceExt;
+
+use noteflow_backend::{
+    config::Config,
+    routes,
+    services::{AuthService, NoteService},
+    utils::jwt::JwtManager,
+};
+
+const METHODS: [&str; 5] = ["get", "post", "put", "patch", "delete"];
+
+/// The API routers with real state; the pool connects lazily and requests
+/// here never get far enough to touch it
+fn app() -> Router {
+    let config_path =
+        std::env::temp_dir().join(format!("noteflow-openapi-test-{}.toml", std::process::id()));
+    std::fs::write(
+        &config_path,
+        r#"
+        database_url = "postgres://localhost/noteflow"
+        redis_url = "redis://localhost"
+        jwt_secret = "openapi-drift-test-secret-0123456789"
+        "#,
+    )
+    .unwrap();
+    let config = Config::load(Some(&config_path)).unwrap();
+    std::fs::remove_file(&config_path).ok();
+
+    let pool = PgPoolOptions::new()
+        .connect_lazy(&config.database_url)
+        .unwrap();
+    let jwt_manager = Arc::new(JwtManager::new(
+        config.jwt_secret.clone(),
+        config.jwt_access_expiration,
+        config.jwt_refresh_expiration,
+    ));
+
+    Router::new()
+        .merge(
+            Router::from(routes::auth())
+                .with_state(Arc::new(AuthService::new(pool.clone(), jwt_manager))),
+        )
+        .merge(Router::from(routes::notes()).with_state(Arc::new(NoteService::new(pool, config))))
+}
+
+fn spec() -> serde_json::Value {
+    serde_json::to_value(routes::openapi()).unwrap()
+}
+
+/// `/api/v1/notes/{id}` -> `/api/v1/notes/00000000-0000-0000-0000-000000000000`
+fn concrete_path(path: &str) -> String {
+    path.split('/')
+        .map(|segment| {
+            if segment.starts_with('{') {
+                uuid::Uuid::nil().to_string()
+            } else {
+                segment.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+async fn status_of(app: &Router, method: &str, path: &str) -> StatusCode {
+    let request = Request::builder()
+        .method(Method::from_bytes(method.to_uppercase().as_bytes()).unwrap())
+        .uri(concrete_path(path))
+        .body(Body::empty())
+        .unwrap();
+    app.clone().oneshot(request).await.unwrap().status()
+}
+
+#[test]
+fn spec_is_openapi_3_1() {
+    let spec = spec();
+    assert!(spec["openapi"].as_str().unwrap().starts_with("3.1"));
+    assert_eq!(spec["info"]["version"], noteflow_backend::VERSION);
+    assert!(spec["components"]["securitySchemes"]["bearer_auth"].is_object());
+}
+
+#[tokio::test]
+async fn every_documented_operation_is_routed() {
+    let app = app();
+    let spec = spec();
+    let paths = spec["paths"].as_object().unwrap();
+    assert!(!paths.is_empty());
+
+    for (path, item) in paths {
+        for method in METHODS.iter().filter(|m| item.get(**m).is_some()) {
+            let status = status_of(&app, method, path).await;
+            assert!(
+                status != StatusCode::NOT_FOUND && status != StatusCode::METHOD_NOT_ALLOWED,
+                "{} {} is documented but not routed ({})",
+                method.to_uppercase(),
+                path,
+                status
+            );
+        }
+    }
+}
+
+#[tokio::test]
+async fn documented_paths_serve_no_undocumented_methods() {
+    let app = app();
+    let spec = spec();
+
+    for (path, item) in spec["paths"].as_object().unwrap() {
+        for method in METHODS.iter().filter(|m| item.get(**m).is_none()) {
+            assert_eq!(
+                status_of(&app, method, path).await,
+                StatusCode::METHOD_NOT_ALLOWED,
+                "{} {} is routed but not documented",
+                method.to_uppercase(),
+                path
+            );
+        }
+    }
+}
+
+#[test]
+fn path_parameters_are_declared() {
+    let spec = spec();
+
+    for (path, item) in spec["paths"].as_object().unwrap() {
+        let placeholders: Vec<&str> = path
+            .split('/')
+            .filter_map(|s| s.strip_prefix('{')?.strip_suffix('}'))
+            .collect();
+
+        for method in METHODS.iter().filter(|m| item.get(**m).is_some()) {
+            let declared: Vec<&str> = item[*method]["parameters"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter(|p| p["in"] == "path")
+                .filter_map(|p| p["name"].as_str())
+                .collect();
+            for name in &placeholders {
+                assert!(
+                    declared.contains(name),
+                    "{} {} does not declare path parameter '{}'",
+                    method.to_uppercase(),
+                    path,
+                    name
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,345 @@
+//! Allow/deny decisions, window expiry and response headers of the rate
+//! limit backends and the middleware applying their policies.
+
+use axum::{
+    body::Body,
+    extract::ConnectInfo,
+    http::{header, Method, Request, StatusCode},
+    middleware,
+    routing::get,
+    Router,
+};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tower::ServiceExt;
+
+use noteflow_backend::config::Config;
+use noteflow_backend::middleware::{
+    auth_failure_limit_middleware, rate_limit_middleware, GcraRateLimiter, InMemoryRateLimiter,
+    RateLimitBackend, RateLimitDecision, RateLimitPolicies, RateLimitPolicy, RateLimiter,
+    RATELIMIT_LIMIT, RATELIMIT_REMAINING, RATELIMIT_RESET,
+};
+
+const WINDOW: Duration = Duration::from_secs(60);
+
+fn backends() -> Vec<(&'static str, Arc<dyn RateLimitBackend>)> {
+    vec![
+        ("sliding_log", Arc::new(InMemoryRateLimiter::new())),
+        ("gcra", Arc::new(GcraRateLimiter::new())),
+    ]
+}
+
+#[tokio::test]
+async fn allows_up_to_the_limit_then_denies() {
+    for (name, backend) in backends() {
+        for expected_remaining in (0..3).rev() {
+            let decision = backend.check("k", 3, WINDOW, 1).await.unwrap();
+            assert!(decision.allowed, "{}", name);
+            assert_eq!(decision.limit, 3, "{}", name);
+            assert_eq!(decision.remaining, expected_remaining, "{}", name);
+            assert_eq!(decision.retry_after, None, "{}", name);
+        }
+
+        let denied = backend.check("k", 3, WINDOW, 1).await.unwrap();
+        assert!(!denied.allowed, "{}", name);
+        assert_eq!(denied.remaining, 0, "{}", name);
+        let retry_after = denied.retry_after.expect("denials say when to retry");
+        assert!(retry_after > Duration::ZERO && retry_after <= WINDOW, "{}", name);
+    }
+}
+
+#[tokio::test]
+async fn keys_have_separate_budgets() {
+    for (name, backend) in backends() {
+        assert!(backend.check("a", 1, WINDOW, 1).await.unwrap().allowed, "{}", name);
+        assert!(!backend.check("a", 1, WINDOW, 1).await.unwrap().allowed, "{}", name);
+        assert!(backend.check("b", 1, WINDOW, 1).await.unwrap().allowed, "{}", name);
+    }
+}
+
+#[tokio::test]
+async fn cost_draws_several_units() {
+    for (name, backend) in backends() {
+        let decision = backend.check("k", 10, WINDOW, 4).await.unwrap();
+        assert!(decision.allowed, "{}", name);
+        assert_eq!(decision.remaining, 6, "{}", name);
+
+        assert!(backend.check("k", 10, WINDOW, 4).await.unwrap().allowed, "{}", name);
+        // Two units left, so a third write no longer fits but a read does
+        assert!(!backend.check("k", 10, WINDOW, 4).await.unwrap().allowed, "{}", name);
+        assert!(backend.check("k", 10, WINDOW, 1).await.unwrap().allowed, "{}", name);
+    }
+}
+
+#[tokio::test]
+async fn budget_returns_once_the_window_passes() {
+    let window = Duration::from_millis(100);
+    for (name, backend) in backends() {
+        assert!(backend.check("k", 2, window, 2).await.unwrap().allowed, "{}", name);
+        let denied = backend.check("k", 2, window, 1).await.unwrap();
+        assert!(!denied.allowed, "{}", name);
+        assert!(denied.retry_after.unwrap() <= window, "{}", name);
+
+        tokio::time::sleep(window + Duration::from_millis(20)).await;
+
+        let decision = backend.check("k", 2, window, 1).await.unwrap();
+        assert!(decision.allowed, "{}", name);
+        assert_eq!(decision.remaining, 1, "{}", name);
+    }
+}
+
+#[tokio::test]
+async fn cleanup_keeps_live_budgets() {
+    for (name, backend) in backends() {
+        assert!(backend.check("k", 1, WINDOW, 1).await.unwrap().allowed, "{}", name);
+        backend.cleanup().await;
+        assert!(!backend.check("k", 1, WINDOW, 1).await.unwrap().allowed, "{}", name);
+    }
+}
+
+#[tokio::test]
+async fn zero_cost_checks_record_nothing() {
+    for (name, backend) in backends() {
+        let fresh = backend.check("k", 2, WINDOW, 0).await.unwrap();
+        assert_eq!(fresh.remaining, 2, "{}", name);
+
+        backend.check("k", 2, WINDOW, 2).await.unwrap();
+        for _ in 0..3 {
+            let spent = backend.check("k", 2, WINDOW, 0).await.unwrap();
+            assert_eq!(spent.remaining, 0, "{}", name);
+        }
+    }
+}
+
+#[test]
+fn headers_round_up_to_whole_seconds() {
+    let mut headers = axum::http::HeaderMap::new();
+    RateLimitDecision {
+        allowed: false,
+        limit: 10,
+        remaining: 0,
+        reset_after: Duration::from_millis(1_200),
+        retry_after: Some(Duration::from_millis(1)),
+    }
+    .apply_headers(&mut headers);
+
+    assert_eq!(headers[RATELIMIT_LIMIT], "10");
+    assert_eq!(headers[RATELIMIT_REMAINING], "0");
+    assert_eq!(headers[RATELIMIT_RESET], "2");
+    assert_eq!(headers[header::RETRY_AFTER], "1");
+}
+
+#[test]
+fn allowed_decisions_have_no_retry_after() {
+    let mut headers = axum::http::HeaderMap::new();
+    RateLimitDecision {
+        allowed: true,
+        limit: 10,
+        remaining: 9,
+        reset_after: WINDOW,
+        retry_after: None,
+    }
+    .apply_headers(&mut headers);
+
+    assert_eq!(headers[RATELIMIT_RESET], "60");
+    assert!(!headers.contains_key(header::RETRY_AFTER));
+}
+
+fn policies() -> RateLimitPolicies {
+    let default = RateLimitPolicy::per_minute("default", 5);
+    RateLimitPolicies::new(default.clone())
+        .route("/login", RateLimitPolicy::per_minute("login", 1))
+        .route_query(Method::GET, "/items", "q", RateLimitPolicy::per_minute("search", 3))
+        .route_method(Method::POST, "/items", default.with_cost(2))
+}
+
+#[test]
+fn policies_match_path_and_method() {
+    let policies = policies();
+
+    assert_eq!(policies.resolve(&Method::POST, Some("/login"), None).name, "login");
+    assert_eq!(policies.resolve(&Method::GET, Some("/login"), None).name, "login");
+    assert_eq!(policies.resolve(&Method::POST, Some("/items"), None).cost, 2);
+    assert_eq!(policies.resolve(&Method::GET, Some("/items"), None).cost, 1);
+    assert_eq!(policies.resolve(&Method::GET, Some("/other"), None).name, "default");
+    assert_eq!(policies.resolve(&Method::GET, None, None).name, "default");
+}
+
+#[test]
+fn policies_can_require_a_query_parameter() {
+    let policies = policies();
+    let name = |method: Method, query| policies.resolve(&method, Some("/items"), query).name;
+
+    assert_eq!(name(Method::GET, Some("q=plans")), "search");
+    assert_eq!(name(Method::GET, Some("limit=5&q=weekly%20plans")), "search");
+    assert_eq!(name(Method::GET, Some("q=")), "default");
+    assert_eq!(name(Method::GET, Some("q=%20")), "default");
+    assert_eq!(name(Method::GET, Some("qq=plans")), "default");
+    assert_eq!(name(Method::GET, None), "default");
+    assert_eq!(name(Method::POST, Some("q=plans")), "default");
+}
+
+fn app() -> Router {
+    let limiter = Arc::new(RateLimiter::in_memory(policies()));
+    Router::new()
+        .route("/login", get(|| async { "ok" }))
+        .route("/items", get(|| async { "ok" }).post(|| async { "ok" }))
+        .layer(middleware::from_fn_with_state(limiter, rate_limit_middleware))
+}
+
+async fn send(app: &Router, method: Method, path: &str, ip: [u8; 4]) -> axum::response::Response {
+    let mut request = Request::builder()
+        .method(method)
+        .uri(path)
+        .body(Body::empty())
+        .unwrap();
+    request
+        .extensions_mut()
+        .insert(ConnectInfo(SocketAddr::from((ip, 40000))));
+    app.clone().oneshot(request).await.unwrap()
+}
+
+#[tokio::test]
+async fn middleware_sets_headers_and_rejects_over_the_limit() {
+    let app = app();
+
+    let ok = send(&app, Method::GET, "/login", [10, 0, 0, 1]).await;
+    assert_eq!(ok.status(), StatusCode::OK);
+    assert_eq!(ok.headers()[RATELIMIT_LIMIT], "1");
+    assert_eq!(ok.headers()[RATELIMIT_REMAINING], "0");
+    assert_eq!(ok.headers()[RATELIMIT_RESET], "60");
+    assert!(!ok.headers().contains_key(header::RETRY_AFTER));
+
+    let limited = send(&app, Method::GET, "/login", [10, 0, 0, 1]).await;
+    assert_eq!(limited.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(limited.headers()[RATELIMIT_REMAINING], "0");
+    let retry_after: u64 = limited.headers()[header::RETRY_AFTER]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!((1..=60).contains(&retry_after));
+
+    // Another client has its own budget
+    let other = send(&app, Method::GET, "/login", [10, 0, 0, 2]).await;
+    assert_eq!(other.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn middleware_charges_the_route_cost() {
+    let app = app();
+
+    let write = send(&app, Method::POST, "/items", [10, 0, 0, 1]).await;
+    assert_eq!(write.headers()[RATELIMIT_LIMIT], "5");
+    assert_eq!(write.headers()[RATELIMIT_REMAINING], "3");
+
+    // Reads share the bucket at a cost of one
+    let read = send(&app, Method::GET, "/items", [10, 0, 0, 1]).await;
+    assert_eq!(read.headers()[RATELIMIT_REMAINING], "2");
+}
+
+/// `/private` authenticates with the header `x-ok`, like `auth_middleware`
+fn guarded_app() -> Router {
+    let limiter = Arc::new(RateLimiter::in_memory(RateLimitPolicies::new(
+        RateLimitPolicy::per_minute("auth_failures", 2),
+    )));
+    Router::new()
+        .route(
+            "/private",
+            get(|headers: axum::http::HeaderMap| async move {
+                if headers.contains_key("x-ok") {
+                    StatusCode::OK
+                } else {
+                    StatusCode::UNAUTHORIZED
+                }
+            }),
+        )
+        .layer(middleware::from_fn_with_state(
+            limiter,
+            auth_failure_limit_middleware,
+        ))
+}
+
+async fn send_private(app: &Router, authenticated: bool) -> axum::response::Response {
+    let mut request = Request::builder().uri("/private");
+    if authenticated {
+        request = request.header("x-ok", "1");
+    }
+    let mut request = request.body(Body::empty()).unwrap();
+    request
+        .extensions_mut()
+        .insert(ConnectInfo(SocketAddr::from(([10, 0, 0, 1], 40000))));
+    app.clone().oneshot(request).await.unwrap()
+}
+
+#[tokio::test]
+async fn authenticated_requests_cost_no_failure_budget() {
+    let app = guarded_app();
+    for _ in 0..5 {
+        let response = send_private(&app, true).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!response.headers().contains_key(RATELIMIT_LIMIT));
+    }
+    assert_eq!(send_private(&app, false).await.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn failed_authentications_lock_out_the_ip() {
+    let app = guarded_app();
+
+    let first = send_private(&app, false).await;
+    assert_eq!(first.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(first.headers()[RATELIMIT_REMAINING], "1");
+    let second = send_private(&app, false).await;
+    assert_eq!(second.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(second.headers()[RATELIMIT_REMAINING], "0");
+
+    // Even valid credentials are turned away until failures expire
+    let locked = send_private(&app, true).await;
+    assert_eq!(locked.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(locked.headers().contains_key(header::RETRY_AFTER));
+}
+
+/// The notes listing behind the real authenticated policies, with defaults
+fn notes_app() -> Router {
+    let config_path = std::env::temp_dir().join(format!(
+        "noteflow-rate-limit-test-{}.toml",
+        std::process::id()
+    ));
+    std::fs::write(
+        &config_path,
+        r#"
+        database_url = "postgres://localhost/noteflow"
+        redis_url = "redis://localhost"
+        jwt_secret = "rate-limit-test-secret-0123456789abcdef"
+        "#,
+    )
+    .unwrap();
+    let config = Config::load(Some(&config_path)).unwrap();
+    std::fs::remove_file(&config_path).ok();
+
+    let limiter = Arc::new(RateLimiter::in_memory(RateLimitPolicies::authenticated(&config)));
+    Router::new()
+        .route("/api/v1/notes", get(|| async { "ok" }))
+        .layer(middleware::from_fn_with_state(limiter, rate_limit_middleware))
+}
+
+#[tokio::test]
+async fn searches_have_a_budget_of_their_own() {
+    let app = notes_app();
+
+    for _ in 0..30 {
+        let search = send(&app, Method::GET, "/api/v1/notes?q=plans", [10, 0, 0, 1]).await;
+        assert_eq!(search.status(), StatusCode::OK);
+        assert_eq!(search.headers()[RATELIMIT_LIMIT], "30");
+    }
+    let search = send(&app, Method::GET, "/api/v1/notes?q=plans", [10, 0, 0, 1]).await;
+    assert_eq!(search.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    // Plain listing draws from the general budget, untouched by the searches
+    let list = send(&app, Method::GET, "/api/v1/notes?limit=20", [10, 0, 0, 1]).await;
+    assert_eq!(list.status(), StatusCode::OK);
+    assert_eq!(list.headers()[RATELIMIT_LIMIT], "100");
+    assert_eq!(list.headers()[RATELIMIT_REMAINING], "99");
+}
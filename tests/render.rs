@@ -0,0 +1,84 @@
+//! Markdown rendering and the HTML sanitizer that makes it safe to embed.
+
+use noteflow_backend::utils::text::render_html;
+
+#[test]
+fn scripts_are_removed_with_their_contents() {
+    let html = render_html("before\n\n<script>alert(1)</script>\n\nafter");
+    assert!(!html.contains("script"), "{}", html);
+    assert!(!html.contains("alert"), "{}", html);
+    assert!(html.contains("before") && html.contains("after"), "{}", html);
+}
+
+#[test]
+fn event_handler_attributes_are_removed() {
+    let html = render_html(r#"<img src="x.png" onerror="alert(1)"> <a href="https://example.com" onclick="steal()">c</a>"#);
+    assert!(!html.contains("onerror"), "{}", html);
+    assert!(!html.contains("onclick"), "{}", html);
+    assert!(html.contains(r#"<img src="x.png">"#), "{}", html);
+}
+
+#[test]
+fn javascript_and_data_links_lose_their_targets() {
+    for markdown in [
+        "[a](javascript:alert(1))",
+        r#"<a href="JavaScript:alert(1)">a</a>"#,
+        "[a](data:text/html;base64,PHNjcmlwdD4=)",
+        r#"<a href="data:text/html,<script>alert(1)</script>">a</a>"#,
+    ] {
+        let html = render_html(markdown);
+        assert!(!html.contains("href"), "{} rendered as {}", markdown, html);
+        assert!(html.contains(">a</a>"), "{} rendered as {}", markdown, html);
+    }
+}
+
+#[test]
+fn unexpected_classes_and_styles_are_dropped() {
+    let html = render_html(r#"<code class="evil language-rust">x</code><p style="position: fixed">s</p>"#);
+    assert!(html.contains(r#"<code class="language-rust">"#), "{}", html);
+    assert!(!html.contains("evil"), "{}", html);
+    assert!(!html.contains("style"), "{}", html);
+}
+
+#[test]
+fn footnote_ids_and_links_get_the_prefix() {
+    let html = render_html("Text[^1]\n\n[^1]: The note <div id=\"main\">x</div>");
+    assert!(html.contains(r##"<a href="#note-1""##), "{}", html);
+    assert!(html.contains(r#"class="footnote-definition" id="note-1""#), "{}", html);
+    // Ids from raw HTML can't clobber the page's own either
+    assert!(html.contains(r#"id="note-main""#), "{}", html);
+    assert!(!html.contains(r#"id="main""#), "{}", html);
+}
+
+#[test]
+fn task_lists_render_as_disabled_checkboxes() {
+    let html = render_html("- [x] done\n- [ ] todo\n\n<input type=\"text\" name=\"password\">");
+    assert!(html.contains(r#"<input checked="" disabled="" type="checkbox">"#), "{}", html);
+    assert!(html.contains(r#"<input disabled="" type="checkbox">"#), "{}", html);
+    // Other inputs in the note become inert checkboxes too
+    assert!(!html.contains("text"), "{}", html);
+    assert!(!html.contains("password"), "{}", html);
+}
+
+#[test]
+fn tables_keep_their_alignment() {
+    let html = render_html("| a | b |\n|:--|--:|\n| 1 | 2 |");
+    assert!(html.contains("<table>"), "{}", html);
+    assert!(html.contains(r#"<th style="text-align: left">a</th>"#), "{}", html);
+    assert!(html.contains(r#"<td style="text-align: right">2</td>"#), "{}", html);
+}
+
+#[test]
+fn links_open_without_referrer_or_opener() {
+    let html = render_html("[ex](https://example.com) and <https://example.org>");
+    assert!(
+        html.contains(r#"<a href="https://example.com" rel="noopener noreferrer">ex</a>"#),
+        "{}",
+        html
+    );
+    assert!(
+        html.contains(r#"<a href="https://example.org" rel="noopener noreferrer">"#),
+        "{}",
+        html
+    );
+}
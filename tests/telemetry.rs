@@ -0,0 +1,104 @@
+//! Spans recorded around database queries.
+
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::Subscriber;
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use uuid::Uuid;
+
+use noteflow_backend::{config::Config, models::CreateNoteRequest, services::NoteService};
+
+type Fields = HashMap<String, String>;
+
+/// Name and fields of every span opened while it is installed
+#[derive(Clone, Default)]
+struct Recorder(Arc<Mutex<Vec<(&'static str, Fields)>>>);
+
+impl<S: Subscriber> Layer<S> for Recorder {
+    fn on_new_span(&self, attrs: &Attributes<'_>, _: &Id, _: Context<'_, S>) {
+        let mut fields = FieldVisitor::default();
+        attrs.record(&mut fields);
+        self.0.lock().unwrap().push((attrs.metadata().name(), fields.0));
+    }
+}
+
+#[derive(Default)]
+struct FieldVisitor(Fields);
+
+impl Visit for FieldVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0.insert(field.name().to_string(), format!("{:?}", value));
+    }
+}
+
+fn service(pool: &PgPool) -> NoteService {
+    let config_path = std::env::temp_dir().join(format!(
+        "noteflow-telemetry-test-{}.toml",
+        std::process::id()
+    ));
+    std::fs::write(
+        &config_path,
+        r#"
+        database_url = "postgres://localhost/noteflow"
+        redis_url = "redis://localhost"
+        jwt_secret = "telemetry-test-secret-0123456789abcdef"
+        "#,
+    )
+    .unwrap();
+    let config = Config::load(Some(&config_path)).unwrap();
+    std::fs::remove_file(&config_path).ok();
+    NoteService::new(pool.clone(), config)
+}
+
+#[sqlx::test]
+async fn each_query_gets_its_own_span(pool: PgPool) {
+    let service = service(&pool);
+    let user_id: Uuid = sqlx::query_scalar(
+        "INSERT INTO users (email, password_hash, display_name) VALUES ('a@example.com', 'x', 'A') RETURNING id",
+    )
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+
+    let recorder = Recorder::default();
+    let subscriber = tracing_subscriber::registry().with(recorder.clone());
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let note = service
+        .create(
+            user_id,
+            CreateNoteRequest {
+                title: "Plan".to_string(),
+                content: Some("Ship it".to_string()),
+                notebook_id: None,
+            },
+        )
+        .await
+        .unwrap();
+    service.get(note.id, user_id).await.unwrap();
+
+    let spans = recorder.0.lock().unwrap();
+    let queries: Vec<&str> = spans
+        .iter()
+        .filter(|(name, _)| *name == "db.query")
+        .map(|(_, fields)| fields["otel.name"].as_str())
+        .collect();
+    // Create counts the user's notes and inserts; get loads the note and its tags
+    assert_eq!(
+        queries,
+        ["SELECT notes", "INSERT notes", "SELECT notes", "SELECT tags"]
+    );
+
+    for (_, fields) in spans.iter().filter(|(name, _)| *name == "db.query") {
+        assert_eq!(fields["db.system"], "postgresql");
+        assert_eq!(fields["otel.kind"], "client");
+        assert!(fields["otel.name"].starts_with(&fields["db.operation"]), "{:?}", fields);
+    }
+}
@@ -0,0 +1,151 @@
+//! The in-process cache of authenticated users, on its own and behind
+//! `AuthService::authenticate`.
+
+use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+use noteflow_backend::{
+    models::AuthUser,
+    services::{AuthService, UserCache},
+    utils::{errors::ErrorCode, jwt::JwtManager},
+};
+
+fn auth_user(name: &str) -> AuthUser {
+    AuthUser {
+        id: Uuid::new_v4(),
+        email: format!("{}@example.com", name),
+        display_name: name.to_string(),
+    }
+}
+
+fn auth_service(pool: &PgPool) -> AuthService {
+    let jwt = JwtManager::new("user-cache-test-secret-0123456789abcdef".to_string(), 60, 60);
+    AuthService::new(pool.clone(), Arc::new(jwt)).with_user_cache(UserCache::memory(60))
+}
+
+async fn user(pool: &PgPool, disabled: bool) -> Uuid {
+    sqlx::query_scalar(
+        "INSERT INTO users (email, password_hash, display_name, disabled_at)
+         VALUES ($1, 'x', 'Test', CASE WHEN $2 THEN NOW() END) RETURNING id",
+    )
+    .bind(format!("{}@example.com", Uuid::new_v4()))
+    .bind(disabled)
+    .fetch_one(pool)
+    .await
+    .unwrap()
+}
+
+async fn delete_user(pool: &PgPool, user_id: Uuid) {
+    sqlx::query("DELETE FROM users WHERE id = $1")
+        .bind(user_id)
+        .execute(pool)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn users_are_served_until_invalidated() {
+    let cache = UserCache::memory(60);
+    let alice = auth_user("alice");
+    let bob = auth_user("bob");
+
+    assert!(cache.get(alice.id).await.is_none());
+    cache.put(&alice).await;
+    cache.put(&bob).await;
+    assert_eq!(cache.get(alice.id).await.map(|u| u.id), Some(alice.id));
+
+    cache.invalidate(alice.id).await;
+    assert!(cache.get(alice.id).await.is_none());
+    // Other users are left alone
+    assert_eq!(cache.get(bob.id).await.map(|u| u.id), Some(bob.id));
+}
+
+#[tokio::test]
+async fn entries_expire_after_the_ttl() {
+    let cache = UserCache::memory(1);
+    let alice = auth_user("alice");
+
+    cache.put(&alice).await;
+    assert!(cache.get(alice.id).await.is_some());
+
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    assert!(cache.get(alice.id).await.is_none());
+}
+
+#[tokio::test]
+async fn a_later_put_replaces_the_entry() {
+    let cache = UserCache::memory(60);
+    let alice = auth_user("alice");
+    let renamed = AuthUser {
+        display_name: "Alice B.".to_string(),
+        ..alice.clone()
+    };
+
+    cache.put(&alice).await;
+    cache.put(&renamed).await;
+    assert_eq!(
+        cache.get(alice.id).await.map(|u| u.display_name),
+        Some(renamed.display_name)
+    );
+}
+
+#[sqlx::test]
+async fn authenticated_users_are_reused_from_the_cache(pool: PgPool) {
+    let service = auth_service(&pool);
+    let user_id = user(&pool, false).await;
+
+    assert_eq!(service.authenticate(user_id).await.unwrap().id, user_id);
+
+    // Still served once the row is gone, so the second lookup never read it
+    delete_user(&pool, user_id).await;
+    assert_eq!(service.authenticate(user_id).await.unwrap().id, user_id);
+
+    service.invalidate_cached_user(user_id).await;
+    assert_eq!(
+        service.authenticate(user_id).await.unwrap_err().code(),
+        ErrorCode::InvalidToken
+    );
+}
+
+#[sqlx::test]
+async fn disabled_users_are_never_cached(pool: PgPool) {
+    let service = auth_service(&pool);
+    let user_id = user(&pool, true).await;
+
+    for _ in 0..2 {
+        assert_eq!(
+            service.authenticate(user_id).await.unwrap_err().code(),
+            ErrorCode::AccountDisabled
+        );
+    }
+
+    // Nothing was kept from those lookups, so a missing row is noticed at once
+    delete_user(&pool, user_id).await;
+    assert_eq!(
+        service.authenticate(user_id).await.unwrap_err().code(),
+        ErrorCode::InvalidToken
+    );
+}
+
+#[sqlx::test]
+async fn disabling_a_cached_user_takes_effect_at_once(pool: PgPool) {
+    let service = auth_service(&pool);
+    let user_id = user(&pool, false).await;
+    let email: String = sqlx::query_scalar("SELECT email FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+    service.authenticate(user_id).await.unwrap();
+    service.set_disabled(&email, true).await.unwrap();
+    assert_eq!(
+        service.authenticate(user_id).await.unwrap_err().code(),
+        ErrorCode::AccountDisabled
+    );
+
+    service.set_disabled(&email, false).await.unwrap();
+    assert_eq!(service.authenticate(user_id).await.unwrap().id, user_id);
+}